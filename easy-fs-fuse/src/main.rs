@@ -0,0 +1,78 @@
+//! Packs the user programs into an easy-fs image the kernel can load them from
+
+use std::fs::{read_dir, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use clap::Parser;
+use easy_fs::{BlockDevice, EasyFileSystem, FsError, InodeType, BLOCK_SIZE};
+
+/// 32 MiB
+const TOTAL_BLOCKS: u32 = 64 * 1024;
+/// 4096 inodes
+const INODE_BITMAP_BLOCKS: u32 = 1;
+const IMAGE_NAME: &str = "fs.img";
+
+#[derive(Parser)]
+#[command(about = "Pack the user programs into an easy-fs image")]
+struct Args {
+    /// Directory of the program sources, one program per file
+    #[arg(short, long)]
+    source: PathBuf,
+    /// Directory of the built programs, the image is written here as well
+    #[arg(short, long)]
+    target: PathBuf
+}
+
+/// An image file on the host used as a block device
+struct BlockFile(Mutex<File>);
+
+impl BlockDevice for BlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), FsError> {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
+            .and_then(|_| file.read_exact(buf))
+            .map_err(|_| FsError::Io(block_id))
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), FsError> {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
+            .and_then(|_| file.write_all(buf))
+            .map_err(|_| FsError::Io(block_id))
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let image = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(args.target.join(IMAGE_NAME))?;
+    image.set_len(TOTAL_BLOCKS as u64 * BLOCK_SIZE as u64)?;
+    let device: Arc<dyn BlockDevice> = Arc::new(BlockFile(Mutex::new(image)));
+    let fs = EasyFileSystem::create(device, TOTAL_BLOCKS, INODE_BITMAP_BLOCKS)?;
+    let root = EasyFileSystem::root_inode(&fs);
+
+    let mut apps: Vec<String> = read_dir(&args.source)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.path().file_stem().and_then(|stem| stem.to_str()).map(String::from))
+        .collect();
+    apps.sort();
+    for app in apps {
+        let mut data = Vec::new();
+        File::open(args.target.join(&app))?.read_to_end(&mut data)?;
+        let inode = root.create(&app, InodeType::File)?;
+        inode.write_at(0, &data)?;
+        // read it back, so a broken image is caught here rather than at boot
+        let mut check = vec![0u8; data.len()];
+        if inode.read_at(0, &mut check)? != data.len() || check != data {
+            return Err(format!("{} corrupted in the image", app).into());
+        }
+        println!("{}: {} bytes", app, data.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use alloc::sync::Arc;
+
+use crate::{BlockDevice, FsError, BLOCK_SIZE};
+
+const BITS_PER_BLOCK: usize = BLOCK_SIZE * 8;
+
+/// Allocation state of `capacity` objects, kept in the blocks starting at `start_block`
+pub struct Bitmap {
+    start_block: usize,
+    blocks: usize,
+    capacity: usize
+}
+
+impl Bitmap {
+    pub fn new(start_block: usize, blocks: usize, capacity: usize) -> Self {
+        assert!(capacity <= blocks * BITS_PER_BLOCK);
+        Bitmap { start_block, blocks, capacity }
+    }
+
+    /// Mark the first free bit as used, `None` if all are taken
+    pub fn alloc(&self, device: &Arc<dyn BlockDevice>) -> Result<Option<usize>, FsError> {
+        let mut buf = [0u8; BLOCK_SIZE];
+        for block in 0..self.blocks {
+            let block_id = self.start_block + block;
+            device.read_block(block_id, &mut buf)?;
+            let Some((byte, value)) = buf.iter().enumerate().find(|(_, value)| **value != u8::MAX) else {
+                continue;
+            };
+            let bit = block * BITS_PER_BLOCK + byte * 8 + value.trailing_ones() as usize;
+            if bit >= self.capacity {
+                return Ok(None);
+            }
+            buf[byte] |= 1 << value.trailing_ones();
+            device.write_block(block_id, &buf)?;
+            return Ok(Some(bit));
+        }
+        Ok(None)
+    }
+
+    pub fn dealloc(&self, device: &Arc<dyn BlockDevice>, bit: usize) -> Result<(), FsError> {
+        if bit >= self.capacity {
+            return Err(FsError::Corrupted);
+        }
+        let mut buf = [0u8; BLOCK_SIZE];
+        let block_id = self.start_block + bit / BITS_PER_BLOCK;
+        let (byte, mask) = (bit % BITS_PER_BLOCK / 8, 1u8 << (bit % 8));
+        device.read_block(block_id, &mut buf)?;
+        if buf[byte] & mask == 0 {
+            return Err(FsError::Corrupted);
+        }
+        buf[byte] &= !mask;
+        device.write_block(block_id, &buf)
+    }
+}
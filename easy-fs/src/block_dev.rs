@@ -0,0 +1,7 @@
+use crate::FsError;
+
+/// Storage the file system lives on, accessed in blocks of `BLOCK_SIZE` bytes
+pub trait BlockDevice: Send + Sync {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), FsError>;
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), FsError>;
+}
@@ -0,0 +1,254 @@
+use alloc::sync::Arc;
+use spin::Mutex;
+
+use crate::bitmap::Bitmap;
+use crate::layout::{DiskInode, InodeType, SuperBlock, DISK_INODE_SIZE, EFS_MAGIC, INODES_PER_BLOCK,
+                    INODE_DIRECT_COUNT, INODE_INDIRECT1_BOUND, INODE_INDIRECT2_BOUND, INODE_INDIRECT_COUNT};
+use crate::vfs::Inode;
+use crate::{BlockDevice, FsError, BLOCK_SIZE};
+
+pub const ROOT_INODE_ID: u32 = 0;
+
+/// Where a block pointer of an inode is stored
+#[derive(Clone, Copy)]
+enum Slot {
+    Direct(usize),
+    /// Index in the indirect block with the given id
+    Indirect(usize, usize)
+}
+
+pub struct EasyFileSystem {
+    device: Arc<dyn BlockDevice>,
+    inode_bitmap: Bitmap,
+    data_bitmap: Bitmap,
+    inode_area_start: usize,
+    data_area_start: usize
+}
+
+impl EasyFileSystem {
+    /// Format `device` with `total_blocks` blocks, `inode_bitmap_blocks` decides the number of inodes
+    pub fn create(device: Arc<dyn BlockDevice>, total_blocks: u32, inode_bitmap_blocks: u32) -> Result<Arc<Mutex<Self>>, FsError> {
+        let inode_count = inode_bitmap_blocks as usize * BLOCK_SIZE * 8;
+        let inode_area_blocks = inode_count.div_ceil(INODES_PER_BLOCK) as u32;
+        let rest = total_blocks.checked_sub(1 + inode_bitmap_blocks + inode_area_blocks).ok_or(FsError::NoSpace)?;
+        // every data bitmap block covers itself plus BLOCK_SIZE * 8 data blocks
+        let data_bitmap_blocks = rest.div_ceil(BLOCK_SIZE as u32 * 8 + 1);
+        let super_block = SuperBlock {
+            magic: EFS_MAGIC,
+            total_blocks,
+            inode_bitmap_blocks,
+            inode_area_blocks,
+            data_bitmap_blocks,
+            data_area_blocks: rest - data_bitmap_blocks
+        };
+        let zero = [0u8; BLOCK_SIZE];
+        for block_id in 1..(1 + inode_bitmap_blocks + inode_area_blocks + data_bitmap_blocks) as usize {
+            device.write_block(block_id, &zero)?;
+        }
+        let mut fs = Self::from_super_block(device, &super_block);
+        fs.store(0, 0, &super_block)?;
+        let root = fs.alloc_inode()?;
+        assert_eq!(root, ROOT_INODE_ID);
+        fs.store_inode(root, &DiskInode::new(InodeType::Directory))?;
+        Ok(Arc::new(Mutex::new(fs)))
+    }
+
+    pub fn open(device: Arc<dyn BlockDevice>) -> Result<Arc<Mutex<Self>>, FsError> {
+        let mut buf = [0u8; BLOCK_SIZE];
+        device.read_block(0, &mut buf)?;
+        let super_block: SuperBlock = unsafe { core::ptr::read_unaligned(buf.as_ptr() as *const SuperBlock) };
+        if !super_block.is_valid() {
+            return Err(FsError::BadMagic);
+        }
+        Ok(Arc::new(Mutex::new(Self::from_super_block(device, &super_block))))
+    }
+
+    fn from_super_block(device: Arc<dyn BlockDevice>, super_block: &SuperBlock) -> Self {
+        let inode_bitmap_start = 1;
+        let inode_area_start = inode_bitmap_start + super_block.inode_bitmap_blocks as usize;
+        let data_bitmap_start = inode_area_start + super_block.inode_area_blocks as usize;
+        let data_area_start = data_bitmap_start + super_block.data_bitmap_blocks as usize;
+        EasyFileSystem {
+            device,
+            inode_bitmap: Bitmap::new(inode_bitmap_start, super_block.inode_bitmap_blocks as usize,
+                                      super_block.inode_area_blocks as usize * INODES_PER_BLOCK),
+            data_bitmap: Bitmap::new(data_bitmap_start, super_block.data_bitmap_blocks as usize,
+                                     super_block.data_area_blocks as usize),
+            inode_area_start,
+            data_area_start
+        }
+    }
+
+    pub fn root_inode(fs: &Arc<Mutex<Self>>) -> Inode {
+        Inode::new(ROOT_INODE_ID, fs.clone())
+    }
+
+    fn load<T: Copy>(&self, block_id: usize, offset: usize) -> Result<T, FsError> {
+        assert!(offset + size_of::<T>() <= BLOCK_SIZE);
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.device.read_block(block_id, &mut buf)?;
+        Ok(unsafe { core::ptr::read_unaligned(buf[offset..].as_ptr() as *const T) })
+    }
+
+    fn store<T: Copy>(&self, block_id: usize, offset: usize, value: &T) -> Result<(), FsError> {
+        assert!(offset + size_of::<T>() <= BLOCK_SIZE);
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.device.read_block(block_id, &mut buf)?;
+        unsafe { core::ptr::write_unaligned(buf[offset..].as_mut_ptr() as *mut T, *value) };
+        self.device.write_block(block_id, &buf)
+    }
+
+    fn inode_position(&self, inode_id: u32) -> (usize, usize) {
+        let inode_id = inode_id as usize;
+        (self.inode_area_start + inode_id / INODES_PER_BLOCK, inode_id % INODES_PER_BLOCK * DISK_INODE_SIZE)
+    }
+
+    pub(crate) fn load_inode(&self, inode_id: u32) -> Result<DiskInode, FsError> {
+        let (block_id, offset) = self.inode_position(inode_id);
+        self.load(block_id, offset)
+    }
+
+    pub(crate) fn store_inode(&self, inode_id: u32, inode: &DiskInode) -> Result<(), FsError> {
+        let (block_id, offset) = self.inode_position(inode_id);
+        self.store(block_id, offset, inode)
+    }
+
+    pub(crate) fn alloc_inode(&mut self) -> Result<u32, FsError> {
+        self.inode_bitmap.alloc(&self.device)?.map(|id| id as u32).ok_or(FsError::NoSpace)
+    }
+
+    pub(crate) fn dealloc_inode(&mut self, inode_id: u32) -> Result<(), FsError> {
+        self.inode_bitmap.dealloc(&self.device, inode_id as usize)
+    }
+
+    /// Allocate a zeroed data block
+    fn alloc_data(&mut self) -> Result<u32, FsError> {
+        let bit = self.data_bitmap.alloc(&self.device)?.ok_or(FsError::NoSpace)?;
+        let block_id = self.data_area_start + bit;
+        self.device.write_block(block_id, &[0u8; BLOCK_SIZE])?;
+        Ok(block_id as u32)
+    }
+
+    fn dealloc_data(&mut self, block_id: u32) -> Result<(), FsError> {
+        let bit = (block_id as usize).checked_sub(self.data_area_start).ok_or(FsError::Corrupted)?;
+        self.data_bitmap.dealloc(&self.device, bit)
+    }
+
+    /// Follow `pointer` to an indirect block, allocating it if asked to
+    fn indirect(&mut self, pointer: &mut u32, create: bool) -> Result<u32, FsError> {
+        if *pointer == 0 && create {
+            *pointer = self.alloc_data()?;
+        }
+        Ok(*pointer)
+    }
+
+    /// Find where the pointer to the `index`th block of `inode` lives, `None` if an indirect
+    /// block on the way is missing and `create` is not set
+    fn slot(&mut self, inode: &mut DiskInode, index: usize, create: bool) -> Result<Option<Slot>, FsError> {
+        if index < INODE_DIRECT_COUNT {
+            return Ok(Some(Slot::Direct(index)));
+        }
+        if index < INODE_INDIRECT1_BOUND {
+            let table = self.indirect(&mut inode.indirect1, create)?;
+            return Ok((table != 0).then_some(Slot::Indirect(table as usize, index - INODE_DIRECT_COUNT)));
+        }
+        if index < INODE_INDIRECT2_BOUND {
+            let index = index - INODE_INDIRECT1_BOUND;
+            let outer = self.indirect(&mut inode.indirect2, create)?;
+            if outer == 0 {
+                return Ok(None);
+            }
+            let outer_slot = Slot::Indirect(outer as usize, index / INODE_INDIRECT_COUNT);
+            let mut table = self.get_slot(inode, outer_slot)?;
+            if table == 0 {
+                if !create {
+                    return Ok(None);
+                }
+                table = self.alloc_data()?;
+                self.set_slot(inode, outer_slot, table)?;
+            }
+            return Ok(Some(Slot::Indirect(table as usize, index % INODE_INDIRECT_COUNT)));
+        }
+        Err(FsError::FileTooLarge)
+    }
+
+    fn get_slot(&self, inode: &DiskInode, slot: Slot) -> Result<u32, FsError> {
+        match slot {
+            Slot::Direct(i) => Ok(inode.direct[i]),
+            Slot::Indirect(table, i) => self.load(table, i * 4)
+        }
+    }
+
+    fn set_slot(&self, inode: &mut DiskInode, slot: Slot, value: u32) -> Result<(), FsError> {
+        match slot {
+            Slot::Direct(i) => {
+                inode.direct[i] = value;
+                Ok(())
+            },
+            Slot::Indirect(table, i) => self.store(table, i * 4, &value)
+        }
+    }
+
+    /// Block id of the `index`th block of `inode`, 0 for a hole
+    pub(crate) fn block_of(&mut self, inode: &mut DiskInode, index: usize) -> Result<u32, FsError> {
+        match self.slot(inode, index, false)? {
+            Some(slot) => self.get_slot(inode, slot),
+            None => Ok(0)
+        }
+    }
+
+    /// Like `block_of`, but fills a hole with a new block
+    pub(crate) fn map_block(&mut self, inode: &mut DiskInode, index: usize) -> Result<u32, FsError> {
+        let slot = self.slot(inode, index, true)?.unwrap();
+        let block_id = self.get_slot(inode, slot)?;
+        if block_id != 0 {
+            return Ok(block_id);
+        }
+        let block_id = self.alloc_data()?;
+        self.set_slot(inode, slot, block_id)?;
+        Ok(block_id)
+    }
+
+    /// Release the `first`th and following blocks of `inode`, together with indirect blocks
+    /// that become unused
+    pub(crate) fn free_blocks_from(&mut self, inode: &mut DiskInode, first: usize) -> Result<(), FsError> {
+        for index in first..inode.blocks() {
+            let Some(slot) = self.slot(inode, index, false)? else {
+                continue;
+            };
+            let block_id = self.get_slot(inode, slot)?;
+            if block_id != 0 {
+                self.dealloc_data(block_id)?;
+                self.set_slot(inode, slot, 0)?;
+            }
+        }
+        if first <= INODE_DIRECT_COUNT && inode.indirect1 != 0 {
+            self.dealloc_data(inode.indirect1)?;
+            inode.indirect1 = 0;
+        }
+        if inode.indirect2 != 0 {
+            let start = first.saturating_sub(INODE_INDIRECT1_BOUND);
+            for outer in start.div_ceil(INODE_INDIRECT_COUNT)..INODE_INDIRECT_COUNT {
+                let slot = Slot::Indirect(inode.indirect2 as usize, outer);
+                let table = self.get_slot(inode, slot)?;
+                if table != 0 {
+                    self.dealloc_data(table)?;
+                    self.set_slot(inode, slot, 0)?;
+                }
+            }
+            if start == 0 {
+                self.dealloc_data(inode.indirect2)?;
+                inode.indirect2 = 0;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_data(&self, block_id: u32, buf: &mut [u8]) -> Result<(), FsError> {
+        self.device.read_block(block_id as usize, buf)
+    }
+
+    pub(crate) fn write_data(&self, block_id: u32, buf: &[u8]) -> Result<(), FsError> {
+        self.device.write_block(block_id as usize, buf)
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{FsError, BLOCK_SIZE};
+
+pub const EFS_MAGIC: u32 = 0x3b80_0001;
+
+/// Block pointers kept in the inode itself
+pub const INODE_DIRECT_COUNT: usize = 28;
+/// Block pointers in an indirect block
+pub const INODE_INDIRECT_COUNT: usize = BLOCK_SIZE / 4;
+pub const INODE_INDIRECT1_BOUND: usize = INODE_DIRECT_COUNT + INODE_INDIRECT_COUNT;
+pub const INODE_INDIRECT2_BOUND: usize = INODE_INDIRECT1_BOUND + INODE_INDIRECT_COUNT * INODE_INDIRECT_COUNT;
+
+pub const DISK_INODE_SIZE: usize = size_of::<DiskInode>();
+pub const INODES_PER_BLOCK: usize = BLOCK_SIZE / DISK_INODE_SIZE;
+
+pub const NAME_LENGTH_LIMIT: usize = 27;
+pub const DIRENT_SIZE: usize = size_of::<DirEntry>();
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SuperBlock {
+    pub magic: u32,
+    pub total_blocks: u32,
+    pub inode_bitmap_blocks: u32,
+    pub inode_area_blocks: u32,
+    pub data_bitmap_blocks: u32,
+    pub data_area_blocks: u32
+}
+
+impl SuperBlock {
+    pub fn is_valid(&self) -> bool {
+        self.magic == EFS_MAGIC
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InodeType {
+    File,
+    Directory
+}
+
+impl InodeType {
+    fn to_raw(self) -> u32 {
+        match self {
+            InodeType::File => 1,
+            InodeType::Directory => 2
+        }
+    }
+
+    fn from_raw(raw: u32) -> Result<Self, FsError> {
+        match raw {
+            1 => Ok(InodeType::File),
+            2 => Ok(InodeType::Directory),
+            _ => Err(FsError::Corrupted)
+        }
+    }
+}
+
+/// An inode as stored on disk. A zero block pointer means a hole, which reads as zeros
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DiskInode {
+    pub size: u32,
+    pub direct: [u32; INODE_DIRECT_COUNT],
+    pub indirect1: u32,
+    pub indirect2: u32,
+    kind: u32
+}
+static_assertions::const_assert_eq!(DISK_INODE_SIZE, 128);
+
+impl DiskInode {
+    pub fn new(kind: InodeType) -> Self {
+        DiskInode { size: 0, direct: [0; INODE_DIRECT_COUNT], indirect1: 0, indirect2: 0, kind: kind.to_raw() }
+    }
+
+    pub fn kind(&self) -> Result<InodeType, FsError> {
+        InodeType::from_raw(self.kind)
+    }
+
+    pub fn blocks(&self) -> usize {
+        (self.size as usize).div_ceil(BLOCK_SIZE)
+    }
+}
+
+/// An entry of a directory, free when the name is empty
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DirEntry {
+    name: [u8; NAME_LENGTH_LIMIT + 1],
+    inode_id: u32
+}
+
+impl DirEntry {
+    pub fn empty() -> Self {
+        DirEntry { name: [0; NAME_LENGTH_LIMIT + 1], inode_id: 0 }
+    }
+
+    pub fn new(name: &str, inode_id: u32) -> Result<Self, FsError> {
+        if name.is_empty() || name.len() > NAME_LENGTH_LIMIT || name.contains('\0') {
+            return Err(FsError::NameTooLong);
+        }
+        let mut entry = DirEntry { name: [0; NAME_LENGTH_LIMIT + 1], inode_id };
+        entry.name[..name.len()].copy_from_slice(name.as_bytes());
+        Ok(entry)
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.name[0] == 0
+    }
+
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|c| *c == 0).unwrap_or(self.name.len());
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+
+    pub fn inode_id(&self) -> u32 {
+        self.inode_id
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as *const u8, DIRENT_SIZE) }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        assert!(bytes.len() >= DIRENT_SIZE);
+        unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const DirEntry) }
+    }
+}
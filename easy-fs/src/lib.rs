@@ -0,0 +1,47 @@
+//! A simple inode based file system: a super block followed by the inode bitmap,
+//! the inode area, the data bitmap and the data area. Shared by the kernel and
+//! the host side packer, so it only relies on `core` and `alloc`
+#![no_std]
+
+extern crate alloc;
+
+mod bitmap;
+mod block_dev;
+mod efs;
+mod layout;
+mod vfs;
+
+use thiserror::Error;
+
+pub use block_dev::BlockDevice;
+pub use efs::EasyFileSystem;
+pub use layout::{InodeType, NAME_LENGTH_LIMIT};
+pub use vfs::{DirEntryInfo, Inode};
+
+pub const BLOCK_SIZE: usize = 512;
+
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    #[error("I/O error on block {0}")]
+    Io(usize),
+    #[error("not an easy-fs image")]
+    BadMagic,
+    #[error("file system corrupted")]
+    Corrupted,
+    #[error("no space left on device")]
+    NoSpace,
+    #[error("file too large")]
+    FileTooLarge,
+    #[error("no such file or directory")]
+    NotFound,
+    #[error("file exists")]
+    AlreadyExists,
+    #[error("not a directory")]
+    NotADirectory,
+    #[error("is a directory")]
+    IsADirectory,
+    #[error("directory not empty")]
+    DirectoryNotEmpty,
+    #[error("file name too long")]
+    NameTooLong
+}
@@ -0,0 +1,203 @@
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+use crate::efs::EasyFileSystem;
+use crate::layout::{DirEntry, DiskInode, InodeType, DIRENT_SIZE, INODE_INDIRECT2_BOUND};
+use crate::{FsError, BLOCK_SIZE};
+
+/// What `Inode::entries` reports for every entry of a directory
+pub struct DirEntryInfo {
+    pub name: String,
+    pub inode_id: u32,
+    pub kind: InodeType
+}
+
+/// Handle to an inode, every operation locks the whole file system
+#[derive(Clone)]
+pub struct Inode {
+    id: u32,
+    fs: Arc<Mutex<EasyFileSystem>>
+}
+
+impl Inode {
+    pub(crate) fn new(id: u32, fs: Arc<Mutex<EasyFileSystem>>) -> Self {
+        Inode { id, fs }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn kind(&self) -> Result<InodeType, FsError> {
+        self.fs.lock().load_inode(self.id)?.kind()
+    }
+
+    pub fn size(&self) -> Result<usize, FsError> {
+        Ok(self.fs.lock().load_inode(self.id)?.size as usize)
+    }
+
+    /// Read from `offset`, returns the number of bytes read which is short at the end of file
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, FsError> {
+        read_at(&mut self.fs.lock(), self.id, offset, buf)
+    }
+
+    /// Write at `offset`, growing the file if needed
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, FsError> {
+        write_at(&mut self.fs.lock(), self.id, offset, buf)
+    }
+
+    /// Shrink or extend the file to `size` bytes, extended parts read as zeros
+    pub fn truncate(&self, size: usize) -> Result<(), FsError> {
+        let mut fs = self.fs.lock();
+        let mut inode = fs.load_inode(self.id)?;
+        if size > INODE_INDIRECT2_BOUND * BLOCK_SIZE {
+            return Err(FsError::FileTooLarge);
+        }
+        if size < inode.size as usize {
+            fs.free_blocks_from(&mut inode, size.div_ceil(BLOCK_SIZE))?;
+            // the tail of the last block has to read as zeros if the file grows again
+            if size % BLOCK_SIZE != 0 {
+                let block_id = fs.block_of(&mut inode, size / BLOCK_SIZE)?;
+                if block_id != 0 {
+                    let mut block = [0u8; BLOCK_SIZE];
+                    fs.read_data(block_id, &mut block)?;
+                    block[size % BLOCK_SIZE..].fill(0);
+                    fs.write_data(block_id, &block)?;
+                }
+            }
+        }
+        inode.size = size as u32;
+        fs.store_inode(self.id, &inode)
+    }
+
+    /// Look up `name` in this directory
+    pub fn find(&self, name: &str) -> Result<Option<Inode>, FsError> {
+        let mut fs = self.fs.lock();
+        Ok(dir_entries(&mut fs, self.id)?.into_iter()
+            .find(|(_, entry)| entry.name() == name)
+            .map(|(_, entry)| Inode::new(entry.inode_id(), self.fs.clone())))
+    }
+
+    /// Create an empty file or directory named `name` in this directory
+    pub fn create(&self, name: &str, kind: InodeType) -> Result<Inode, FsError> {
+        let mut fs = self.fs.lock();
+        let entries = dir_entries(&mut fs, self.id)?;
+        if entries.iter().any(|(_, entry)| entry.name() == name) {
+            return Err(FsError::AlreadyExists);
+        }
+        // validate the name before anything is allocated
+        DirEntry::new(name, 0)?;
+        let inode_id = fs.alloc_inode()?;
+        fs.store_inode(inode_id, &DiskInode::new(kind))?;
+        let slot = entries.iter().find(|(_, entry)| entry.is_free()).map(|(slot, _)| *slot).unwrap_or(entries.len());
+        write_at(&mut fs, self.id, slot * DIRENT_SIZE, DirEntry::new(name, inode_id)?.as_bytes())?;
+        Ok(Inode::new(inode_id, self.fs.clone()))
+    }
+
+    /// Remove `name` from this directory, directories have to be empty. The inode stays
+    /// allocated so that open handles keep working, `release` frees it once they are done
+    pub fn unlink(&self, name: &str) -> Result<(), FsError> {
+        let mut fs = self.fs.lock();
+        let Some((slot, entry)) = dir_entries(&mut fs, self.id)?.into_iter().find(|(_, entry)| entry.name() == name) else {
+            return Err(FsError::NotFound);
+        };
+        let inode = fs.load_inode(entry.inode_id())?;
+        if inode.kind()? == InodeType::Directory && dir_entries(&mut fs, entry.inode_id())?.iter().any(|(_, e)| !e.is_free()) {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+        write_at(&mut fs, self.id, slot * DIRENT_SIZE, DirEntry::empty().as_bytes())?;
+        Ok(())
+    }
+
+    /// Free the blocks and the inode of an unlinked file or directory. No handle to it may
+    /// be used afterwards
+    pub fn release(self) -> Result<(), FsError> {
+        let mut fs = self.fs.lock();
+        let mut inode = fs.load_inode(self.id)?;
+        fs.free_blocks_from(&mut inode, 0)?;
+        fs.dealloc_inode(self.id)
+    }
+
+    /// List this directory
+    pub fn entries(&self) -> Result<Vec<DirEntryInfo>, FsError> {
+        let mut fs = self.fs.lock();
+        dir_entries(&mut fs, self.id)?.into_iter()
+            .filter(|(_, entry)| !entry.is_free())
+            .map(|(_, entry)| Ok(DirEntryInfo {
+                name: entry.name().to_string(),
+                inode_id: entry.inode_id(),
+                kind: fs.load_inode(entry.inode_id())?.kind()?
+            }))
+            .collect()
+    }
+}
+
+fn read_at(fs: &mut EasyFileSystem, inode_id: u32, offset: usize, buf: &mut [u8]) -> Result<usize, FsError> {
+    let mut inode = fs.load_inode(inode_id)?;
+    let end = (inode.size as usize).min(offset + buf.len());
+    if offset >= end {
+        return Ok(0);
+    }
+    let mut block = [0u8; BLOCK_SIZE];
+    let mut pos = offset;
+    while pos < end {
+        let in_block = pos % BLOCK_SIZE;
+        let len = (BLOCK_SIZE - in_block).min(end - pos);
+        let dst = &mut buf[pos - offset..pos - offset + len];
+        match fs.block_of(&mut inode, pos / BLOCK_SIZE)? {
+            0 => dst.fill(0),
+            block_id => {
+                fs.read_data(block_id, &mut block)?;
+                dst.copy_from_slice(&block[in_block..in_block + len]);
+            }
+        }
+        pos += len;
+    }
+    Ok(end - offset)
+}
+
+fn write_at(fs: &mut EasyFileSystem, inode_id: u32, offset: usize, buf: &[u8]) -> Result<usize, FsError> {
+    let mut inode = fs.load_inode(inode_id)?;
+    let end = offset + buf.len();
+    if end > INODE_INDIRECT2_BOUND * BLOCK_SIZE {
+        return Err(FsError::FileTooLarge);
+    }
+    let mut block = [0u8; BLOCK_SIZE];
+    let mut pos = offset;
+    let mut result = Ok(());
+    while pos < end {
+        let in_block = pos % BLOCK_SIZE;
+        let len = (BLOCK_SIZE - in_block).min(end - pos);
+        result = fs.map_block(&mut inode, pos / BLOCK_SIZE).and_then(|block_id| {
+            if len < BLOCK_SIZE {
+                fs.read_data(block_id, &mut block)?;
+            }
+            block[in_block..in_block + len].copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            fs.write_data(block_id, &block)
+        });
+        if result.is_err() {
+            break;
+        }
+        pos += len;
+    }
+    // blocks may have been mapped even if it failed halfway, keep track of them
+    if pos > offset {
+        inode.size = inode.size.max(pos as u32);
+    }
+    fs.store_inode(inode_id, &inode)?;
+    result.map(|_| pos - offset)
+}
+
+/// All entry slots of a directory, including free ones, with their index
+fn dir_entries(fs: &mut EasyFileSystem, inode_id: u32) -> Result<Vec<(usize, DirEntry)>, FsError> {
+    let inode = fs.load_inode(inode_id)?;
+    if inode.kind()? != InodeType::Directory {
+        return Err(FsError::NotADirectory);
+    }
+    let mut raw = vec![0u8; inode.size as usize];
+    read_at(fs, inode_id, 0, &mut raw)?;
+    Ok(raw.chunks_exact(DIRENT_SIZE).map(DirEntry::from_bytes).enumerate().collect())
+}
@@ -1,5 +1,9 @@
 use core::fmt::{self, Write};
 use core::slice;
+use alloc::{collections::vec_deque::VecDeque, vec, vec::Vec};
+use lazy_static::lazy_static;
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::io::uart::UART;
 use log::error;
 use crate::mm::kernel::KERNEL_MEMORY_MANAGER;
 use crate::sbi;
@@ -22,6 +26,10 @@ pub struct Stdout;
 
 impl fmt::Write for Stdout{
     fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(mut uart) = UART.try_exclusive_access() && let Some(uart) = uart.as_mut() {
+            uart.puts(s);
+            return Ok(());
+        }
         let km = KERNEL_MEMORY_MANAGER.exclusive_access();
         if km.is_identical_address(s.as_ptr() as usize) {
             sbi::putstr(s);
@@ -40,6 +48,16 @@ impl fmt::Write for Stdout{
 }
 
 
+/// Write `s` without looking it up in the kernel address space, so it must be identically mapped
+/// unless the UART has taken over the console
+pub fn putstr(s: &str) {
+    if let Some(mut uart) = UART.try_exclusive_access() && let Some(uart) = uart.as_mut() {
+        uart.puts(s);
+    } else {
+        sbi::putstr(s);
+    }
+}
+
 pub fn print(args: core::fmt::Arguments){
     let _ = Stdout.write_fmt(args);
 }
@@ -51,4 +69,110 @@ macro_rules! with_color {
 }
 pub fn print_with_color(args: core::fmt::Arguments, color: u8){
     let _ = Stdout.write_fmt(with_color!(args, color));
-}
\ No newline at end of file
+}
+
+const LF: u8 = b'\n';
+const CR: u8 = b'\r';
+const BS: u8 = 0x08;
+const DL: u8 = 0x7f;
+const EOT: u8 = 0x04;
+const INPUT_CHUNK_SIZE: usize = 32;
+
+/// Line discipline of the console: input is echoed and can be edited until a newline
+/// arrives, only completed lines are handed to readers
+pub struct ConsoleInput {
+    /// the line being typed
+    editing: VecDeque<u8>,
+    /// completed lines waiting to be read
+    ready: VecDeque<u8>,
+    /// set by ^D on an empty line, the next read returns 0
+    eof: bool,
+    chunk: Vec<u8>
+}
+
+impl ConsoleInput {
+    fn new() -> Self {
+        ConsoleInput { editing: VecDeque::new(), ready: VecDeque::new(), eof: false, chunk: vec![0; INPUT_CHUNK_SIZE] }
+    }
+
+    /// Fetch whatever the console has received and run it through the line discipline
+    pub fn poll(&mut self) {
+        let received = UART.exclusive_access().as_mut().map(|uart| {
+            uart.handle_interrupt();
+            let mut received = Vec::new();
+            while let Some(c) = uart.getc() {
+                received.push(c);
+            }
+            received
+        });
+        if let Some(received) = received {
+            received.into_iter().for_each(|c| self.receive(c));
+            return;
+        }
+        loop {
+            // the chunk lives on the heap, which is identically mapped
+            let len = sbi::getstr(&mut self.chunk);
+            if len == 0 {
+                break;
+            }
+            for i in 0..len {
+                let c = self.chunk[i];
+                self.receive(c);
+            }
+        }
+    }
+
+    fn receive(&mut self, c: u8) {
+        match c {
+            LF | CR => {
+                print!("\n");
+                self.ready.extend(self.editing.drain(..));
+                self.ready.push_back(LF);
+            },
+            BS | DL => {
+                if self.editing.pop_back().is_some() {
+                    print!("{} {}", BS as char, BS as char);
+                }
+            },
+            EOT => {
+                if self.editing.is_empty() {
+                    self.eof = true;
+                } else {
+                    self.ready.extend(self.editing.drain(..));
+                }
+            },
+            _ if c.is_ascii() && !c.is_ascii_control() => {
+                print!("{}", c as char);
+                self.editing.push_back(c);
+            },
+            _ => {}
+        }
+    }
+
+    /// Whether a read can be served right now
+    pub fn readable(&self) -> bool {
+        !self.ready.is_empty() || self.eof
+    }
+
+    /// Move the ready input into `buf`, stops after a newline. Returns 0 on end of file
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        if self.ready.is_empty() && self.eof {
+            self.eof = false;
+            return 0;
+        }
+        let mut len = 0;
+        while len < buf.len() {
+            let Some(c) = self.ready.pop_front() else { break };
+            buf[len] = c;
+            len += 1;
+            if c == LF {
+                break;
+            }
+        }
+        len
+    }
+}
+
+lazy_static!{
+    pub static ref CONSOLE_INPUT: SingleThreadSafeCell<ConsoleInput> = SingleThreadSafeCell::new(ConsoleInput::new());
+}
@@ -0,0 +1,301 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::FsError;
+use lazy_static::lazy_static;
+use riscv::register::time;
+
+use crate::fs::file::{File, FileError, OpenFlags, Stat, S_IFCHR};
+use crate::fs::stdio::{Stdin, Stdout};
+use crate::fs::vfs::{Dentry, DirEntry, Inode, InodeKind, SuperBlock};
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::helper::chacha::{ChaCha20Rng, KEY_SIZE};
+
+lazy_static!{
+    static ref RNG: SingleThreadSafeCell<ChaCha20Rng> = SingleThreadSafeCell::new(ChaCha20Rng::new(jitter_seed()));
+}
+
+/// Gather a seed from how long it takes the `time` CSR to tick, which varies with whatever
+/// the hart and the host are busy with
+fn jitter_seed() -> [u8; KEY_SIZE] {
+    let mut seed = [0u8; KEY_SIZE];
+    for i in 0..KEY_SIZE * 8 {
+        let start = time::read();
+        let mut spins = 0u32;
+        while time::read() == start {
+            spins = spins.wrapping_add(1);
+        }
+        seed[i % KEY_SIZE] ^= (spins as u8).rotate_left(i as u32 / KEY_SIZE as u32) ^ start as u8;
+    }
+    seed
+}
+
+/// The device nodes, all of them in the root directory
+pub struct DevFs {
+    root: Arc<DevRoot>
+}
+
+impl DevFs {
+    pub fn new() -> Self {
+        // random doesn't wait for more entropy than urandom does, they are the same generator
+        let devices: [(&'static str, Arc<dyn File>); 5] = [
+            ("null", Arc::new(Null)),
+            ("zero", Arc::new(Zero)),
+            ("console", Arc::new(Console)),
+            ("random", Arc::new(Urandom)),
+            ("urandom", Arc::new(Urandom))
+        ];
+        let nodes = devices.into_iter().enumerate()
+            .map(|(i, (name, device))| Arc::new(DevNode { name, ino: i as u64 + 2, device }))
+            .collect();
+        DevFs { root: Arc::new(DevRoot { nodes }) }
+    }
+}
+
+impl SuperBlock for DevFs {
+    fn fs_type(&self) -> &'static str {
+        "devfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+struct DevRoot {
+    nodes: Vec<Arc<DevNode>>
+}
+
+struct DevNode {
+    name: &'static str,
+    ino: u64,
+    device: Arc<dyn File>
+}
+
+impl Inode for DevRoot {
+    fn ino(&self) -> u64 {
+        1
+    }
+
+    fn kind(&self) -> InodeKind {
+        InodeKind::Directory
+    }
+
+    fn size(&self) -> Result<usize, FileError> {
+        Ok(0)
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, FileError> {
+        Err(FsError::IsADirectory.into())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FileError> {
+        match self.nodes.iter().find(|node| node.name == name) {
+            Some(node) => Ok(node.clone()),
+            None => Err(FsError::NotFound.into())
+        }
+    }
+
+    fn entries(&self) -> Result<Vec<DirEntry>, FileError> {
+        Ok(self.nodes.iter()
+            .map(|node| DirEntry { name: node.name.into(), ino: node.ino, kind: InodeKind::CharDevice })
+            .collect())
+    }
+}
+
+/// Reading or writing the node itself goes to the device, offsets mean nothing to devices
+impl Inode for DevNode {
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn kind(&self) -> InodeKind {
+        InodeKind::CharDevice
+    }
+
+    fn size(&self) -> Result<usize, FileError> {
+        Ok(0)
+    }
+
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize, FileError> {
+        self.device.read(buf)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize, FileError> {
+        self.device.write(buf)
+    }
+
+    fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, FileError> {
+        Err(FsError::NotADirectory.into())
+    }
+
+    fn entries(&self) -> Result<Vec<DirEntry>, FileError> {
+        Err(FsError::NotADirectory.into())
+    }
+
+    fn device(&self) -> Option<Arc<dyn File>> {
+        Some(self.device.clone())
+    }
+}
+
+/// A device opened by a process, with the access the process asked for
+pub struct DeviceFile {
+    readable: bool,
+    writable: bool,
+    dentry: Arc<Dentry>,
+    device: Arc<dyn File>
+}
+
+impl DeviceFile {
+    pub fn new(dentry: Arc<Dentry>, device: Arc<dyn File>, flags: OpenFlags) -> Self {
+        let (readable, writable) = flags.access();
+        DeviceFile { readable, writable, dentry, device }
+    }
+}
+
+impl File for DeviceFile {
+    fn readable(&self) -> bool {
+        self.readable && self.device.readable()
+    }
+
+    fn writable(&self) -> bool {
+        self.writable && self.device.writable()
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize, FileError> {
+        if !self.readable {
+            return Err(FileError::BadAccess);
+        }
+        self.device.read(buf)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, FileError> {
+        if !self.writable {
+            return Err(FileError::BadAccess);
+        }
+        self.device.write(buf)
+    }
+
+    fn stat(&self) -> Result<Stat, FileError> {
+        self.dentry.inode().stat()
+    }
+
+    fn dentry(&self) -> Option<Arc<Dentry>> {
+        Some(self.dentry.clone())
+    }
+}
+
+fn device_stat() -> Stat {
+    Stat::new(0, S_IFCHR | 0o666, 0)
+}
+
+/// Reads as end of file, swallows whatever is written
+struct Null;
+
+/// Reads as an endless stream of zeros, swallows whatever is written
+struct Zero;
+
+/// The console, readable and writable unlike the stdio descriptors
+struct Console;
+
+/// Never-ending random bytes, what is written is mixed into the generator
+struct Urandom;
+
+impl File for Null {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> Result<usize, FileError> {
+        Ok(0)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, FileError> {
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> Result<Stat, FileError> {
+        Ok(device_stat())
+    }
+}
+
+impl File for Zero {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize, FileError> {
+        buf.fill(0);
+        Ok(buf.len())
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, FileError> {
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> Result<Stat, FileError> {
+        Ok(device_stat())
+    }
+}
+
+impl File for Console {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize, FileError> {
+        Stdin.read(buf)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, FileError> {
+        Stdout.write(buf)
+    }
+
+    fn stat(&self) -> Result<Stat, FileError> {
+        Stdin.stat()
+    }
+}
+
+impl File for Urandom {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize, FileError> {
+        let mut rng = RNG.exclusive_access();
+        // whenever the read happens adds a little more entropy
+        rng.reseed(&time::read().to_le_bytes());
+        rng.fill(buf);
+        Ok(buf.len())
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, FileError> {
+        RNG.exclusive_access().reseed(buf);
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> Result<Stat, FileError> {
+        Ok(device_stat())
+    }
+}
+
+/// Open the device behind `dentry` if it is a device node
+pub fn open_device(dentry: &Arc<Dentry>, flags: OpenFlags) -> Option<Arc<dyn File>> {
+    let device = dentry.inode().device()?;
+    Some(Arc::new(DeviceFile::new(dentry.clone(), device, flags)))
+}
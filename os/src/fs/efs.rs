@@ -0,0 +1,176 @@
+use alloc::collections::btree_map::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use easy_fs::{EasyFileSystem, FsError, InodeType};
+use log::error;
+use spin::Mutex;
+
+use crate::fs::file::FileError;
+use crate::fs::vfs::{DirEntry, Inode, InodeKind, SuperBlock};
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::io::block::BlockDevice;
+
+/// Lets easy-fs run on top of our block device drivers
+struct BlockDeviceAdapter(Arc<dyn BlockDevice>);
+
+impl easy_fs::BlockDevice for BlockDeviceAdapter {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), FsError> {
+        self.0.read_block(block_id, buf).map_err(|e| {
+            error!("[FS] Failed to read block {}: {}", block_id, e);
+            FsError::Io(block_id)
+        })
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), FsError> {
+        self.0.write_block(block_id, buf).map_err(|e| {
+            error!("[FS] Failed to write block {}: {}", block_id, e);
+            FsError::Io(block_id)
+        })
+    }
+}
+
+pub struct EfsSuperBlock {
+    fs: Arc<Efs>
+}
+
+impl EfsSuperBlock {
+    pub fn open(device: Arc<dyn BlockDevice>) -> Result<Self, FsError> {
+        let fs = Efs {
+            fs: EasyFileSystem::open(Arc::new(BlockDeviceAdapter(device)))?,
+            inodes: SingleThreadSafeCell::new(BTreeMap::new())
+        };
+        Ok(EfsSuperBlock { fs: Arc::new(fs) })
+    }
+}
+
+impl SuperBlock for EfsSuperBlock {
+    fn fs_type(&self) -> &'static str {
+        "easy-fs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        self.fs.inode(EasyFileSystem::root_inode(&self.fs.fs)).expect("[FS] easy-fs root inode unreadable")
+    }
+}
+
+struct Efs {
+    fs: Arc<Mutex<EasyFileSystem>>,
+    /// Inodes in use, so that an unlinked one is only released by its last user
+    inodes: SingleThreadSafeCell<BTreeMap<u32, Weak<EfsInode>>>
+}
+
+impl Efs {
+    /// The inode for `inode`, shared with whoever already uses it
+    fn inode(self: &Arc<Self>, inode: easy_fs::Inode) -> Result<Arc<EfsInode>, FileError> {
+        let mut inodes = self.inodes.exclusive_access();
+        if let Some(shared) = inodes.get(&inode.id()).and_then(Weak::upgrade) {
+            return Ok(shared);
+        }
+        inodes.retain(|_, inode| inode.strong_count() > 0);
+        let kind = kind_of(inode.kind()?);
+        let id = inode.id();
+        let shared = Arc::new(EfsInode {
+            fs: self.clone(),
+            inode: Some(inode),
+            kind,
+            unlinked: SingleThreadSafeCell::new(false)
+        });
+        inodes.insert(id, Arc::downgrade(&shared));
+        Ok(shared)
+    }
+}
+
+struct EfsInode {
+    fs: Arc<Efs>,
+    /// Only taken when the inode is released
+    inode: Option<easy_fs::Inode>,
+    /// Never changes, kept so that it isn't read from the disk every time
+    kind: InodeKind,
+    /// Removed from its directory, the inode and its blocks go away with the last user
+    unlinked: SingleThreadSafeCell<bool>
+}
+
+impl EfsInode {
+    fn inode(&self) -> &easy_fs::Inode {
+        self.inode.as_ref().unwrap()
+    }
+}
+
+impl Drop for EfsInode {
+    fn drop(&mut self) {
+        if !*self.unlinked.exclusive_access() {
+            return;
+        }
+        if let Err(e) = self.inode.take().unwrap().release() {
+            error!("[FS] Failed to release a removed easy-fs inode: {}", e);
+        }
+    }
+}
+
+fn kind_of(kind: InodeType) -> InodeKind {
+    match kind {
+        InodeType::File => InodeKind::Regular,
+        InodeType::Directory => InodeKind::Directory
+    }
+}
+
+impl Inode for EfsInode {
+    fn ino(&self) -> u64 {
+        self.inode().id() as u64
+    }
+
+    fn kind(&self) -> InodeKind {
+        self.kind
+    }
+
+    fn size(&self) -> Result<usize, FileError> {
+        Ok(self.inode().size()?)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, FileError> {
+        Ok(self.inode().read_at(offset, buf)?)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, FileError> {
+        Ok(self.inode().write_at(offset, buf)?)
+    }
+
+    fn truncate(&self, size: usize) -> Result<(), FileError> {
+        Ok(self.inode().truncate(size)?)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FileError> {
+        match self.inode().find(name)? {
+            Some(inode) => Ok(self.fs.inode(inode)?),
+            None => Err(FsError::NotFound.into())
+        }
+    }
+
+    fn create(&self, name: &str, kind: InodeKind) -> Result<Arc<dyn Inode>, FileError> {
+        let kind = match kind {
+            InodeKind::Regular => InodeType::File,
+            InodeKind::Directory => InodeType::Directory,
+            InodeKind::CharDevice => return Err(FileError::InvalidArgument)
+        };
+        // whatever is created in a removed directory would never be released
+        if *self.unlinked.exclusive_access() {
+            return Err(FsError::NotFound.into());
+        }
+        Ok(self.fs.inode(self.inode().create(name, kind)?)?)
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), FileError> {
+        let child = self.inode().find(name)?.ok_or(FsError::NotFound)?;
+        let child = self.fs.inode(child)?;
+        self.inode().unlink(name)?;
+        // released right away unless some file or working directory still refers to it
+        *child.unlinked.exclusive_access() = true;
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<Vec<DirEntry>, FileError> {
+        Ok(self.inode().entries()?.into_iter()
+            .map(|entry| DirEntry { name: entry.name, ino: entry.inode_id as u64, kind: kind_of(entry.kind) })
+            .collect())
+    }
+}
@@ -0,0 +1,908 @@
+use alloc::collections::btree_map::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+use easy_fs::FsError;
+use log::error;
+
+use crate::fs::file::FileError;
+use crate::fs::vfs::{DirEntry, Inode, InodeKind, SuperBlock};
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::io::block::{BlockDevice, BLOCK_SIZE};
+
+const DIR_ENTRY_SIZE: usize = 32;
+/// FAT32 entries are 28 bits, the top 4 are reserved and kept as they are
+const FAT_ENTRY_MASK: u32 = 0x0FFF_FFFF;
+/// Anything from here on ends a cluster chain
+const END_OF_CHAIN: u32 = 0x0FFF_FFF8;
+/// Clusters 0 and 1 don't exist, their FAT entries hold the media type and flags
+const FIRST_CLUSTER: u32 = 2;
+/// A directory can't hold more entries than that
+const MAX_DIR_ENTRIES: usize = 65536;
+/// In UTF-16 units
+const MAX_NAME_LENGTH: usize = 255;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0F;
+const ATTR_LONG_NAME_MASK: u8 = 0x3F;
+/// Lower case short names, the way Windows NT and Linux record them
+const CASE_LOWER_BASE: u8 = 0x08;
+const CASE_LOWER_EXT: u8 = 0x10;
+
+const END_OF_DIR: u8 = 0x00;
+const DELETED: u8 = 0xE5;
+/// Stands for a leading 0xE5 in short names, which would read as deleted
+const ESCAPED_DELETED: u8 = 0x05;
+
+const LFN_CHARS: usize = 13;
+/// Where the UTF-16 units of a long name entry are
+const LFN_CHAR_OFFSETS: [usize; LFN_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+const LFN_LAST: u8 = 0x40;
+const LFN_ORDER_MASK: u8 = 0x1F;
+
+const DOT: [u8; 11] = *b".          ";
+const DOT_DOT: [u8; 11] = *b"..         ";
+/// Characters allowed in short names besides letters and digits
+const SHORT_NAME_SPECIALS: &[u8] = b"$%'-_@~`!(){}^#&";
+const INVALID_NAME_CHARS: &[char] = &['"', '*', '/', ':', '<', '>', '?', '\\', '|'];
+/// 1980-01-01, there is no clock to date files with
+const DEFAULT_DATE: u16 = (1 << 5) | 1;
+
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FS_INFO_SIGNATURE: u32 = 0x6141_7272;
+const FS_INFO_FREE_COUNT: usize = 488;
+const FS_INFO_NEXT_FREE: usize = 492;
+const FREE_COUNT_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+const ROOT_INO: u64 = 1;
+
+fn le16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn le32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// A FAT32 volume, as made by `mkfs.vfat -F 32`. Only 512 bytes sectors are supported
+pub struct Fat32SuperBlock {
+    fs: Arc<Fat32>
+}
+
+impl Fat32SuperBlock {
+    pub fn open(device: Arc<dyn BlockDevice>) -> Result<Self, FsError> {
+        Ok(Fat32SuperBlock { fs: Arc::new(Fat32::open(device)?) })
+    }
+}
+
+impl SuperBlock for Fat32SuperBlock {
+    fn fs_type(&self) -> &'static str {
+        "vfat"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        self.fs.inode(ROOT_INO, InodeKind::Directory, None, self.fs.root_cluster, 0)
+    }
+}
+
+/// Layout of the volume read from the boot sector, and what its inodes share
+struct Fat32 {
+    device: Arc<dyn BlockDevice>,
+    sectors_per_cluster: usize,
+    fat_start: usize,
+    fat_sectors: usize,
+    fat_count: usize,
+    data_start: usize,
+    /// Clusters are numbered from `FIRST_CLUSTER` to `cluster_count + 1`
+    cluster_count: usize,
+    root_cluster: u32,
+    /// Where looking for a free cluster starts
+    next_free: SingleThreadSafeCell<u32>,
+    /// Inodes in use, so that everyone sees the same clusters and size of a file
+    inodes: SingleThreadSafeCell<BTreeMap<u64, Weak<FatInode>>>
+}
+
+impl Fat32 {
+    fn open(device: Arc<dyn BlockDevice>) -> Result<Self, FsError> {
+        let mut boot = [0u8; BLOCK_SIZE];
+        device.read_block(0, &mut boot).map_err(|e| {
+            error!("[FS] Failed to read the FAT32 boot sector: {}", e);
+            FsError::Io(0)
+        })?;
+        let sectors_per_cluster = boot[13] as usize;
+        let reserved = le16(&boot, 14) as usize;
+        let fat_count = boot[16] as usize;
+        let fat_sectors = le32(&boot, 36) as usize;
+        // FAT12 and FAT16 have a fixed root directory and 16 bits FAT sizes
+        let is_fat32 = boot[510..512] == [0x55, 0xAA] && le16(&boot, 11) as usize == BLOCK_SIZE
+            && le16(&boot, 17) == 0 && le16(&boot, 22) == 0 && fat_sectors != 0
+            && sectors_per_cluster.is_power_of_two() && reserved != 0 && fat_count != 0;
+        if !is_fat32 {
+            return Err(FsError::BadMagic);
+        }
+        let total_sectors = match le16(&boot, 19) {
+            0 => le32(&boot, 32) as usize,
+            sectors => sectors as usize
+        };
+        let data_start = reserved + fat_count * fat_sectors;
+        if total_sectors <= data_start || total_sectors > device.num_blocks() {
+            return Err(FsError::Corrupted);
+        }
+        let cluster_count = ((total_sectors - data_start) / sectors_per_cluster)
+            .min(fat_sectors * BLOCK_SIZE / 4 - FIRST_CLUSTER as usize);
+        let fs = Fat32 {
+            device,
+            sectors_per_cluster,
+            fat_start: reserved,
+            fat_sectors,
+            fat_count,
+            data_start,
+            cluster_count,
+            root_cluster: le32(&boot, 44),
+            next_free: SingleThreadSafeCell::new(FIRST_CLUSTER),
+            inodes: SingleThreadSafeCell::new(BTreeMap::new())
+        };
+        if !fs.is_valid_cluster(fs.root_cluster) {
+            return Err(FsError::Corrupted);
+        }
+        fs.open_fs_info(le16(&boot, 48) as usize, reserved)?;
+        Ok(fs)
+    }
+
+    /// Take the next free cluster hint from the FSInfo sector. The free count isn't kept up to
+    /// date, it is marked unknown so that other systems count again
+    fn open_fs_info(&self, sector: usize, reserved: usize) -> Result<(), FsError> {
+        if sector == 0 || sector >= reserved {
+            return Ok(());
+        }
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.read_sector(sector, &mut buf)?;
+        if le32(&buf, 0) != FS_INFO_LEAD_SIGNATURE || le32(&buf, 484) != FS_INFO_SIGNATURE {
+            return Ok(());
+        }
+        let next_free = le32(&buf, FS_INFO_NEXT_FREE);
+        if self.is_valid_cluster(next_free) {
+            *self.next_free.exclusive_access() = next_free;
+        }
+        buf[FS_INFO_FREE_COUNT..FS_INFO_FREE_COUNT + 4].copy_from_slice(&FREE_COUNT_UNKNOWN.to_le_bytes());
+        self.write_sector(sector, &buf)
+    }
+
+    /// The inode for the entry at `ino`, shared with whoever already uses it
+    fn inode(self: &Arc<Self>, ino: u64, kind: InodeKind, entry: Option<(usize, usize)>, first_cluster: u32,
+             size: usize) -> Arc<FatInode> {
+        let mut inodes = self.inodes.exclusive_access();
+        if let Some(inode) = inodes.get(&ino).and_then(Weak::upgrade) {
+            return inode;
+        }
+        inodes.retain(|_, inode| inode.strong_count() > 0);
+        let inode = Arc::new(FatInode {
+            fs: self.clone(),
+            ino,
+            kind,
+            entry,
+            state: SingleThreadSafeCell::new(InodeState { first_cluster, size, unlinked: false })
+        });
+        inodes.insert(ino, Arc::downgrade(&inode));
+        inode
+    }
+
+    fn read_sector(&self, sector: usize, buf: &mut [u8]) -> Result<(), FsError> {
+        self.device.read_block(sector, buf).map_err(|e| {
+            error!("[FS] Failed to read block {}: {}", sector, e);
+            FsError::Io(sector)
+        })
+    }
+
+    fn write_sector(&self, sector: usize, buf: &[u8]) -> Result<(), FsError> {
+        self.device.write_block(sector, buf).map_err(|e| {
+            error!("[FS] Failed to write block {}: {}", sector, e);
+            FsError::Io(sector)
+        })
+    }
+
+    fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster * BLOCK_SIZE
+    }
+
+    fn is_valid_cluster(&self, cluster: u32) -> bool {
+        cluster >= FIRST_CLUSTER && (cluster as usize) < self.cluster_count + FIRST_CLUSTER as usize
+    }
+
+    /// Sector holding byte `pos` of the data in `chain`, and where in the sector it is
+    fn locate(&self, chain: &[u32], pos: usize) -> (usize, usize) {
+        let cluster = chain[pos / self.cluster_size()];
+        let sector = self.data_start + (cluster - FIRST_CLUSTER) as usize * self.sectors_per_cluster
+            + pos % self.cluster_size() / BLOCK_SIZE;
+        (sector, pos % BLOCK_SIZE)
+    }
+
+    /// Clusters of the chain starting at `first`, none for a file without data
+    fn chain(&self, first: u32) -> Result<Vec<u32>, FsError> {
+        let mut chain = Vec::new();
+        if first == 0 {
+            return Ok(chain);
+        }
+        let mut buf = [0u8; BLOCK_SIZE];
+        let mut loaded = None;
+        let mut cluster = first;
+        loop {
+            // a loop in the chain would make it longer than the volume
+            if !self.is_valid_cluster(cluster) || chain.len() >= self.cluster_count {
+                error!("[FS] Broken FAT32 cluster chain from {}", first);
+                return Err(FsError::Corrupted);
+            }
+            chain.push(cluster);
+            let sector = self.fat_start + cluster as usize * 4 / BLOCK_SIZE;
+            if loaded != Some(sector) {
+                self.read_sector(sector, &mut buf)?;
+                loaded = Some(sector);
+            }
+            let next = le32(&buf, cluster as usize * 4 % BLOCK_SIZE) & FAT_ENTRY_MASK;
+            if next >= END_OF_CHAIN {
+                return Ok(chain);
+            }
+            cluster = next;
+        }
+    }
+
+    /// Set the entry of `cluster` in every copy of the FAT
+    fn set_fat_entry(&self, cluster: u32, value: u32) -> Result<(), FsError> {
+        let offset = cluster as usize * 4;
+        let mut buf = [0u8; BLOCK_SIZE];
+        for fat in 0..self.fat_count {
+            let sector = self.fat_start + fat * self.fat_sectors + offset / BLOCK_SIZE;
+            self.read_sector(sector, &mut buf)?;
+            let old = le32(&buf, offset % BLOCK_SIZE);
+            let new = old & !FAT_ENTRY_MASK | value & FAT_ENTRY_MASK;
+            buf[offset % BLOCK_SIZE..offset % BLOCK_SIZE + 4].copy_from_slice(&new.to_le_bytes());
+            self.write_sector(sector, &buf)?;
+        }
+        Ok(())
+    }
+
+    fn find_free_cluster(&self) -> Result<u32, FsError> {
+        let end = self.cluster_count as u32 + FIRST_CLUSTER;
+        let start = *self.next_free.exclusive_access();
+        let mut buf = [0u8; BLOCK_SIZE];
+        let mut loaded = None;
+        for cluster in (start..end).chain(FIRST_CLUSTER..start) {
+            let sector = self.fat_start + cluster as usize * 4 / BLOCK_SIZE;
+            if loaded != Some(sector) {
+                self.read_sector(sector, &mut buf)?;
+                loaded = Some(sector);
+            }
+            if le32(&buf, cluster as usize * 4 % BLOCK_SIZE) & FAT_ENTRY_MASK == 0 {
+                return Ok(cluster);
+            }
+        }
+        Err(FsError::NoSpace)
+    }
+
+    /// Take a free cluster, zeroed and linked after `prev` if there is one
+    fn alloc_cluster(&self, prev: Option<u32>) -> Result<u32, FsError> {
+        let cluster = self.find_free_cluster()?;
+        self.set_fat_entry(cluster, FAT_ENTRY_MASK)?;
+        let zeros = [0u8; BLOCK_SIZE];
+        let (first_sector, _) = self.locate(&[cluster], 0);
+        for sector in first_sector..first_sector + self.sectors_per_cluster {
+            self.write_sector(sector, &zeros)?;
+        }
+        if let Some(prev) = prev {
+            self.set_fat_entry(prev, cluster)?;
+        }
+        let next = cluster + 1;
+        *self.next_free.exclusive_access() = if self.is_valid_cluster(next) { next } else { FIRST_CLUSTER };
+        Ok(cluster)
+    }
+
+    /// Grow `chain` to `clusters` clusters, it keeps what it got if the volume fills up
+    fn extend_chain(&self, chain: &mut Vec<u32>, clusters: usize) -> Result<(), FsError> {
+        while chain.len() < clusters {
+            let cluster = self.alloc_cluster(chain.last().copied())?;
+            chain.push(cluster);
+        }
+        Ok(())
+    }
+
+    fn free_clusters(&self, clusters: &[u32]) -> Result<(), FsError> {
+        clusters.iter().try_for_each(|&cluster| self.set_fat_entry(cluster, 0))
+    }
+
+    /// Read from `offset` of the data in `chain`, which has to be long enough
+    fn read_chain(&self, chain: &[u32], offset: usize, buf: &mut [u8]) -> Result<(), FsError> {
+        let mut sector_buf = [0u8; BLOCK_SIZE];
+        let mut done = 0;
+        while done < buf.len() {
+            let (sector, in_sector) = self.locate(chain, offset + done);
+            let len = (BLOCK_SIZE - in_sector).min(buf.len() - done);
+            self.read_sector(sector, &mut sector_buf)?;
+            buf[done..done + len].copy_from_slice(&sector_buf[in_sector..in_sector + len]);
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// Write at `offset` of the data in `chain`, which has to be long enough
+    fn write_chain(&self, chain: &[u32], offset: usize, buf: &[u8]) -> Result<(), FsError> {
+        let mut sector_buf = [0u8; BLOCK_SIZE];
+        let mut done = 0;
+        while done < buf.len() {
+            let (sector, in_sector) = self.locate(chain, offset + done);
+            let len = (BLOCK_SIZE - in_sector).min(buf.len() - done);
+            if len == BLOCK_SIZE {
+                self.write_sector(sector, &buf[done..done + len])?;
+            } else {
+                self.read_sector(sector, &mut sector_buf)?;
+                sector_buf[in_sector..in_sector + len].copy_from_slice(&buf[done..done + len]);
+                self.write_sector(sector, &sector_buf)?;
+            }
+            done += len;
+        }
+        Ok(())
+    }
+
+    fn zero_chain(&self, chain: &[u32], range: Range<usize>) -> Result<(), FsError> {
+        let zeros = [0u8; BLOCK_SIZE];
+        let mut pos = range.start;
+        while pos < range.end {
+            let len = (BLOCK_SIZE - pos % BLOCK_SIZE).min(range.end - pos);
+            self.write_chain(chain, pos, &zeros[..len])?;
+            pos += len;
+        }
+        Ok(())
+    }
+
+    /// Inode number of the directory entry at `slot` of the directory in `chain`, which is
+    /// its position on the disk, and the sector and offset of the entry
+    fn slot_location(&self, chain: &[u32], slot: usize) -> (u64, (usize, usize)) {
+        let (sector, offset) = self.locate(chain, slot * DIR_ENTRY_SIZE);
+        (((sector * BLOCK_SIZE + offset) / DIR_ENTRY_SIZE) as u64, (sector, offset))
+    }
+}
+
+struct InodeState {
+    /// 0 while a file has no data
+    first_cluster: u32,
+    size: usize,
+    /// The entry is gone, the clusters are freed once the inode isn't used any more
+    unlinked: bool
+}
+
+struct FatInode {
+    fs: Arc<Fat32>,
+    ino: u64,
+    kind: InodeKind,
+    /// Sector and offset of the short entry describing the inode, the root has none
+    entry: Option<(usize, usize)>,
+    state: SingleThreadSafeCell<InodeState>
+}
+
+/// An entry of a directory, with its long name if it has one
+struct RawEntry {
+    name: String,
+    short_name: [u8; 11],
+    attr: u8,
+    first_cluster: u32,
+    size: usize,
+    /// Slots taken, from the first long name entry to the short entry
+    slots: Range<usize>
+}
+
+impl RawEntry {
+    fn kind(&self) -> InodeKind {
+        if self.attr & ATTR_DIRECTORY != 0 {
+            InodeKind::Directory
+        } else {
+            InodeKind::Regular
+        }
+    }
+
+    /// Names are compared ignoring case, the short name works too
+    fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || short_name_string(&self.short_name, 0).eq_ignore_ascii_case(name)
+    }
+}
+
+/// Long name entries seen so far, they come last part first
+struct LongName {
+    units: Vec<u16>,
+    checksum: u8,
+    /// Order of the part expected next, 0 once all of them have been seen
+    next: u8,
+    first_slot: usize
+}
+
+impl LongName {
+    fn name(&self) -> Option<String> {
+        let len = self.units.iter().position(|&unit| unit == 0).unwrap_or(self.units.len());
+        let name: String = char::decode_utf16(self.units[..len].iter().copied())
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+fn checksum(short_name: &[u8; 11]) -> u8 {
+    short_name.iter().fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// The short name as shown, `NAME.EXT` or lower case if the entry says so
+fn short_name_string(short_name: &[u8; 11], case: u8) -> String {
+    let part = |bytes: &[u8], lower: bool| -> String {
+        let len = bytes.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+        bytes[..len].iter()
+            .map(|&b| if lower { char::from(b.to_ascii_lowercase()) } else { char::from(b) })
+            .collect()
+    };
+    let mut bytes = *short_name;
+    if bytes[0] == ESCAPED_DELETED {
+        bytes[0] = DELETED;
+    }
+    let mut name = part(&bytes[..8], case & CASE_LOWER_BASE != 0);
+    let ext = part(&bytes[8..], case & CASE_LOWER_EXT != 0);
+    if !ext.is_empty() {
+        name.push('.');
+        name.push_str(&ext);
+    }
+    name
+}
+
+/// Entries of a directory, deleted ones, the volume label, `.` and `..` left out
+fn parse_dir(data: &[u8]) -> Vec<RawEntry> {
+    let mut entries = Vec::new();
+    let mut long_name: Option<LongName> = None;
+    for (slot, raw) in data.chunks_exact(DIR_ENTRY_SIZE).enumerate() {
+        match raw[0] {
+            END_OF_DIR => break,
+            DELETED => {
+                long_name = None;
+                continue;
+            },
+            _ => {}
+        }
+        let attr = raw[11];
+        if attr & ATTR_LONG_NAME_MASK == ATTR_LONG_NAME {
+            let order = raw[0] & LFN_ORDER_MASK;
+            if raw[0] & LFN_LAST != 0 {
+                long_name = Some(LongName {
+                    units: vec![0; order as usize * LFN_CHARS],
+                    checksum: raw[13],
+                    next: order,
+                    first_slot: slot
+                });
+            }
+            // parts out of order make the whole name unusable
+            long_name = long_name.filter(|long_name| order != 0 && long_name.next == order && long_name.checksum == raw[13]);
+            if let Some(long_name) = &mut long_name {
+                let start = (order as usize - 1) * LFN_CHARS;
+                for (i, &offset) in LFN_CHAR_OFFSETS.iter().enumerate() {
+                    long_name.units[start + i] = le16(raw, offset);
+                }
+                long_name.next -= 1;
+            }
+            continue;
+        }
+        let long_name = long_name.take();
+        let short_name: [u8; 11] = raw[..11].try_into().unwrap();
+        if attr & ATTR_VOLUME_ID != 0 || short_name == DOT || short_name == DOT_DOT {
+            continue;
+        }
+        let long_name = long_name
+            .filter(|long_name| long_name.next == 0 && long_name.checksum == checksum(&short_name))
+            .and_then(|long_name| Some((long_name.name()?, long_name.first_slot)));
+        let (name, first_slot) = long_name.unwrap_or_else(|| (short_name_string(&short_name, raw[12]), slot));
+        entries.push(RawEntry {
+            name,
+            short_name,
+            attr,
+            first_cluster: (le16(raw, 20) as u32) << 16 | le16(raw, 26) as u32,
+            size: le32(raw, 28) as usize,
+            slots: first_slot..slot + 1
+        });
+    }
+    entries
+}
+
+fn check_name(name: &str) -> Result<(), FileError> {
+    if name.encode_utf16().count() > MAX_NAME_LENGTH {
+        return Err(FsError::NameTooLong.into());
+    }
+    // trailing dots and spaces are dropped by other systems, the name wouldn't be found there
+    if name.ends_with(['.', ' ']) || name.chars().any(|c| c < ' ' || INVALID_NAME_CHARS.contains(&c)) {
+        return Err(FileError::InvalidArgument);
+    }
+    Ok(())
+}
+
+fn is_short_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c.is_ascii() && SHORT_NAME_SPECIALS.contains(&(c as u8))
+}
+
+fn split_name(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => (name, "")
+    }
+}
+
+/// `name` as a short name if it is one, maybe in lower case, with the case flags to record
+fn exact_short_name(name: &str) -> Option<([u8; 11], u8)> {
+    let (base, ext) = split_name(name);
+    if base.is_empty() || base.len() > 8 || ext.len() > 3 || !base.chars().chain(ext.chars()).all(is_short_name_char) {
+        return None;
+    }
+    // a mixed case part can only be kept by a long name
+    let lower = |part: &str, flag: u8| {
+        let has_lower = part.chars().any(|c| c.is_ascii_lowercase());
+        let has_upper = part.chars().any(|c| c.is_ascii_uppercase());
+        match (has_lower, has_upper) {
+            (true, true) => None,
+            (true, false) => Some(flag),
+            _ => Some(0)
+        }
+    };
+    let case = lower(base, CASE_LOWER_BASE)? | lower(ext, CASE_LOWER_EXT)?;
+    let mut short_name = [b' '; 11];
+    short_name[..base.len()].copy_from_slice(base.to_ascii_uppercase().as_bytes());
+    short_name[8..8 + ext.len()].copy_from_slice(ext.to_ascii_uppercase().as_bytes());
+    Some((short_name, case))
+}
+
+/// A `BASIS~N.EXT` short name for a name that needs long name entries, not in `taken`
+fn generated_short_name(name: &str, taken: &[[u8; 11]]) -> Result<[u8; 11], FsError> {
+    let sanitize = |part: &str| -> Vec<u8> {
+        part.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| if is_short_name_char(c) { c.to_ascii_uppercase() as u8 } else { b'_' })
+            .collect()
+    };
+    let (base, ext) = split_name(name.trim_start_matches('.'));
+    let mut base = sanitize(base);
+    if base.is_empty() {
+        base.push(b'_');
+    }
+    let mut ext = sanitize(ext);
+    ext.truncate(3);
+    for n in 1..1_000_000 {
+        let tail = format!("~{}", n);
+        let keep = base.len().min(8 - tail.len());
+        let mut short_name = [b' '; 11];
+        short_name[..keep].copy_from_slice(&base[..keep]);
+        short_name[keep..keep + tail.len()].copy_from_slice(tail.as_bytes());
+        short_name[8..8 + ext.len()].copy_from_slice(&ext);
+        if !taken.contains(&short_name) {
+            return Ok(short_name);
+        }
+    }
+    Err(FsError::NoSpace)
+}
+
+/// Long name entries for `name`, in the order they are stored, last part first
+fn long_name_entries(name: &str, short_name: &[u8; 11]) -> Vec<[u8; DIR_ENTRY_SIZE]> {
+    let units: Vec<u16> = name.encode_utf16().collect();
+    let count = units.len().div_ceil(LFN_CHARS);
+    let checksum = checksum(short_name);
+    (1..=count).rev()
+        .map(|order| {
+            let mut raw = [0u8; DIR_ENTRY_SIZE];
+            raw[0] = order as u8 | if order == count { LFN_LAST } else { 0 };
+            raw[11] = ATTR_LONG_NAME;
+            raw[13] = checksum;
+            for (i, &offset) in LFN_CHAR_OFFSETS.iter().enumerate() {
+                let index = (order - 1) * LFN_CHARS + i;
+                // the name ends with a nul if there is room, the rest is padding
+                let unit = match index.cmp(&units.len()) {
+                    core::cmp::Ordering::Less => units[index],
+                    core::cmp::Ordering::Equal => 0,
+                    core::cmp::Ordering::Greater => 0xFFFF
+                };
+                raw[offset..offset + 2].copy_from_slice(&unit.to_le_bytes());
+            }
+            raw
+        })
+        .collect()
+}
+
+fn short_entry(short_name: &[u8; 11], case: u8, attr: u8, first_cluster: u32) -> [u8; DIR_ENTRY_SIZE] {
+    let mut raw = [0u8; DIR_ENTRY_SIZE];
+    raw[..11].copy_from_slice(short_name);
+    raw[11] = attr;
+    raw[12] = case;
+    // creation, access and modification dates
+    for offset in [16, 18, 24] {
+        raw[offset..offset + 2].copy_from_slice(&DEFAULT_DATE.to_le_bytes());
+    }
+    raw[20..22].copy_from_slice(&((first_cluster >> 16) as u16).to_le_bytes());
+    raw[26..28].copy_from_slice(&(first_cluster as u16).to_le_bytes());
+    raw
+}
+
+impl FatInode {
+    /// Clusters and whole content of the directory
+    fn dir_content(&self) -> Result<(Vec<u32>, Vec<u8>), FileError> {
+        if self.kind != InodeKind::Directory {
+            return Err(FsError::NotADirectory.into());
+        }
+        let chain = self.fs.chain(self.state.exclusive_access().first_cluster)?;
+        let mut data = vec![0u8; chain.len() * self.fs.cluster_size()];
+        self.fs.read_chain(&chain, 0, &mut data)?;
+        Ok((chain, data))
+    }
+
+    fn child(&self, chain: &[u32], entry: &RawEntry) -> Arc<FatInode> {
+        let (ino, location) = self.fs.slot_location(chain, entry.slots.end - 1);
+        self.fs.inode(ino, entry.kind(), Some(location), entry.first_cluster, entry.size)
+    }
+
+    /// Write the first cluster and the size back to the entry in the parent directory
+    fn sync_entry(&self, state: &InodeState) -> Result<(), FsError> {
+        let Some((sector, offset)) = self.entry else {
+            return Ok(());
+        };
+        if state.unlinked {
+            return Ok(());
+        }
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.fs.read_sector(sector, &mut buf)?;
+        let raw = &mut buf[offset..offset + DIR_ENTRY_SIZE];
+        raw[20..22].copy_from_slice(&((state.first_cluster >> 16) as u16).to_le_bytes());
+        raw[26..28].copy_from_slice(&(state.first_cluster as u16).to_le_bytes());
+        if self.kind == InodeKind::Regular {
+            raw[28..32].copy_from_slice(&(state.size as u32).to_le_bytes());
+        }
+        self.fs.write_sector(sector, &buf)
+    }
+
+    /// A cluster for a new subdirectory, holding `.` and `..`
+    fn new_dir_cluster(&self) -> Result<u32, FsError> {
+        let cluster = self.fs.alloc_cluster(None)?;
+        // `..` of the children of the root says 0 rather than the root cluster
+        let parent = match self.entry {
+            Some(_) => self.state.exclusive_access().first_cluster,
+            None => 0
+        };
+        let mut dots = [0u8; 2 * DIR_ENTRY_SIZE];
+        dots[..DIR_ENTRY_SIZE].copy_from_slice(&short_entry(&DOT, 0, ATTR_DIRECTORY, cluster));
+        dots[DIR_ENTRY_SIZE..].copy_from_slice(&short_entry(&DOT_DOT, 0, ATTR_DIRECTORY, parent));
+        if let Err(e) = self.fs.write_chain(&[cluster], 0, &dots) {
+            self.fs.free_clusters(&[cluster])?;
+            return Err(e);
+        }
+        Ok(cluster)
+    }
+
+    /// Write `slots` to consecutive free slots of the directory, growing it if there are not
+    /// enough, returns the slot of the last one
+    fn place_entries(&self, chain: &mut Vec<u32>, data: &[u8], slots: &[[u8; DIR_ENTRY_SIZE]]) -> Result<usize, FsError> {
+        let total = data.len() / DIR_ENTRY_SIZE;
+        // everything from the end marker on is free
+        let end = (0..total).find(|&slot| data[slot * DIR_ENTRY_SIZE] == END_OF_DIR).unwrap_or(total);
+        let mut run = 0;
+        let mut start = None;
+        for slot in 0..end {
+            if data[slot * DIR_ENTRY_SIZE] == DELETED {
+                run += 1;
+                if run == slots.len() {
+                    start = Some(slot + 1 - run);
+                    break;
+                }
+            } else {
+                run = 0;
+            }
+        }
+        let start = match start {
+            Some(start) => start,
+            None => {
+                // deleted slots just before the end marker are used too
+                let start = end - run;
+                let needed = start + slots.len();
+                if needed > MAX_DIR_ENTRIES {
+                    return Err(FsError::NoSpace);
+                }
+                self.fs.extend_chain(chain, (needed * DIR_ENTRY_SIZE).div_ceil(self.fs.cluster_size()))?;
+                start
+            }
+        };
+        for (i, slot) in slots.iter().enumerate() {
+            self.fs.write_chain(chain, (start + i) * DIR_ENTRY_SIZE, slot)?;
+        }
+        // past the old end marker, whatever follows has to read as the end
+        let after = start + slots.len();
+        if after > end && after < total && data[after * DIR_ENTRY_SIZE] != END_OF_DIR {
+            self.fs.write_chain(chain, after * DIR_ENTRY_SIZE, &[0u8; DIR_ENTRY_SIZE])?;
+        }
+        Ok(after - 1)
+    }
+}
+
+impl Drop for FatInode {
+    fn drop(&mut self) {
+        let state = self.state.exclusive_access();
+        if !state.unlinked || state.first_cluster == 0 {
+            return;
+        }
+        if let Err(e) = self.fs.chain(state.first_cluster).and_then(|chain| self.fs.free_clusters(&chain)) {
+            error!("[FS] Failed to free the clusters of a removed file: {}", e);
+        }
+    }
+}
+
+impl Inode for FatInode {
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn kind(&self) -> InodeKind {
+        self.kind
+    }
+
+    /// Directories have no size recorded
+    fn size(&self) -> Result<usize, FileError> {
+        Ok(self.state.exclusive_access().size)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, FileError> {
+        if self.kind == InodeKind::Directory {
+            return Err(FsError::IsADirectory.into());
+        }
+        let state = self.state.exclusive_access();
+        let end = state.size.min(offset.saturating_add(buf.len()));
+        if end <= offset {
+            return Ok(0);
+        }
+        let chain = self.fs.chain(state.first_cluster)?;
+        if chain.len() * self.fs.cluster_size() < end {
+            return Err(FsError::Corrupted.into());
+        }
+        self.fs.read_chain(&chain, offset, &mut buf[..end - offset])?;
+        Ok(end - offset)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, FileError> {
+        if self.kind == InodeKind::Directory {
+            return Err(FsError::IsADirectory.into());
+        }
+        // sizes are 32 bits
+        let end = offset.checked_add(buf.len())
+            .filter(|&end| end <= u32::MAX as usize)
+            .ok_or(FsError::FileTooLarge)?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut state = self.state.exclusive_access();
+        let mut chain = self.fs.chain(state.first_cluster)?;
+        let allocated = chain.len() * self.fs.cluster_size();
+        let result = self.fs.extend_chain(&mut chain, end.div_ceil(self.fs.cluster_size()));
+        state.first_cluster = chain.first().copied().unwrap_or(0);
+        let end = end.min(chain.len() * self.fs.cluster_size());
+        if end <= offset {
+            self.sync_entry(&state)?;
+            result?;
+            return Err(FsError::NoSpace.into());
+        }
+        // new clusters come zeroed, only what was there beyond the end of file needs it
+        if offset > state.size {
+            self.fs.zero_chain(&chain, state.size..offset.min(allocated))?;
+        }
+        self.fs.write_chain(&chain, offset, &buf[..end - offset])?;
+        state.size = state.size.max(end);
+        self.sync_entry(&state)?;
+        Ok(end - offset)
+    }
+
+    fn truncate(&self, size: usize) -> Result<(), FileError> {
+        if self.kind == InodeKind::Directory {
+            return Err(FsError::IsADirectory.into());
+        }
+        if size > u32::MAX as usize {
+            return Err(FsError::FileTooLarge.into());
+        }
+        let mut state = self.state.exclusive_access();
+        let mut chain = self.fs.chain(state.first_cluster)?;
+        let clusters = size.div_ceil(self.fs.cluster_size());
+        if size > state.size {
+            let allocated = chain.len() * self.fs.cluster_size();
+            let result = self.fs.extend_chain(&mut chain, clusters);
+            state.first_cluster = chain.first().copied().unwrap_or(0);
+            if let Err(e) = result {
+                self.sync_entry(&state)?;
+                return Err(e.into());
+            }
+            self.fs.zero_chain(&chain, state.size..size.min(allocated))?;
+        } else if clusters < chain.len() {
+            // cut the chain before freeing its tail
+            match clusters {
+                0 => state.first_cluster = 0,
+                _ => self.fs.set_fat_entry(chain[clusters - 1], FAT_ENTRY_MASK)?
+            }
+            self.sync_entry(&state)?;
+            self.fs.free_clusters(&chain[clusters..])?;
+        }
+        state.size = size;
+        self.sync_entry(&state)?;
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FileError> {
+        let (chain, data) = self.dir_content()?;
+        match parse_dir(&data).iter().find(|entry| entry.matches(name)) {
+            Some(entry) => Ok(self.child(&chain, entry)),
+            None => Err(FsError::NotFound.into())
+        }
+    }
+
+    fn create(&self, name: &str, kind: InodeKind) -> Result<Arc<dyn Inode>, FileError> {
+        let attr = match kind {
+            InodeKind::Regular => ATTR_ARCHIVE,
+            InodeKind::Directory => ATTR_DIRECTORY,
+            InodeKind::CharDevice => return Err(FileError::InvalidArgument)
+        };
+        let (mut chain, data) = self.dir_content()?;
+        if self.state.exclusive_access().unlinked {
+            return Err(FsError::NotFound.into());
+        }
+        check_name(name)?;
+        let entries = parse_dir(&data);
+        if entries.iter().any(|entry| entry.matches(name)) {
+            return Err(FsError::AlreadyExists.into());
+        }
+        let (short_name, case, mut slots) = match exact_short_name(name) {
+            Some((short_name, case)) => (short_name, case, Vec::new()),
+            None => {
+                let taken: Vec<[u8; 11]> = entries.iter().map(|entry| entry.short_name).collect();
+                let short_name = generated_short_name(name, &taken)?;
+                (short_name, 0, long_name_entries(name, &short_name))
+            }
+        };
+        let first_cluster = match kind {
+            InodeKind::Directory => self.new_dir_cluster()?,
+            _ => 0
+        };
+        slots.push(short_entry(&short_name, case, attr, first_cluster));
+        let slot = match self.place_entries(&mut chain, &data, &slots) {
+            Ok(slot) => slot,
+            Err(e) => {
+                if first_cluster != 0 {
+                    self.fs.free_clusters(&[first_cluster])?;
+                }
+                return Err(e.into());
+            }
+        };
+        let (ino, location) = self.fs.slot_location(&chain, slot);
+        Ok(self.fs.inode(ino, kind, Some(location), first_cluster, 0))
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), FileError> {
+        let (chain, data) = self.dir_content()?;
+        let entries = parse_dir(&data);
+        let entry = entries.iter().find(|entry| entry.matches(name)).ok_or(FsError::NotFound)?;
+        let child = self.child(&chain, entry);
+        if child.kind == InodeKind::Directory && !parse_dir(&child.dir_content()?.1).is_empty() {
+            return Err(FsError::DirectoryNotEmpty.into());
+        }
+        for slot in entry.slots.clone() {
+            self.fs.write_chain(&chain, slot * DIR_ENTRY_SIZE, &[DELETED])?;
+        }
+        // the slot may be reused by another file, which must not get this inode
+        self.fs.inodes.exclusive_access().remove(&child.ino);
+        child.state.exclusive_access().unlinked = true;
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<Vec<DirEntry>, FileError> {
+        let (chain, data) = self.dir_content()?;
+        Ok(parse_dir(&data).into_iter()
+            .map(|entry| {
+                let (ino, _) = self.fs.slot_location(&chain, entry.slots.end - 1);
+                let kind = entry.kind();
+                DirEntry { name: entry.name, ino, kind }
+            })
+            .collect())
+    }
+}
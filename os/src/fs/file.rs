@@ -0,0 +1,150 @@
+use alloc::sync::Arc;
+use easy_fs::FsError;
+use thiserror::Error;
+
+use crate::fs::vfs::Dentry;
+
+#[derive(Debug, Error)]
+pub enum FileError {
+    /// Nothing can be transferred right now, the caller should try again later
+    #[error("operation would block")]
+    WouldBlock,
+    #[error("file not opened for this operation")]
+    BadAccess,
+    #[error("invalid argument")]
+    InvalidArgument,
+    #[error("illegal seek")]
+    IllegalSeek,
+    #[error("broken pipe")]
+    BrokenPipe,
+    #[error("resource busy")]
+    Busy,
+    #[error("out of memory")]
+    OutOfMemory,
+    #[error(transparent)]
+    Fs(#[from] FsError)
+}
+
+bitflags! {
+    #[derive(Clone, Copy, Debug)]
+    pub struct OpenFlags: u32 {
+        const WRONLY = 0o1;
+        const RDWR = 0o2;
+        const CREAT = 0o100;
+        const TRUNC = 0o1000;
+        const APPEND = 0o2000;
+        const DIRECTORY = 0o200000;
+    }
+}
+
+impl OpenFlags {
+    /// Whether the file is opened for reading and for writing, `O_RDONLY` is the absence of
+    /// the other access modes
+    pub fn access(&self) -> (bool, bool) {
+        if self.contains(OpenFlags::RDWR) {
+            (true, true)
+        } else if self.contains(OpenFlags::WRONLY) {
+            (false, true)
+        } else {
+            (true, false)
+        }
+    }
+}
+
+pub enum SeekFrom {
+    Start(usize),
+    Current(isize),
+    End(isize)
+}
+
+impl SeekFrom {
+    /// From the `whence` and `offset` arguments of `lseek`
+    pub fn from_whence(whence: usize, offset: isize) -> Option<Self> {
+        match whence {
+            0 => Some(SeekFrom::Start(offset as usize)),
+            1 => Some(SeekFrom::Current(offset)),
+            2 => Some(SeekFrom::End(offset)),
+            _ => None
+        }
+    }
+}
+
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+
+/// `struct stat` of Linux riscv64
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    __pad: u64,
+    pub size: i64,
+    pub blksize: i32,
+    __pad2: i32,
+    pub blocks: i64,
+    pub atime_sec: i64,
+    pub atime_nsec: i64,
+    pub mtime_sec: i64,
+    pub mtime_nsec: i64,
+    pub ctime_sec: i64,
+    pub ctime_nsec: i64,
+    __unused: [u32; 2]
+}
+const_assert_eq!(size_of::<Stat>(), 128);
+
+impl Stat {
+    /// Block counts are in 512 bytes units whatever the file system, timestamps aren't kept
+    pub fn new(ino: u64, mode: u32, size: usize) -> Self {
+        Stat {
+            dev: 0,
+            ino,
+            mode,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            __pad: 0,
+            size: size as i64,
+            blksize: 512,
+            __pad2: 0,
+            blocks: size.div_ceil(512) as i64,
+            atime_sec: 0,
+            atime_nsec: 0,
+            mtime_sec: 0,
+            mtime_nsec: 0,
+            ctime_sec: 0,
+            ctime_nsec: 0,
+            __unused: [0; 2]
+        }
+    }
+}
+
+/// An opened file, shared by every descriptor (and process) it was duplicated to
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+    /// Read at the current position, 0 means end of file
+    fn read(&self, buf: &mut [u8]) -> Result<usize, FileError>;
+    fn write(&self, buf: &[u8]) -> Result<usize, FileError>;
+    /// Move the position, returns the new one
+    fn seek(&self, _pos: SeekFrom) -> Result<usize, FileError> {
+        Err(FileError::IllegalSeek)
+    }
+    fn stat(&self) -> Result<Stat, FileError>;
+    /// Where the file is in the tree, for files that have a path
+    fn dentry(&self) -> Option<Arc<Dentry>> {
+        None
+    }
+    /// Fill `buf` with `struct linux_dirent64` records from the current position
+    fn getdents(&self, _buf: &mut [u8]) -> Result<usize, FileError> {
+        Err(FsError::NotADirectory.into())
+    }
+}
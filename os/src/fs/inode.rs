@@ -0,0 +1,159 @@
+use alloc::sync::Arc;
+use easy_fs::FsError;
+
+use crate::fs::devfs;
+use crate::fs::file::{File, FileError, OpenFlags, SeekFrom, Stat};
+use crate::fs::page_cache::{self, PageCache};
+use crate::fs::vfs::{self, Dentry, InodeKind};
+use crate::helper::cell::SingleThreadSafeCell;
+
+/// Size of `struct linux_dirent64` without the name
+const DIRENT64_HEADER_SIZE: usize = 19;
+
+/// A file or directory opened by a process, whatever file system it is on
+pub struct OSInode {
+    readable: bool,
+    writable: bool,
+    append: bool,
+    dentry: Arc<Dentry>,
+    /// Byte offset for files, index of the next entry for directories
+    offset: SingleThreadSafeCell<usize>
+}
+
+impl OSInode {
+    pub fn new(dentry: Arc<Dentry>, flags: OpenFlags) -> Self {
+        let (readable, writable) = flags.access();
+        OSInode {
+            readable,
+            writable,
+            append: flags.contains(OpenFlags::APPEND),
+            dentry,
+            offset: SingleThreadSafeCell::new(0)
+        }
+    }
+}
+
+impl File for OSInode {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize, FileError> {
+        if !self.readable {
+            return Err(FileError::BadAccess);
+        }
+        if self.dentry.is_dir() {
+            return Err(FsError::IsADirectory.into());
+        }
+        let mut offset = self.offset.exclusive_access();
+        let len = match PageCache::lookup(&self.dentry) {
+            Some(cache) => cache.read(*offset, buf)?,
+            None => self.dentry.inode().read_at(*offset, buf)?
+        };
+        *offset += len;
+        Ok(len)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, FileError> {
+        if !self.writable {
+            return Err(FileError::BadAccess);
+        }
+        let inode = self.dentry.inode();
+        let mut offset = self.offset.exclusive_access();
+        if self.append {
+            *offset = inode.size()?;
+        }
+        let len = match PageCache::lookup(&self.dentry) {
+            Some(cache) => cache.write(*offset, buf)?,
+            None => inode.write_at(*offset, buf)?
+        };
+        *offset += len;
+        Ok(len)
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<usize, FileError> {
+        let mut offset = self.offset.exclusive_access();
+        let new_offset = match pos {
+            SeekFrom::Start(new_offset) => Some(new_offset),
+            SeekFrom::Current(delta) => offset.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.dentry.inode().size()?.checked_add_signed(delta)
+        }.ok_or(FileError::InvalidArgument)?;
+        *offset = new_offset;
+        Ok(new_offset)
+    }
+
+    fn stat(&self) -> Result<Stat, FileError> {
+        self.dentry.inode().stat()
+    }
+
+    fn dentry(&self) -> Option<Arc<Dentry>> {
+        Some(self.dentry.clone())
+    }
+
+    /// Entries are `.`, `..`, then what the file system lists
+    fn getdents(&self, buf: &mut [u8]) -> Result<usize, FileError> {
+        if !self.dentry.is_dir() {
+            return Err(FsError::NotADirectory.into());
+        }
+        let dot = (self.dentry.inode().ino(), InodeKind::Directory);
+        let dot_dot = (self.dentry.parent().inode().ino(), InodeKind::Directory);
+        let entries = self.dentry.inode().entries()?;
+        let all = [(".", dot), ("..", dot_dot)].into_iter()
+            .chain(entries.iter().map(|entry| (entry.name.as_str(), (entry.ino, entry.kind))));
+        let mut offset = self.offset.exclusive_access();
+        let mut len = 0;
+        for (index, (name, (ino, kind))) in all.enumerate().skip(*offset) {
+            let record_len = (DIRENT64_HEADER_SIZE + name.len() + 1).next_multiple_of(8);
+            if len + record_len > buf.len() {
+                if len == 0 {
+                    return Err(FileError::InvalidArgument);
+                }
+                break;
+            }
+            let record = &mut buf[len..len + record_len];
+            record[0..8].copy_from_slice(&ino.to_ne_bytes());
+            record[8..16].copy_from_slice(&(index as i64 + 1).to_ne_bytes());
+            record[16..18].copy_from_slice(&(record_len as u16).to_ne_bytes());
+            record[18] = kind.dirent_type();
+            record[DIRENT64_HEADER_SIZE..DIRENT64_HEADER_SIZE + name.len()].copy_from_slice(name.as_bytes());
+            record[DIRENT64_HEADER_SIZE + name.len()..].fill(0);
+            len += record_len;
+            *offset = index + 1;
+        }
+        Ok(len)
+    }
+}
+
+/// Open `path` relative to `base`, creating it if asked to
+pub fn open_file(base: &Arc<Dentry>, path: &str, flags: OpenFlags) -> Result<Arc<dyn File>, FileError> {
+    let dentry = match vfs::lookup(base, path) {
+        Ok(dentry) => dentry,
+        Err(FileError::Fs(FsError::NotFound)) if flags.contains(OpenFlags::CREAT) => {
+            let (dir, name) = vfs::lookup_parent(base, path)?;
+            dir.inode().create(name, InodeKind::Regular)?;
+            dir.child(name)?
+        },
+        Err(e) => return Err(e)
+    };
+    if let Some(device) = devfs::open_device(&dentry, flags) {
+        if flags.contains(OpenFlags::DIRECTORY) {
+            return Err(FsError::NotADirectory.into());
+        }
+        return Ok(device);
+    }
+    let file = OSInode::new(dentry, flags);
+    if file.dentry.is_dir() && file.writable {
+        return Err(FsError::IsADirectory.into());
+    }
+    if flags.contains(OpenFlags::DIRECTORY) && !file.dentry.is_dir() {
+        return Err(FsError::NotADirectory.into());
+    }
+    if flags.contains(OpenFlags::TRUNC) && file.writable {
+        page_cache::truncate(&file.dentry, 0)?;
+    }
+    Ok(Arc::new(file))
+}
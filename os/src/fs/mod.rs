@@ -0,0 +1,90 @@
+pub(crate) mod devfs;
+pub(crate) mod efs;
+pub(crate) mod fat32;
+pub(crate) mod file;
+pub(crate) mod inode;
+pub(crate) mod mount;
+pub(crate) mod page_cache;
+pub(crate) mod pipe;
+pub(crate) mod procfs;
+pub(crate) mod stdio;
+pub(crate) mod tmpfs;
+pub(crate) mod vfs;
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use easy_fs::FsError;
+use log::{error, info, warn};
+
+use crate::fs::devfs::DevFs;
+use crate::fs::efs::EfsSuperBlock;
+use crate::fs::fat32::Fat32SuperBlock;
+use crate::fs::file::FileError;
+use crate::fs::procfs::ProcFs;
+use crate::fs::tmpfs::TmpFs;
+use crate::fs::vfs::{Dentry, InodeKind, SuperBlock};
+use crate::io::block;
+
+/// Where the FAT32 disk is mounted, if one is attached
+const FAT32_MOUNT_POINT: &str = "mnt";
+
+/// Mount the root file system, the in-memory ones and a FAT32 disk if there is one, the
+/// block devices must have been probed. Which device holds what is told by their content,
+/// the order they are probed in depends on the machine
+pub fn init() {
+    let mut root = None;
+    let mut fat32 = None;
+    for (id, device) in (0..).map_while(|id| block::get(id).map(|device| (id, device))) {
+        if root.is_none() {
+            match EfsSuperBlock::open(device.clone()) {
+                Ok(sb) => {
+                    root = Some(sb);
+                    continue;
+                },
+                Err(FsError::BadMagic) => {},
+                Err(e) => panic!("[FS] Failed to open the root file system on block device {}: {}", id, e)
+            }
+        }
+        match Fat32SuperBlock::open(device) {
+            Ok(sb) if fat32.is_none() => fat32 = Some(sb),
+            Ok(_) => warn!("[FS] Only one FAT32 disk is mounted, block device {} is left alone", id),
+            Err(FsError::BadMagic) => warn!("[FS] No file system known on block device {}", id),
+            Err(e) => error!("[FS] Failed to open the FAT32 file system on block device {}: {}", id, e)
+        }
+    }
+    let root = root.expect("[FS] No block device holds the root file system");
+    mount::mount_root(Arc::new(root));
+    mount_on_root_dir("tmp", Arc::new(TmpFs::new()));
+    mount_on_root_dir("proc", Arc::new(ProcFs));
+    mount_on_root_dir("dev", Arc::new(DevFs::new()));
+    if let Some(sb) = fat32 {
+        mount_on_root_dir(FAT32_MOUNT_POINT, Arc::new(sb));
+        info!("[FS] FAT32 disk mounted on /{}", FAT32_MOUNT_POINT);
+    }
+}
+
+/// Mount `sb` on the directory `name` of the root, which is created if the image lacks it
+fn mount_on_root_dir(name: &str, sb: Arc<dyn SuperBlock>) {
+    let root = mount::root();
+    let fs_type = sb.fs_type();
+    let result = match root.inode().create(name, InodeKind::Directory) {
+        Ok(_) | Err(FileError::Fs(FsError::AlreadyExists)) => mount::mount(name, sb),
+        Err(e) => Err(e)
+    };
+    if let Err(e) = result {
+        error!("[FS] Failed to mount {} on /{}: {}", fs_type, name, e);
+    }
+}
+
+/// Read the whole program at `path`, relative paths start from `base`
+pub fn read_app(base: &Arc<Dentry>, path: &str) -> Option<Vec<u8>> {
+    let inode = vfs::lookup(base, path).ok()?.inode().clone();
+    if inode.kind() != InodeKind::Regular {
+        return None;
+    }
+    let mut data = vec![0u8; inode.size().ok()?];
+    let len = inode.read_at(0, &mut data).ok()?;
+    data.truncate(len);
+    Some(data)
+}
@@ -0,0 +1,61 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use log::info;
+
+use crate::fs::file::FileError;
+use crate::fs::vfs::{self, Dentry, Inode, SuperBlock};
+use crate::helper::cell::SingleThreadSafeCell;
+
+struct Mount {
+    sb: Arc<dyn SuperBlock>,
+    /// Mount id and inode number of the directory it covers, `None` for the root
+    point: Option<(usize, u64)>
+}
+
+struct MountTable {
+    /// Indexed by mount id, mounts are never removed
+    mounts: Vec<Mount>,
+    root: Option<Arc<Dentry>>
+}
+
+lazy_static!{
+    static ref MOUNT_TABLE: SingleThreadSafeCell<MountTable> =
+        SingleThreadSafeCell::new(MountTable { mounts: Vec::new(), root: None });
+}
+
+/// Mount `sb` as the root of the tree, has to come before any other mount
+pub fn mount_root(sb: Arc<dyn SuperBlock>) {
+    let mut table = MOUNT_TABLE.exclusive_access();
+    assert!(table.root.is_none(), "[FS] Root file system mounted twice");
+    info!("[FS] Mounted {} on /", sb.fs_type());
+    table.root = Some(Dentry::new_root(0, sb.root()));
+    table.mounts.push(Mount { sb, point: None });
+}
+
+/// Mount `sb` on the directory `path`, which is covered until the machine powers off
+pub fn mount(path: &str, sb: Arc<dyn SuperBlock>) -> Result<(), FileError> {
+    let point = vfs::lookup(&root(), path)?;
+    if !point.is_dir() {
+        return Err(easy_fs::FsError::NotADirectory.into());
+    }
+    // looking a mount point up leads to the mounted root, so covered directories can't be reached here
+    if point.is_mount_root() {
+        return Err(FileError::Busy);
+    }
+    info!("[FS] Mounted {} on {}", sb.fs_type(), point.path());
+    MOUNT_TABLE.exclusive_access().mounts.push(Mount { sb, point: Some((point.mount_id(), point.inode().ino())) });
+    Ok(())
+}
+
+/// The mount covering the inode `ino` of mount `mount_id`, with the root inode of the mounted file system
+pub fn mounted_on(mount_id: usize, ino: u64) -> Option<(usize, Arc<dyn Inode>)> {
+    let table = MOUNT_TABLE.exclusive_access();
+    table.mounts.iter()
+        .position(|mount| mount.point == Some((mount_id, ino)))
+        .map(|id| (id, table.mounts[id].sb.root()))
+}
+
+pub fn root() -> Arc<Dentry> {
+    MOUNT_TABLE.exclusive_access().root.clone().expect("[FS] No root file system mounted")
+}
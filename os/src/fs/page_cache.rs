@@ -0,0 +1,196 @@
+use alloc::collections::btree_map::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::ops::Range;
+use easy_fs::FsError;
+use lazy_static::lazy_static;
+use log::error;
+
+use crate::fs::file::FileError;
+use crate::fs::vfs::{Dentry, DirEntry, Inode, InodeKind};
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::mm::address::{PhysPageNumber, PAGE_SIZE_BYTES};
+use crate::mm::frame_allocator::{Frame, FrameAllocator, FRAME_ALLOCATOR};
+
+/// Files are told apart by their inode, every user of a file shares the same one. Inode
+/// numbers won't do, file systems hand them out again once a file is removed
+type FileKey = usize;
+
+lazy_static!{
+    /// Caches of the files mapped somewhere, a cache goes away with the last mapping. It keeps
+    /// its inode alive, so no other file can take its key meanwhile
+    static ref PAGE_CACHES: SingleThreadSafeCell<BTreeMap<FileKey, Weak<PageCache>>> = SingleThreadSafeCell::new(BTreeMap::new());
+}
+
+struct CachedPage {
+    frame: Frame,
+    /// Mapped writable and shared, so it may differ from the file
+    dirty: bool
+}
+
+/// Pages of a file, shared by every mapping of it. While the file is mapped its reads and
+/// writes go through the cache too, so that they agree with what the mappings see
+pub struct PageCache {
+    inode: Arc<dyn Inode>,
+    pages: SingleThreadSafeCell<BTreeMap<usize, CachedPage>>
+}
+
+fn key_of(dentry: &Dentry) -> FileKey {
+    Arc::as_ptr(dentry.inode()) as *const () as usize
+}
+
+impl PageCache {
+    /// The cache of the file behind `dentry`, created if the file isn't mapped yet
+    pub fn of(dentry: &Dentry) -> Arc<PageCache> {
+        let mut caches = PAGE_CACHES.exclusive_access();
+        if let Some(cache) = caches.get(&key_of(dentry)).and_then(Weak::upgrade) {
+            return cache;
+        }
+        caches.retain(|_, cache| cache.strong_count() > 0);
+        let cache = Arc::new(PageCache { inode: dentry.inode().clone(), pages: SingleThreadSafeCell::new(BTreeMap::new()) });
+        caches.insert(key_of(dentry), Arc::downgrade(&cache));
+        cache
+    }
+
+    /// Zeroed pages backed by no file, shared by the mappings that hold the cache. Being
+    /// empty, the file behind it is never read or written
+    pub fn anonymous() -> Arc<PageCache> {
+        Arc::new(PageCache { inode: Arc::new(AnonymousInode), pages: SingleThreadSafeCell::new(BTreeMap::new()) })
+    }
+
+    /// The cache of the file behind `dentry` if the file is mapped somewhere
+    pub fn lookup(dentry: &Dentry) -> Option<Arc<PageCache>> {
+        PAGE_CACHES.exclusive_access().get(&key_of(dentry)).and_then(Weak::upgrade)
+    }
+
+    /// Frame holding page `index` of the file, read from the file on first use. What is past
+    /// the end of the file reads as zeros
+    pub fn page(&self, index: usize) -> Result<PhysPageNumber, FileError> {
+        let mut pages = self.pages.exclusive_access();
+        if let Some(page) = pages.get(&index) {
+            return Ok(page.frame.ppn());
+        }
+        let ppn = FRAME_ALLOCATOR.exclusive_access().alloc().ok_or(FileError::OutOfMemory)?;
+        let frame = Frame::new(ppn);
+        let offset = index * PAGE_SIZE_BYTES;
+        // files without a size, like the ones in /proc, are never read
+        if offset < self.inode.size()? {
+            self.inode.read_at(offset, ppn.get_mut_array::<u8>())?;
+        }
+        pages.insert(index, CachedPage { frame, dirty: false });
+        Ok(ppn)
+    }
+
+    /// Page `index` is about to be mapped writable by a shared mapping
+    pub fn mark_dirty(&self, index: usize) {
+        if let Some(page) = self.pages.exclusive_access().get_mut(&index) {
+            page.dirty = true;
+        }
+    }
+
+    /// Write the dirty pages in `range` back to the file, up to its end. They stay dirty, the
+    /// mappings can write to them without the cache knowing
+    pub fn sync(&self, range: Range<usize>) -> Result<(), FileError> {
+        let size = self.inode.size()?;
+        let pages = self.pages.exclusive_access();
+        for (index, page) in pages.range(range).filter(|(_, page)| page.dirty) {
+            let offset = index * PAGE_SIZE_BYTES;
+            if offset >= size {
+                break;
+            }
+            let len = PAGE_SIZE_BYTES.min(size - offset);
+            self.inode.write_at(offset, &page.frame.ppn().get_array::<u8>()[..len])?;
+        }
+        Ok(())
+    }
+
+    /// Read the file, from the cached pages where there are some
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> Result<usize, FileError> {
+        let end = self.inode.size()?.min(offset.saturating_add(buf.len()));
+        let pages = self.pages.exclusive_access();
+        let mut pos = offset;
+        while pos < end {
+            let in_page = pos % PAGE_SIZE_BYTES;
+            let len = (PAGE_SIZE_BYTES - in_page).min(end - pos);
+            let dst = &mut buf[pos - offset..pos - offset + len];
+            match pages.get(&(pos / PAGE_SIZE_BYTES)) {
+                Some(page) => dst.copy_from_slice(&page.frame.ppn().get_array::<u8>()[in_page..in_page + len]),
+                None => {
+                    if self.inode.read_at(pos, dst)? < len {
+                        return Ok(pos - offset);
+                    }
+                }
+            }
+            pos += len;
+        }
+        Ok(end.saturating_sub(offset))
+    }
+
+    /// Write through to the file, the cached pages are updated as well
+    pub fn write(&self, offset: usize, buf: &[u8]) -> Result<usize, FileError> {
+        let len = self.inode.write_at(offset, buf)?;
+        let end = offset + len;
+        let pages = self.pages.exclusive_access();
+        for (&index, page) in pages.range(offset / PAGE_SIZE_BYTES..end.div_ceil(PAGE_SIZE_BYTES)) {
+            let start = (index * PAGE_SIZE_BYTES).max(offset);
+            let stop = ((index + 1) * PAGE_SIZE_BYTES).min(end);
+            let in_page = start % PAGE_SIZE_BYTES;
+            page.frame.ppn().get_mut_array::<u8>()[in_page..in_page + stop - start]
+                .copy_from_slice(&buf[start - offset..stop - offset]);
+        }
+        Ok(len)
+    }
+
+    /// The file has been cut to `size`, what is past it has to read as zeros again
+    fn truncate(&self, size: usize) {
+        for (&index, page) in self.pages.exclusive_access().range(size / PAGE_SIZE_BYTES..) {
+            page.frame.ppn().get_mut_array::<u8>()[size.saturating_sub(index * PAGE_SIZE_BYTES)..].fill(0);
+        }
+    }
+}
+
+impl Drop for PageCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.sync(0..usize::MAX) {
+            error!("[FS] Failed to write back a mapped file: {}", e);
+        }
+    }
+}
+
+/// What shared anonymous mappings are a mapping of
+struct AnonymousInode;
+
+impl Inode for AnonymousInode {
+    fn ino(&self) -> u64 {
+        0
+    }
+
+    fn kind(&self) -> InodeKind {
+        InodeKind::Regular
+    }
+
+    fn size(&self) -> Result<usize, FileError> {
+        Ok(0)
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, FileError> {
+        Ok(0)
+    }
+
+    fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, FileError> {
+        Err(FsError::NotADirectory.into())
+    }
+
+    fn entries(&self) -> Result<Vec<DirEntry>, FileError> {
+        Err(FsError::NotADirectory.into())
+    }
+}
+
+/// Truncate the file behind `dentry`, keeping its cache in step if it is mapped
+pub fn truncate(dentry: &Dentry, size: usize) -> Result<(), FileError> {
+    dentry.inode().truncate(size)?;
+    if let Some(cache) = PageCache::lookup(dentry) {
+        cache.truncate(size);
+    }
+    Ok(())
+}
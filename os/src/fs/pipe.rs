@@ -0,0 +1,94 @@
+use alloc::sync::Arc;
+
+use crate::fs::file::{File, FileError, Stat, S_IFIFO};
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::helper::ring_buffer::RingBuffer;
+
+const PIPE_BUFFER_SIZE: usize = 4096;
+
+struct PipeBuffer {
+    buffer: RingBuffer<PIPE_BUFFER_SIZE>,
+    /// Whether the ends are still open, readers see end of file once the write end is
+    /// closed and writers fail once the read end is
+    read_open: bool,
+    write_open: bool
+}
+
+/// One end of an anonymous pipe. Duplicated descriptors and forked children share the
+/// same end, it is only closed once all of them are
+pub struct Pipe {
+    readable: bool,
+    buffer: Arc<SingleThreadSafeCell<PipeBuffer>>
+}
+
+impl Pipe {
+    /// Returns the read end and the write end
+    pub fn new() -> (Arc<Pipe>, Arc<Pipe>) {
+        let buffer = Arc::new(SingleThreadSafeCell::new(PipeBuffer {
+            buffer: RingBuffer::new(),
+            read_open: true,
+            write_open: true
+        }));
+        (Arc::new(Pipe { readable: true, buffer: buffer.clone() }), Arc::new(Pipe { readable: false, buffer }))
+    }
+}
+
+impl File for Pipe {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        !self.readable
+    }
+
+    /// Blocks while the pipe is empty and a writer exists
+    fn read(&self, buf: &mut [u8]) -> Result<usize, FileError> {
+        if !self.readable {
+            return Err(FileError::BadAccess);
+        }
+        let mut pipe = self.buffer.exclusive_access();
+        if pipe.buffer.is_empty() {
+            return if !pipe.write_open { Ok(0) } else { Err(FileError::WouldBlock) };
+        }
+        let len = buf.len().min(pipe.buffer.len());
+        for c in buf[..len].iter_mut() {
+            *c = pipe.buffer.pop().unwrap();
+        }
+        Ok(len)
+    }
+
+    /// Blocks while the pipe is full
+    fn write(&self, buf: &[u8]) -> Result<usize, FileError> {
+        if self.readable {
+            return Err(FileError::BadAccess);
+        }
+        let mut pipe = self.buffer.exclusive_access();
+        if !pipe.read_open {
+            return Err(FileError::BrokenPipe);
+        }
+        if pipe.buffer.is_full() {
+            return Err(FileError::WouldBlock);
+        }
+        let len = buf.len().min(PIPE_BUFFER_SIZE - pipe.buffer.len());
+        for c in buf[..len].iter() {
+            pipe.buffer.push(*c).unwrap();
+        }
+        Ok(len)
+    }
+
+    fn stat(&self) -> Result<Stat, FileError> {
+        Ok(Stat::new(0, S_IFIFO | 0o600, self.buffer.exclusive_access().buffer.len()))
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut pipe = self.buffer.exclusive_access();
+        if self.readable {
+            pipe.read_open = false;
+        } else {
+            pipe.write_open = false;
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use easy_fs::FsError;
+
+use crate::fs::file::FileError;
+use crate::fs::vfs::{DirEntry, Inode, InodeKind, SuperBlock};
+use crate::mm::address::PAGE_SIZE_BYTES;
+use crate::mm::frame_allocator::FRAME_ALLOCATOR;
+use crate::mm::memory_structure::{cow_faults, MemoryAreaPermissions, MemoryAreaType};
+use crate::task::tcb::{TaskControlBlock, TaskStatus};
+use crate::task::TASK_MANAGER;
+use crate::timer::get_time_us;
+
+/// Files of every `/proc/<pid>` directory
+const PID_FILES: [(&str, ProcNode); 2] = [("status", ProcNode::Status(0)), ("maps", ProcNode::Maps(0))];
+
+/// Read-only view of the kernel state, file contents are generated at every read
+pub struct ProcFs;
+
+impl SuperBlock for ProcFs {
+    fn fs_type(&self) -> &'static str {
+        "proc"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(ProcInode(ProcNode::Root))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ProcNode {
+    Root,
+    MemInfo,
+    Uptime,
+    /// `/proc/<pid>`
+    Task(usize),
+    Status(usize),
+    Maps(usize)
+}
+
+impl ProcNode {
+    fn with_pid(self, pid: usize) -> Self {
+        match self {
+            ProcNode::Status(_) => ProcNode::Status(pid),
+            ProcNode::Maps(_) => ProcNode::Maps(pid),
+            node => node
+        }
+    }
+}
+
+struct ProcInode(ProcNode);
+
+fn task_state(status: TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Ready => "R (ready)",
+        TaskStatus::Running => "R (running)",
+        TaskStatus::Zombie => "Z (zombie)"
+    }
+}
+
+fn status(tcb: &TaskControlBlock) -> String {
+    let pages: usize = tcb.memory_set().areas().iter().map(|area| area.frame_count()).sum();
+    let mut text = format!("Name:\t{}\nState:\t{}\nPid:\t{}\n", tcb.name(), task_state(tcb.status()), tcb.pid());
+    match tcb.parent() {
+        Some(parent) => writeln!(text, "PPid:\t{}", parent),
+        None => writeln!(text, "PPid:\t-")
+    }.unwrap();
+    writeln!(text, "Children:\t{}", tcb.children().len()).unwrap();
+    writeln!(text, "VmRSS:\t{} kB", pages * PAGE_SIZE_BYTES / 1024).unwrap();
+    // only meaningful once the task has exited
+    if tcb.status() == TaskStatus::Zombie {
+        writeln!(text, "ExitCode:\t{}", tcb.exit_code()).unwrap();
+    }
+    text
+}
+
+/// One line per memory area: range, permissions, how it's mapped and the frames it owns
+fn maps(tcb: &TaskControlBlock) -> String {
+    let mut text = String::new();
+    for area in tcb.memory_set().areas() {
+        let range = area.vpn_range();
+        let permissions = area.permissions();
+        let flag = |flag, c| if permissions.contains(flag) { c } else { '-' };
+        writeln!(text, "{:016x}-{:016x} {}{}{}{} {:<9} {}",
+                 range.start.start_addr().0, range.end.start_addr().0,
+                 flag(MemoryAreaPermissions::R, 'r'), flag(MemoryAreaPermissions::W, 'w'),
+                 flag(MemoryAreaPermissions::X, 'x'), flag(MemoryAreaPermissions::U, 'u'),
+                 match area.map_type() {
+                     MemoryAreaType::Identical => "identical",
+                     MemoryAreaType::Framed => "framed",
+                     MemoryAreaType::FileBacked if area.file().is_some_and(|file| file.shared) => "shared",
+                     MemoryAreaType::FileBacked => "private",
+                     MemoryAreaType::Lazy => "lazy",
+                     MemoryAreaType::Guard => "guard"
+                 },
+                 area.frame_count()).unwrap();
+    }
+    text
+}
+
+fn meminfo() -> String {
+    let (total, free) = {
+        let allocator = FRAME_ALLOCATOR.exclusive_access();
+        (allocator.total_frames(), allocator.free_frames())
+    };
+    let kb = |frames: usize| frames * PAGE_SIZE_BYTES / 1024;
+    format!("MemTotal:\t{} kB\nMemFree:\t{} kB\nMemUsed:\t{} kB\nFramesTotal:\t{}\nFramesFree:\t{}\nFramesUsed:\t{}\nCowFaults:\t{}\n",
+            kb(total), kb(free), kb(total - free), total, free, total - free, cow_faults())
+}
+
+impl ProcInode {
+    fn task_exists(pid: usize) -> bool {
+        TASK_MANAGER.inspect_task(pid, |_| ()).is_some()
+    }
+
+    fn content(&self) -> Result<String, FileError> {
+        let task_gone = || FileError::from(FsError::NotFound);
+        match self.0 {
+            ProcNode::MemInfo => Ok(meminfo()),
+            ProcNode::Uptime => {
+                let us = get_time_us();
+                Ok(format!("{}.{:02}\n", us / 1_000_000, us % 1_000_000 / 10_000))
+            },
+            ProcNode::Status(pid) => TASK_MANAGER.inspect_task(pid, status).ok_or_else(task_gone),
+            ProcNode::Maps(pid) => TASK_MANAGER.inspect_task(pid, maps).ok_or_else(task_gone),
+            ProcNode::Root | ProcNode::Task(_) => Err(FsError::IsADirectory.into())
+        }
+    }
+}
+
+impl Inode for ProcInode {
+    fn ino(&self) -> u64 {
+        match self.0 {
+            ProcNode::Root => 1,
+            ProcNode::MemInfo => 2,
+            ProcNode::Uptime => 3,
+            // 4 numbers for every pid, after the global files
+            ProcNode::Task(pid) => (pid as u64 + 1) * 4,
+            ProcNode::Status(pid) => (pid as u64 + 1) * 4 + 1,
+            ProcNode::Maps(pid) => (pid as u64 + 1) * 4 + 2
+        }
+    }
+
+    fn kind(&self) -> InodeKind {
+        match self.0 {
+            ProcNode::Root | ProcNode::Task(_) => InodeKind::Directory,
+            _ => InodeKind::Regular
+        }
+    }
+
+    /// Contents are generated on the fly, their size isn't known beforehand
+    fn size(&self) -> Result<usize, FileError> {
+        Ok(0)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, FileError> {
+        let content = self.content()?;
+        let content = content.as_bytes().get(offset..).unwrap_or_default();
+        let len = content.len().min(buf.len());
+        buf[..len].copy_from_slice(&content[..len]);
+        Ok(len)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FileError> {
+        let node = match self.0 {
+            ProcNode::Root => match name {
+                "meminfo" => Some(ProcNode::MemInfo),
+                "uptime" => Some(ProcNode::Uptime),
+                "self" => Some(ProcNode::Task(TASK_MANAGER.get_current_app_id())),
+                name => name.parse().ok().filter(|pid| Self::task_exists(*pid)).map(ProcNode::Task)
+            },
+            ProcNode::Task(pid) => PID_FILES.iter()
+                .find(|(file, _)| *file == name)
+                .map(|(_, node)| node.with_pid(pid)),
+            _ => return Err(FsError::NotADirectory.into())
+        };
+        node.map(|node| Arc::new(ProcInode(node)) as Arc<dyn Inode>).ok_or(FsError::NotFound.into())
+    }
+
+    fn entries(&self) -> Result<Vec<DirEntry>, FileError> {
+        let nodes: Vec<(String, ProcNode)> = match self.0 {
+            ProcNode::Root => [("meminfo".to_string(), ProcNode::MemInfo), ("uptime".to_string(), ProcNode::Uptime)]
+                .into_iter()
+                .chain(TASK_MANAGER.pids().into_iter().map(|pid| (pid.to_string(), ProcNode::Task(pid))))
+                .collect(),
+            ProcNode::Task(pid) => PID_FILES.iter().map(|(name, node)| (name.to_string(), node.with_pid(pid))).collect(),
+            _ => return Err(FsError::NotADirectory.into())
+        };
+        Ok(nodes.into_iter()
+            .map(|(name, node)| {
+                let inode = ProcInode(node);
+                DirEntry { name, ino: inode.ino(), kind: inode.kind() }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,65 @@
+use alloc::string::String;
+
+use crate::console::CONSOLE_INPUT;
+use crate::fs::file::{File, FileError, Stat, S_IFCHR};
+
+/// Console input, a read returns at most one line and blocks until a whole line
+/// (or end of file) is available
+pub struct Stdin;
+
+/// Console output, used for both stdout and stderr
+pub struct Stdout;
+
+fn console_stat() -> Stat {
+    Stat::new(0, S_IFCHR | 0o620, 0)
+}
+
+impl File for Stdin {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize, FileError> {
+        let mut input = CONSOLE_INPUT.exclusive_access();
+        input.poll();
+        if !input.readable() {
+            return Err(FileError::WouldBlock);
+        }
+        Ok(input.read(buf))
+    }
+
+    fn write(&self, _buf: &[u8]) -> Result<usize, FileError> {
+        Err(FileError::BadAccess)
+    }
+
+    fn stat(&self) -> Result<Stat, FileError> {
+        Ok(console_stat())
+    }
+}
+
+impl File for Stdout {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> Result<usize, FileError> {
+        Err(FileError::BadAccess)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize, FileError> {
+        print!("{}", String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> Result<Stat, FileError> {
+        Ok(console_stat())
+    }
+}
@@ -0,0 +1,198 @@
+use alloc::collections::btree_map::{BTreeMap, Entry};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use easy_fs::{FsError, NAME_LENGTH_LIMIT};
+
+use crate::fs::file::FileError;
+use crate::fs::vfs::{DirEntry, Inode, InodeKind, SuperBlock};
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::mm::address::PAGE_SIZE_BYTES;
+use crate::mm::frame_allocator::{Frame, FrameAllocator, FRAME_ALLOCATOR};
+
+/// A file system living in memory, file contents are kept in frames which go back to the
+/// frame allocator once a file is truncated or unlinked and no longer opened
+pub struct TmpFs {
+    root: Arc<TmpInode>
+}
+
+impl TmpFs {
+    pub fn new() -> Self {
+        let next_ino = Arc::new(AtomicU64::new(1));
+        TmpFs { root: TmpInode::new(&next_ino, InodeKind::Directory).unwrap() }
+    }
+}
+
+impl SuperBlock for TmpFs {
+    fn fs_type(&self) -> &'static str {
+        "tmpfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+enum Content {
+    /// Pages that were never written are holes and read as zeros
+    File { size: usize, pages: BTreeMap<usize, Frame> },
+    Directory { children: BTreeMap<String, Arc<TmpInode>> }
+}
+
+struct TmpInode {
+    ino: u64,
+    /// Shared by the whole file system
+    next_ino: Arc<AtomicU64>,
+    content: SingleThreadSafeCell<Content>
+}
+
+impl TmpInode {
+    fn new(next_ino: &Arc<AtomicU64>, kind: InodeKind) -> Result<Arc<Self>, FileError> {
+        let content = match kind {
+            InodeKind::Regular => Content::File { size: 0, pages: BTreeMap::new() },
+            InodeKind::Directory => Content::Directory { children: BTreeMap::new() },
+            InodeKind::CharDevice => return Err(FileError::InvalidArgument)
+        };
+        Ok(Arc::new(TmpInode {
+            ino: next_ino.fetch_add(1, Ordering::Relaxed),
+            next_ino: next_ino.clone(),
+            content: SingleThreadSafeCell::new(content)
+        }))
+    }
+}
+
+impl Inode for TmpInode {
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn kind(&self) -> InodeKind {
+        match *self.content.exclusive_access() {
+            Content::File { .. } => InodeKind::Regular,
+            Content::Directory { .. } => InodeKind::Directory
+        }
+    }
+
+    fn size(&self) -> Result<usize, FileError> {
+        Ok(match &*self.content.exclusive_access() {
+            Content::File { size, .. } => *size,
+            Content::Directory { children } => children.len()
+        })
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, FileError> {
+        let content = self.content.exclusive_access();
+        let Content::File { size, pages } = &*content else {
+            return Err(FsError::IsADirectory.into());
+        };
+        let end = (*size).min(offset.saturating_add(buf.len()));
+        let mut pos = offset;
+        while pos < end {
+            let in_page = pos % PAGE_SIZE_BYTES;
+            let len = (PAGE_SIZE_BYTES - in_page).min(end - pos);
+            let dst = &mut buf[pos - offset..pos - offset + len];
+            match pages.get(&(pos / PAGE_SIZE_BYTES)) {
+                Some(frame) => dst.copy_from_slice(&frame.ppn().get_array::<u8>()[in_page..in_page + len]),
+                None => dst.fill(0)
+            }
+            pos += len;
+        }
+        Ok(end.saturating_sub(offset))
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, FileError> {
+        let mut content = self.content.exclusive_access();
+        let Content::File { size, pages } = &mut *content else {
+            return Err(FsError::IsADirectory.into());
+        };
+        let end = offset.checked_add(buf.len()).ok_or(FsError::FileTooLarge)?;
+        let mut pos = offset;
+        while pos < end {
+            let index = pos / PAGE_SIZE_BYTES;
+            if let Entry::Vacant(entry) = pages.entry(index) {
+                let Some(ppn) = FRAME_ALLOCATOR.exclusive_access().alloc() else {
+                    break;
+                };
+                entry.insert(Frame::new(ppn));
+            }
+            let in_page = pos % PAGE_SIZE_BYTES;
+            let len = (PAGE_SIZE_BYTES - in_page).min(end - pos);
+            pages[&index].ppn().get_mut_array::<u8>()[in_page..in_page + len]
+                .copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            pos += len;
+        }
+        if pos == offset && !buf.is_empty() {
+            return Err(FsError::NoSpace.into());
+        }
+        *size = (*size).max(pos);
+        Ok(pos - offset)
+    }
+
+    fn truncate(&self, new_size: usize) -> Result<(), FileError> {
+        let mut content = self.content.exclusive_access();
+        let Content::File { size, pages } = &mut *content else {
+            return Err(FsError::IsADirectory.into());
+        };
+        // dropping the frames gives them back
+        pages.split_off(&new_size.div_ceil(PAGE_SIZE_BYTES));
+        // the tail of the last page has to read as zeros if the file grows again
+        if let Some(frame) = pages.get(&(new_size / PAGE_SIZE_BYTES)) {
+            frame.ppn().get_mut_array::<u8>()[new_size % PAGE_SIZE_BYTES..].fill(0);
+        }
+        *size = new_size;
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FileError> {
+        let content = self.content.exclusive_access();
+        let Content::Directory { children } = &*content else {
+            return Err(FsError::NotADirectory.into());
+        };
+        match children.get(name) {
+            Some(inode) => Ok(inode.clone()),
+            None => Err(FsError::NotFound.into())
+        }
+    }
+
+    fn create(&self, name: &str, kind: InodeKind) -> Result<Arc<dyn Inode>, FileError> {
+        let mut content = self.content.exclusive_access();
+        let Content::Directory { children } = &mut *content else {
+            return Err(FsError::NotADirectory.into());
+        };
+        // same limit as on the disk, so that files can be copied over
+        if name.len() > NAME_LENGTH_LIMIT {
+            return Err(FsError::NameTooLong.into());
+        }
+        if children.contains_key(name) {
+            return Err(FsError::AlreadyExists.into());
+        }
+        let inode = TmpInode::new(&self.next_ino, kind)?;
+        children.insert(String::from(name), inode.clone());
+        Ok(inode)
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), FileError> {
+        let mut content = self.content.exclusive_access();
+        let Content::Directory { children } = &mut *content else {
+            return Err(FsError::NotADirectory.into());
+        };
+        let inode = children.get(name).ok_or(FsError::NotFound)?;
+        if matches!(&*inode.content.exclusive_access(), Content::Directory { children } if !children.is_empty()) {
+            return Err(FsError::DirectoryNotEmpty.into());
+        }
+        // the pages are released once the file isn't opened any more
+        children.remove(name);
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<Vec<DirEntry>, FileError> {
+        let content = self.content.exclusive_access();
+        let Content::Directory { children } = &*content else {
+            return Err(FsError::NotADirectory.into());
+        };
+        Ok(children.iter()
+            .map(|(name, inode)| DirEntry { name: name.clone(), ino: inode.ino, kind: inode.kind() })
+            .collect())
+    }
+}
@@ -0,0 +1,182 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::FsError;
+
+use crate::fs::file::{File, FileError, Stat, S_IFCHR, S_IFDIR, S_IFREG};
+use crate::fs::mount;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InodeKind {
+    Regular,
+    Directory,
+    CharDevice
+}
+
+impl InodeKind {
+    /// `d_type` of `getdents64`
+    pub fn dirent_type(&self) -> u8 {
+        match self {
+            InodeKind::Regular => 8,
+            InodeKind::Directory => 4,
+            InodeKind::CharDevice => 2
+        }
+    }
+}
+
+/// An entry of a directory listing, `.` and `..` are not part of it
+pub struct DirEntry {
+    pub name: String,
+    pub ino: u64,
+    pub kind: InodeKind
+}
+
+/// A file or directory of some file system, all operations take `&self` since inodes are
+/// shared by every dentry and opened file referring to them.
+/// Directory operations fail with `NotADirectory` on anything else, the defaults are for
+/// file systems that can't be modified
+pub trait Inode: Send + Sync {
+    fn ino(&self) -> u64;
+    fn kind(&self) -> InodeKind;
+    fn size(&self) -> Result<usize, FileError>;
+    /// Read from `offset`, short at the end of file
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, FileError>;
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize, FileError> {
+        Err(FileError::BadAccess)
+    }
+    fn truncate(&self, _size: usize) -> Result<(), FileError> {
+        Err(FileError::BadAccess)
+    }
+    /// Look up `name` in this directory, `.` and `..` are handled by the dentries
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FileError>;
+    fn create(&self, _name: &str, _kind: InodeKind) -> Result<Arc<dyn Inode>, FileError> {
+        Err(FileError::BadAccess)
+    }
+    /// Remove `name` from this directory, directories have to be empty
+    fn unlink(&self, _name: &str) -> Result<(), FileError> {
+        Err(FileError::BadAccess)
+    }
+    fn entries(&self) -> Result<Vec<DirEntry>, FileError>;
+    fn stat(&self) -> Result<Stat, FileError> {
+        let mode = match self.kind() {
+            InodeKind::Regular => S_IFREG | 0o644,
+            InodeKind::Directory => S_IFDIR | 0o755,
+            InodeKind::CharDevice => S_IFCHR | 0o666
+        };
+        Ok(Stat::new(self.ino(), mode, self.size()?))
+    }
+    /// What opening a device node gives, the device itself rather than a file reading the inode
+    fn device(&self) -> Option<Arc<dyn File>> {
+        None
+    }
+}
+
+/// A mounted file system
+pub trait SuperBlock: Send + Sync {
+    fn fs_type(&self) -> &'static str;
+    fn root(&self) -> Arc<dyn Inode>;
+}
+
+/// An inode reached through a path, the chain of parents gives `..` and the path back.
+/// Dentries aren't cached, every lookup builds its own
+pub struct Dentry {
+    name: String,
+    parent: Option<Arc<Dentry>>,
+    /// Index of the mount the inode belongs to
+    mount_id: usize,
+    inode: Arc<dyn Inode>
+}
+
+impl Dentry {
+    /// The root of the whole tree
+    pub fn new_root(mount_id: usize, inode: Arc<dyn Inode>) -> Arc<Self> {
+        Arc::new(Dentry { name: String::from("/"), parent: None, mount_id, inode })
+    }
+
+    pub fn inode(&self) -> &Arc<dyn Inode> {
+        &self.inode
+    }
+
+    pub fn mount_id(&self) -> usize {
+        self.mount_id
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.inode.kind() == InodeKind::Directory
+    }
+
+    /// Whether this is the root of a mounted file system, the root of the tree included
+    pub fn is_mount_root(&self) -> bool {
+        self.parent.as_ref().is_none_or(|parent| parent.mount_id != self.mount_id)
+    }
+
+    /// `..`, the root is its own parent
+    pub fn parent(self: &Arc<Self>) -> Arc<Dentry> {
+        self.parent.clone().unwrap_or_else(|| self.clone())
+    }
+
+    /// Step into `name`, crossing into the file system mounted there if any
+    pub fn child(self: &Arc<Self>, name: &str) -> Result<Arc<Dentry>, FileError> {
+        if !self.is_dir() {
+            return Err(FsError::NotADirectory.into());
+        }
+        let inode = self.inode.lookup(name)?;
+        let (mount_id, inode) = match mount::mounted_on(self.mount_id, inode.ino()) {
+            Some((mount_id, root)) => (mount_id, root),
+            None => (self.mount_id, inode)
+        };
+        Ok(Arc::new(Dentry { name: String::from(name), parent: Some(self.clone()), mount_id, inode }))
+    }
+
+    /// Absolute path from the root
+    pub fn path(&self) -> String {
+        let mut names = Vec::new();
+        let mut dentry = self;
+        while let Some(parent) = &dentry.parent {
+            names.push(dentry.name.as_str());
+            dentry = parent;
+        }
+        if names.is_empty() {
+            return String::from("/");
+        }
+        names.iter().rev().fold(String::new(), |mut path, name| {
+            path.push('/');
+            path.push_str(name);
+            path
+        })
+    }
+}
+
+/// Resolve `path` from `base`, or from the root if it is absolute
+pub fn lookup(base: &Arc<Dentry>, path: &str) -> Result<Arc<Dentry>, FileError> {
+    if path.is_empty() {
+        return Err(FsError::NotFound.into());
+    }
+    let mut dentry = if path.starts_with('/') { mount::root() } else { base.clone() };
+    for name in path.split('/') {
+        dentry = match name {
+            "" | "." => dentry,
+            ".." => dentry.parent(),
+            name => dentry.child(name)?
+        };
+    }
+    Ok(dentry)
+}
+
+/// Resolve the directory `path` is in, together with the last component, which is what
+/// creating or removing `path` needs
+pub fn lookup_parent<'a>(base: &Arc<Dentry>, path: &'a str) -> Result<(Arc<Dentry>, &'a str), FileError> {
+    let path = path.trim_end_matches('/');
+    let (dir, name) = match path.rsplit_once('/') {
+        Some(("", name)) => (mount::root(), name),
+        Some((dir, name)) => (lookup(base, dir)?, name),
+        None => (base.clone(), path)
+    };
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(FileError::InvalidArgument);
+    }
+    if !dir.is_dir() {
+        return Err(FsError::NotADirectory.into());
+    }
+    Ok((dir, name))
+}
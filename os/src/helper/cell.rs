@@ -15,4 +15,10 @@ impl<T> SingleThreadSafeCell<T>{
     pub fn exclusive_access(&self) -> RefMut<T>{
         self.inner.borrow_mut()
     }
-}
\ No newline at end of file
+    /// Like `exclusive_access`, but returns `None` instead of panicking when already borrowed,
+    /// for paths (e.g. the panic handler) that may run while the cell is in use
+    #[allow(mismatched_lifetime_syntaxes)]
+    pub fn try_exclusive_access(&self) -> Option<RefMut<T>>{
+        self.inner.try_borrow_mut().ok()
+    }
+}
@@ -0,0 +1,76 @@
+/// "expand 32-byte k"
+const CONSTANTS: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+pub const KEY_SIZE: usize = 32;
+const BLOCK_SIZE: usize = 64;
+
+/// ChaCha20 (RFC 8439) keystream used as a random generator. The key is replaced by fresh
+/// keystream after every request, so earlier output can't be recovered from the state
+pub struct ChaCha20Rng {
+    key: [u32; 8],
+    counter: u64
+}
+
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]); s[d] ^= s[a]; s[d] = s[d].rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]); s[b] ^= s[c]; s[b] = s[b].rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]); s[d] ^= s[a]; s[d] = s[d].rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]); s[b] ^= s[c]; s[b] = s[b].rotate_left(7);
+}
+
+impl ChaCha20Rng {
+    pub fn new(seed: [u8; KEY_SIZE]) -> Self {
+        let mut key = [0u32; 8];
+        for (word, bytes) in key.iter_mut().zip(seed.chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+        ChaCha20Rng { key, counter: 0 }
+    }
+
+    /// The next keystream block, the nonce is always zero
+    fn block(&mut self) -> [u8; BLOCK_SIZE] {
+        let mut initial = [0u32; 16];
+        initial[..4].copy_from_slice(&CONSTANTS);
+        initial[4..12].copy_from_slice(&self.key);
+        initial[12] = self.counter as u32;
+        initial[13] = (self.counter >> 32) as u32;
+        self.counter = self.counter.wrapping_add(1);
+        let mut state = initial;
+        for _ in 0..10 {
+            quarter_round(&mut state, 0, 4, 8, 12);
+            quarter_round(&mut state, 1, 5, 9, 13);
+            quarter_round(&mut state, 2, 6, 10, 14);
+            quarter_round(&mut state, 3, 7, 11, 15);
+            quarter_round(&mut state, 0, 5, 10, 15);
+            quarter_round(&mut state, 1, 6, 11, 12);
+            quarter_round(&mut state, 2, 7, 8, 13);
+            quarter_round(&mut state, 3, 4, 9, 14);
+        }
+        let mut out = [0u8; BLOCK_SIZE];
+        for (i, bytes) in out.chunks_exact_mut(4).enumerate() {
+            bytes.copy_from_slice(&state[i].wrapping_add(initial[i]).to_le_bytes());
+        }
+        out
+    }
+
+    /// Mix `data` into the key, e.g. more entropy gathered after seeding
+    pub fn reseed(&mut self, data: &[u8]) {
+        for (i, byte) in data.iter().enumerate() {
+            self.key[i / 4 % 8] ^= (*byte as u32) << (i % 4 * 8);
+        }
+        self.rekey();
+    }
+
+    fn rekey(&mut self) {
+        let block = self.block();
+        for (word, bytes) in self.key.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+    }
+
+    pub fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(BLOCK_SIZE) {
+            chunk.copy_from_slice(&self.block()[..chunk.len()]);
+        }
+        self.rekey();
+    }
+}
@@ -1 +1,3 @@
-pub(crate) mod cell;
\ No newline at end of file
+pub(crate) mod cell;
+pub(crate) mod chacha;
+pub(crate) mod ring_buffer;
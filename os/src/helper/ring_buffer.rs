@@ -0,0 +1,43 @@
+/// Fixed capacity byte queue, pushing into a full buffer fails instead of overwriting
+pub struct RingBuffer<const N: usize> {
+    data: [u8; N],
+    head: usize,
+    len: usize
+}
+
+impl<const N: usize> RingBuffer<N> {
+    pub const fn new() -> Self {
+        RingBuffer { data: [0; N], head: 0, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn push(&mut self, c: u8) -> Result<(), u8> {
+        if self.is_full() {
+            return Err(c);
+        }
+        self.data[(self.head + self.len) % N] = c;
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+        let c = self.data[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(c)
+    }
+}
@@ -0,0 +1,49 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use thiserror::Error;
+
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::io::block_cache::CachedBlockDevice;
+
+pub const BLOCK_SIZE: usize = 512;
+
+#[derive(Debug, Error)]
+pub enum BlockError {
+    #[error("block {0} out of range")]
+    OutOfRange(usize),
+    #[error("buffer of {0} bytes doesn't match the block size")]
+    InvalidBuffer(usize),
+    #[error("device failed with status {0}")]
+    DeviceError(u8)
+}
+
+/// A device addressed by fixed sized blocks of `BLOCK_SIZE` bytes
+pub trait BlockDevice: Send + Sync {
+    fn num_blocks(&self) -> usize;
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError>;
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError>;
+}
+
+lazy_static!{
+    /// Probed block devices, indexed by the order they are found in
+    static ref BLOCK_DEVICES: SingleThreadSafeCell<Vec<Arc<dyn BlockDevice>>> = SingleThreadSafeCell::new(Vec::new());
+}
+
+/// Make `device` available to the rest of the kernel, returns its id
+pub fn register(device: Arc<dyn BlockDevice>) -> usize {
+    let mut devices = BLOCK_DEVICES.exclusive_access();
+    devices.push(device);
+    devices.len() - 1
+}
+
+/// The device `id`, with its blocks going through the block cache
+pub fn get(id: usize) -> Option<Arc<dyn BlockDevice>> {
+    let device = get_raw(id)?;
+    Some(Arc::new(CachedBlockDevice::new(id, device)))
+}
+
+/// The device `id` as the driver gave it, bypassing the block cache
+pub fn get_raw(id: usize) -> Option<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES.exclusive_access().get(id).cloned()
+}
@@ -0,0 +1,131 @@
+use alloc::boxed::Box;
+use alloc::collections::vec_deque::VecDeque;
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
+use log::{error, info};
+
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::io::block::{self, BlockDevice, BlockError, BLOCK_SIZE};
+
+/// How many blocks are kept, shared by every device
+const BLOCK_CACHE_CAPACITY: usize = 128;
+
+struct CachedBlock {
+    device: usize,
+    block_id: usize,
+    data: Box<[u8; BLOCK_SIZE]>,
+    /// Changed since it was read, has to reach the device before being dropped
+    dirty: bool
+}
+
+struct BlockCache {
+    /// Least recently used first
+    blocks: VecDeque<CachedBlock>,
+    hits: usize,
+    misses: usize,
+    write_backs: usize
+}
+
+lazy_static!{
+    static ref BLOCK_CACHE: SingleThreadSafeCell<BlockCache> = SingleThreadSafeCell::new(BlockCache {
+        blocks: VecDeque::with_capacity(BLOCK_CACHE_CAPACITY),
+        hits: 0,
+        misses: 0,
+        write_backs: 0
+    });
+}
+
+impl BlockCache {
+    fn write_back(&mut self, index: usize) -> Result<(), BlockError> {
+        let block = &mut self.blocks[index];
+        if block.dirty {
+            block::get_raw(block.device).unwrap().write_block(block.block_id, &block.data[..])?;
+            block.dirty = false;
+            self.write_backs += 1;
+        }
+        Ok(())
+    }
+
+    /// Index of the block, now the most recently used, loaded from the device unless it is
+    /// going to be overwritten as a whole
+    fn get(&mut self, device: usize, block_id: usize, overwrite: bool) -> Result<usize, BlockError> {
+        if let Some(index) = self.blocks.iter().position(|block| block.device == device && block.block_id == block_id) {
+            self.hits += 1;
+            let block = self.blocks.remove(index).unwrap();
+            self.blocks.push_back(block);
+            return Ok(self.blocks.len() - 1);
+        }
+        self.misses += 1;
+        let mut data = Box::new([0u8; BLOCK_SIZE]);
+        if !overwrite {
+            block::get_raw(device).unwrap().read_block(block_id, &mut data[..])?;
+        }
+        if self.blocks.len() == BLOCK_CACHE_CAPACITY {
+            // a block that can't be written back stays, its data would be lost otherwise
+            self.write_back(0)?;
+            self.blocks.pop_front();
+        }
+        self.blocks.push_back(CachedBlock { device, block_id, data, dirty: false });
+        Ok(self.blocks.len() - 1)
+    }
+}
+
+/// A block device whose blocks go through the cache, writes reach the device when the
+/// block is evicted or on `sync`
+pub struct CachedBlockDevice {
+    id: usize,
+    device: Arc<dyn BlockDevice>
+}
+
+impl CachedBlockDevice {
+    pub fn new(id: usize, device: Arc<dyn BlockDevice>) -> Self {
+        CachedBlockDevice { id, device }
+    }
+}
+
+impl BlockDevice for CachedBlockDevice {
+    fn num_blocks(&self) -> usize {
+        self.device.num_blocks()
+    }
+
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        if buf.len() != BLOCK_SIZE {
+            return Err(BlockError::InvalidBuffer(buf.len()));
+        }
+        let mut cache = BLOCK_CACHE.exclusive_access();
+        let index = cache.get(self.id, block_id, false)?;
+        buf.copy_from_slice(&cache.blocks[index].data[..]);
+        Ok(())
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
+        if buf.len() != BLOCK_SIZE {
+            return Err(BlockError::InvalidBuffer(buf.len()));
+        }
+        if block_id >= self.device.num_blocks() {
+            return Err(BlockError::OutOfRange(block_id));
+        }
+        let mut cache = BLOCK_CACHE.exclusive_access();
+        let index = cache.get(self.id, block_id, true)?;
+        let block = &mut cache.blocks[index];
+        block.data.copy_from_slice(buf);
+        block.dirty = true;
+        Ok(())
+    }
+}
+
+/// Write every dirty block back, and log how well the cache does
+pub fn sync() -> Result<(), BlockError> {
+    let mut cache = BLOCK_CACHE.exclusive_access();
+    let mut result = Ok(());
+    for index in 0..cache.blocks.len() {
+        if let Err(e) = cache.write_back(index) {
+            let block = &cache.blocks[index];
+            error!("[BlockCache] Failed to write back block {} of device {}: {}", block.block_id, block.device, e);
+            result = Err(e);
+        }
+    }
+    info!("[BlockCache] {} hits, {} misses, {} write-backs, {}/{} blocks cached",
+          cache.hits, cache.misses, cache.write_backs, cache.blocks.len(), BLOCK_CACHE_CAPACITY);
+    result
+}
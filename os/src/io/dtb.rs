@@ -3,15 +3,36 @@ use alloc::vec::Vec;
 use dtb_walker::{Dtb, DtbObj, Property, WalkOperation};
 use lazy_static::lazy_static;
 use core::ops::Range;
-use dtb_walker::utils::indent;
 use crate::helper::cell::SingleThreadSafeCell;
 
 #[derive(Clone)]
 pub struct DeviceTree{
-    pub memory: Vec<Range<usize>>
+    pub memory: Vec<Range<usize>>,
+    pub devices: Vec<DeviceNode>
 }
-pub enum DeviceType {
-    Memory,
+/// A node of the device tree with the properties drivers care about
+#[derive(Clone, Default)]
+pub struct DeviceNode {
+    pub name: String,
+    pub compatible: Vec<String>,
+    pub reg: Vec<Range<usize>>,
+    pub interrupts: Vec<u32>,
+    pub device_type: Option<String>,
+    /// Other properties as (name, raw big endian value)
+    pub properties: Vec<(String, Vec<u8>)>
+}
+impl DeviceNode {
+    pub fn is_compatible(&self, compatible: &str) -> bool {
+        self.compatible.iter().any(|c| c == compatible)
+    }
+
+    /// Read a single cell property, e.g. `reg-shift`
+    pub fn property_u32(&self, name: &str) -> Option<u32> {
+        self.properties.iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, value)| value.get(0..4))
+            .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
 }
 lazy_static!{
     pub static ref DEVICE_TREE: SingleThreadSafeCell<Option<DeviceTree>> = SingleThreadSafeCell::new(None);
@@ -21,26 +42,37 @@ lazy_static!{
 impl DeviceTree {
     pub fn from_ptr(dtb_ptr: * const u8) -> Self {
         let dtb = unsafe { Dtb::from_raw_parts(dtb_ptr) }.expect("Failed to parse device tree");
-        let mut memory_ranges = Vec::new();
-        let mut last_device: Option<DeviceType> = None;
-        dtb.walk(|path, obj| match obj {
+        let mut devices = Vec::new();
+        // properties of a node always come before its children, so a node is complete
+        // once the next node starts
+        let mut current = DeviceNode::default();
+        dtb.walk(|_path, obj| match obj {
             DtbObj::SubNode { name } => {
-                last_device = None;
-                // println!("{}{}", indent(path.level(), 2), String::from_utf8_lossy(name));
+                devices.push(core::mem::take(&mut current));
+                current.name = String::from_utf8_lossy(name).into_owned();
                 WalkOperation::StepInto
 
             }
             DtbObj::Property(prop) => {
-                // println!("{}{:?}", indent(path.level(), 2), prop);
-                match prop{ 
-                    Property::General { name, value } => {
-                        if name.as_bytes() == b"device_type" && &value[0..value.len() - 1] == b"memory" {
-                            last_device = Some(DeviceType::Memory);
-                        }
+                match prop{
+                    Property::Compatible(list) => {
+                        current.compatible.extend(list.map(|c| String::from_utf8_lossy(c.as_bytes()).into_owned()));
                     },
                     Property::Reg(reg) => {
-                        if let Some(DeviceType::Memory) = last_device {
-                            memory_ranges.extend(reg);
+                        current.reg.extend(reg);
+                    },
+                    Property::General { name, value } => {
+                        match name.as_bytes() {
+                            b"device_type" => {
+                                current.device_type = Some(String::from_utf8_lossy(&value[0..value.len() - 1]).into_owned());
+                            },
+                            b"interrupts" => {
+                                current.interrupts.extend(value.chunks_exact(4)
+                                    .map(|cell| u32::from_be_bytes(cell.try_into().unwrap())));
+                            },
+                            _ => {
+                                current.properties.push((String::from_utf8_lossy(name.as_bytes()).into_owned(), value.to_vec()));
+                            }
                         }
                     },
                     _ => {}
@@ -48,8 +80,17 @@ impl DeviceTree {
                 WalkOperation::StepOver
             },
         });
+        devices.push(current);
 
-        DeviceTree { memory: memory_ranges }
+        let memory = devices.iter()
+            .filter(|node| node.device_type.as_deref() == Some("memory"))
+            .flat_map(|node| node.reg.iter().cloned())
+            .collect();
+        DeviceTree { memory, devices }
+    }
+
+    pub fn find_compatible<'a>(&'a self, compatible: &'a str) -> impl Iterator<Item = &'a DeviceNode> {
+        self.devices.iter().filter(move |node| node.is_compatible(compatible))
     }
 }
 
@@ -59,4 +100,4 @@ pub fn init_dtb(dtb_ptr: *const u8) {
         panic!("[IO] Device tree already initialized");
     }
     tree_ref.replace(DeviceTree::from_ptr(dtb_ptr));
-}
\ No newline at end of file
+}
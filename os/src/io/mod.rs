@@ -1,5 +1,22 @@
+pub(crate) mod block;
+pub(crate) mod block_cache;
 pub(crate) mod dtb;
+pub(crate) mod plic;
+pub(crate) mod uart;
+pub(crate) mod virtio;
+
+use crate::io::dtb::DEVICE_TREE;
 
 pub fn init(dtb_ptr: *const u8) {
     dtb::init_dtb(dtb_ptr);
-}
\ No newline at end of file
+}
+
+/// Bring up the device drivers, paging must have been enabled
+pub fn init_devices() {
+    let dtb = DEVICE_TREE.exclusive_access();
+    let dtb = dtb.as_ref().unwrap();
+    // the interrupt controller comes first so that drivers can register their handlers
+    plic::init(dtb);
+    uart::init(dtb);
+    virtio::init(dtb);
+}
@@ -0,0 +1,151 @@
+use core::ptr::{read_volatile, write_volatile};
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use riscv::register::sie;
+use thiserror::Error;
+
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::io::dtb::DeviceTree;
+use crate::mm::kernel::KERNEL_MEMORY_MANAGER;
+
+pub const COMPATIBLE: &str = "riscv,plic0";
+/// Cause of the supervisor external interrupt in `interrupts-extended`
+const S_EXTERNAL_INTERRUPT: u32 = 9;
+/// Fallback when the context can't be found in the device tree: hart 0, S mode
+const DEFAULT_CONTEXT: usize = 1;
+const DEFAULT_NUM_SOURCES: u32 = 127;
+
+const PRIORITY_OFFSET: usize = 0x0;
+const ENABLE_OFFSET: usize = 0x2000;
+const ENABLE_CONTEXT_STRIDE: usize = 0x80;
+const CONTEXT_OFFSET: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const THRESHOLD: usize = 0x0;
+const CLAIM_COMPLETE: usize = 0x4;
+
+#[derive(Debug, Error)]
+pub enum PlicError {
+    #[error("no interrupt controller available")]
+    NotInitialized,
+    #[error("invalid irq {0}")]
+    InvalidIrq(u32),
+    #[error("irq {0} already has a handler")]
+    HandlerExists(u32)
+}
+
+pub type IrqHandler = Arc<dyn Fn() + Send + Sync>;
+
+pub struct Plic {
+    base: usize,
+    context: usize,
+    num_sources: u32,
+    handlers: BTreeMap<u32, IrqHandler>
+}
+
+impl Plic {
+    fn new(base: usize, context: usize, num_sources: u32) -> Self {
+        let plic = Plic { base, context, num_sources, handlers: BTreeMap::new() };
+        // let every enabled source with a non-zero priority through
+        plic.write(CONTEXT_OFFSET + CONTEXT_STRIDE * context + THRESHOLD, 0);
+        plic
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { write_volatile((self.base + offset) as *mut u32, value) }
+    }
+
+    fn enable(&self, irq: u32) {
+        let offset = ENABLE_OFFSET + ENABLE_CONTEXT_STRIDE * self.context + (irq as usize / 32) * 4;
+        self.write(PRIORITY_OFFSET + irq as usize * 4, 1);
+        self.write(offset, self.read(offset) | 1 << (irq % 32));
+    }
+
+    fn claim(&self) -> u32 {
+        self.read(CONTEXT_OFFSET + CONTEXT_STRIDE * self.context + CLAIM_COMPLETE)
+    }
+
+    fn complete(&self, irq: u32) {
+        self.write(CONTEXT_OFFSET + CONTEXT_STRIDE * self.context + CLAIM_COMPLETE, irq);
+    }
+}
+
+lazy_static!{
+    pub static ref PLIC: SingleThreadSafeCell<Option<Plic>> = SingleThreadSafeCell::new(None);
+}
+
+/// Find the S mode context of the boot hart from `interrupts-extended` (pairs of `<phandle cause>`)
+fn find_context(raw: &[u8]) -> Option<usize> {
+    raw.chunks_exact(8)
+        .position(|pair| u32::from_be_bytes(pair[4..8].try_into().unwrap()) == S_EXTERNAL_INTERRUPT)
+}
+
+/// Probe the interrupt controller and enable external interrupts, has to be done
+/// before the drivers registering their handlers
+pub fn init(device_tree: &DeviceTree) {
+    let Some(node) = device_tree.find_compatible(COMPATIBLE).next() else {
+        warn!("[PLIC] No {} found in device tree, external interrupts disabled", COMPATIBLE);
+        return;
+    };
+    let Some(reg) = node.reg.first().cloned() else {
+        warn!("[PLIC] {} has no registers, external interrupts disabled", node.name);
+        return;
+    };
+    if let Err(e) = KERNEL_MEMORY_MANAGER.exclusive_access().map_mmio(reg.clone()) {
+        warn!("[PLIC] Failed to map registers of {}: {}, external interrupts disabled", node.name, e);
+        return;
+    }
+    let context = node.properties.iter()
+        .find(|(name, _)| name == "interrupts-extended")
+        .and_then(|(_, value)| find_context(value))
+        .unwrap_or(DEFAULT_CONTEXT);
+    let num_sources = node.property_u32("riscv,ndev").unwrap_or(DEFAULT_NUM_SOURCES);
+    debug!("[PLIC] {} at [{:#x}, {:#x}), context {}, {} sources", node.name, reg.start, reg.end, context, num_sources);
+    PLIC.exclusive_access().replace(Plic::new(reg.start, context, num_sources));
+    unsafe { sie::set_sext(); }
+}
+
+/// Route `irq` to `handler`, the handler runs with the interrupt claimed and should
+/// clear the condition on its device
+pub fn register_handler(irq: u32, handler: IrqHandler) -> Result<(), PlicError> {
+    let mut plic = PLIC.exclusive_access();
+    let plic = plic.as_mut().ok_or(PlicError::NotInitialized)?;
+    if irq == 0 || irq > plic.num_sources {
+        return Err(PlicError::InvalidIrq(irq));
+    }
+    if plic.handlers.contains_key(&irq) {
+        return Err(PlicError::HandlerExists(irq));
+    }
+    plic.handlers.insert(irq, handler);
+    plic.enable(irq);
+    Ok(())
+}
+
+/// Serve every pending external interrupt
+pub fn handle_external_interrupt() {
+    loop {
+        // the handler may take a while, don't keep the controller borrowed meanwhile
+        let (irq, handler) = {
+            let plic = PLIC.exclusive_access();
+            let Some(plic) = plic.as_ref() else { return };
+            let irq = plic.claim();
+            (irq, plic.handlers.get(&irq).cloned())
+        };
+        if irq == 0 {
+            return;
+        }
+        match handler {
+            Some(handler) => handler(),
+            None => warn!("[PLIC] Unexpected irq {}", irq)
+        }
+        if let Some(plic) = PLIC.exclusive_access().as_ref() {
+            plic.complete(irq);
+        }
+    }
+}
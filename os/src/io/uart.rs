@@ -0,0 +1,165 @@
+use core::ptr::{read_volatile, write_volatile};
+
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
+use log::{debug, warn};
+
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::helper::ring_buffer::RingBuffer;
+use crate::io::dtb::DeviceTree;
+use crate::io::plic;
+use crate::mm::kernel::KERNEL_MEMORY_MANAGER;
+
+pub const COMPATIBLE: &str = "ns16550a";
+const BUFFER_SIZE: usize = 1024;
+
+// register offsets (before shifted by `reg-shift`)
+const RBR: usize = 0; // receive buffer, read
+const THR: usize = 0; // transmit holding, write
+const IER: usize = 1; // interrupt enable
+const FCR: usize = 2; // fifo control, write
+const LCR: usize = 3; // line control
+const MCR: usize = 4; // modem control
+const LSR: usize = 5; // line status
+
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+const IER_TX_EMPTY: u8 = 1 << 1;
+const FCR_ENABLE_AND_CLEAR: u8 = 0b111;
+const LCR_8N1: u8 = 0b11;
+const MCR_DTR_RTS_OUT2: u8 = 0b1011;
+const LSR_DATA_READY: u8 = 1 << 0;
+const LSR_THR_EMPTY: u8 = 1 << 5;
+
+pub struct Ns16550a {
+    base: usize,
+    reg_shift: usize,
+    /// Set once the interrupt is routed to us, otherwise the device is polled
+    irq: Option<u32>,
+    ier: u8,
+    rx: RingBuffer<BUFFER_SIZE>,
+    tx: RingBuffer<BUFFER_SIZE>
+}
+
+impl Ns16550a {
+    /// The baud rate is left as configured by the firmware
+    fn new(base: usize, reg_shift: usize) -> Self {
+        let mut uart = Ns16550a { base, reg_shift, irq: None, ier: 0, rx: RingBuffer::new(), tx: RingBuffer::new() };
+        uart.write_reg(IER, 0);
+        uart.write_reg(FCR, FCR_ENABLE_AND_CLEAR);
+        uart.write_reg(LCR, LCR_8N1);
+        uart.write_reg(MCR, MCR_DTR_RTS_OUT2);
+        uart.set_ier(IER_RX_AVAILABLE);
+        uart
+    }
+
+    fn read_reg(&self, reg: usize) -> u8 {
+        unsafe { read_volatile((self.base + (reg << self.reg_shift)) as *const u8) }
+    }
+
+    fn write_reg(&self, reg: usize, value: u8) {
+        unsafe { write_volatile((self.base + (reg << self.reg_shift)) as *mut u8, value) }
+    }
+
+    fn set_ier(&mut self, ier: u8) {
+        if self.ier != ier {
+            self.ier = ier;
+            self.write_reg(IER, ier);
+        }
+    }
+
+    /// Queue `c` for transmission, only spins when the transmit buffer is full
+    pub fn putc(&mut self, c: u8) {
+        while self.tx.push(c).is_err() {
+            self.flush();
+        }
+    }
+
+    pub fn puts(&mut self, s: &str) {
+        for c in s.bytes() {
+            if c == b'\n' {
+                self.putc(b'\r');
+            }
+            self.putc(c);
+        }
+        self.flush();
+    }
+
+    /// Move as much as the transmitter accepts from the buffer to the device, the rest is
+    /// sent from the transmitter-empty interrupt
+    pub fn flush(&mut self) {
+        while !self.tx.is_empty() && self.read_reg(LSR) & LSR_THR_EMPTY != 0 {
+            let c = self.tx.pop().unwrap();
+            self.write_reg(THR, c);
+        }
+        if self.tx.is_empty() {
+            self.set_ier(self.ier & !IER_TX_EMPTY);
+        } else {
+            self.set_ier(self.ier | IER_TX_EMPTY);
+        }
+    }
+
+    /// Take a received byte
+    pub fn getc(&mut self) -> Option<u8> {
+        self.rx.pop()
+    }
+
+    /// Drain the receive fifo and refill the transmitter. Bytes arriving while
+    /// the receive buffer is full are dropped
+    pub fn handle_interrupt(&mut self) {
+        while self.read_reg(LSR) & LSR_DATA_READY != 0 {
+            let c = self.read_reg(RBR);
+            if self.rx.push(c).is_err() {
+                warn!("[UART] Receive buffer full, input dropped");
+            }
+        }
+        self.flush();
+    }
+}
+
+lazy_static!{
+    pub static ref UART: SingleThreadSafeCell<Option<Ns16550a>> = SingleThreadSafeCell::new(None);
+}
+
+/// Probe the first ns16550a in the device tree and map its registers.
+/// Must be called after paging is enabled, from then on the console goes through it
+pub fn init(device_tree: &DeviceTree) {
+    let Some(node) = device_tree.find_compatible(COMPATIBLE).next() else {
+        warn!("[UART] No {} found in device tree, keep using SBI console", COMPATIBLE);
+        return;
+    };
+    let Some(reg) = node.reg.first().cloned() else {
+        warn!("[UART] {} has no registers, keep using SBI console", node.name);
+        return;
+    };
+    if let Err(e) = KERNEL_MEMORY_MANAGER.exclusive_access().map_mmio(reg.clone()) {
+        warn!("[UART] Failed to map registers of {}: {}, keep using SBI console", node.name, e);
+        return;
+    }
+    let reg_shift = node.property_u32("reg-shift").unwrap_or(0) as usize;
+    let irq = node.interrupts.first().copied();
+    debug!("[UART] {} at [{:#x}, {:#x}), irq {:?}", node.name, reg.start, reg.end, irq);
+    UART.exclusive_access().replace(Ns16550a::new(reg.start, reg_shift));
+    let Some(irq) = irq else {
+        warn!("[UART] {} has no interrupt, falling back to polling", node.name);
+        return;
+    };
+    match plic::register_handler(irq, Arc::new(handle_irq)) {
+        Ok(()) => UART.exclusive_access().as_mut().unwrap().irq = Some(irq),
+        Err(e) => warn!("[UART] Failed to register irq {}: {}, falling back to polling", irq, e)
+    }
+}
+
+fn handle_irq() {
+    // the interrupt is level triggered, if the console is busy right now it fires again
+    // once completed
+    if let Some(Some(uart)) = UART.try_exclusive_access().as_deref_mut() {
+        uart.handle_interrupt();
+    }
+}
+
+/// Poll the device in case its interrupt can't be delivered
+pub fn poll() {
+    if let Some(uart) = UART.exclusive_access().as_mut() && uart.irq.is_none() {
+        uart.handle_interrupt();
+    }
+}
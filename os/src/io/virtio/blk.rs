@@ -0,0 +1,143 @@
+use core::arch::asm;
+use core::ptr::{read_volatile, write_volatile};
+
+use alloc::sync::Arc;
+use log::{debug, warn};
+
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::io::block::{self, BlockDevice, BlockError, BLOCK_SIZE};
+use crate::io::plic;
+use crate::io::virtio::{VirtQueue, VirtioError, VirtioMmio, DESC_F_NEXT, DESC_F_WRITE};
+use crate::mm::frame_allocator::{Frame, FrameAllocator, FRAME_ALLOCATOR};
+
+const REQUEST_IN: u32 = 0;
+const REQUEST_OUT: u32 = 1;
+const STATUS_OK: u8 = 0;
+/// Written before submitting, so a request the device never touched doesn't look successful
+const STATUS_PENDING: u8 = 0xff;
+
+/// Offset of the capacity (in 512 bytes sectors) in the config space
+const CONFIG_CAPACITY: usize = 0;
+
+// layout of the bounce page shared with the device
+const HEADER_OFFSET: usize = 0;
+const STATUS_OFFSET: usize = 16;
+const DATA_OFFSET: usize = BLOCK_SIZE;
+
+#[repr(C)]
+struct RequestHeader {
+    request_type: u32,
+    reserved: u32,
+    sector: u64
+}
+
+struct VirtioBlkInner {
+    queue: VirtQueue,
+    /// Header, status and data of the request in flight. Callers' buffers may live on a
+    /// kernel stack, which is not identically mapped, so data is always copied through here
+    bounce: Frame
+}
+
+pub struct VirtioBlk {
+    transport: VirtioMmio,
+    capacity: usize,
+    /// Whether completion is signaled through the interrupt controller
+    irq_driven: bool,
+    inner: SingleThreadSafeCell<VirtioBlkInner>
+}
+
+impl VirtioBlk {
+    fn new(transport: VirtioMmio) -> Result<Self, VirtioError> {
+        transport.begin_init(0)?;
+        let queue = VirtQueue::new()?;
+        transport.setup_queue(0, &queue)?;
+        let ppn = FRAME_ALLOCATOR.exclusive_access().alloc().ok_or(VirtioError::OutOfMemory)?;
+        let bounce = Frame::new(ppn);
+        let capacity = transport.read_config_u64(CONFIG_CAPACITY) as usize;
+        transport.finish_init();
+        Ok(VirtioBlk {
+            transport,
+            capacity,
+            irq_driven: false,
+            inner: SingleThreadSafeCell::new(VirtioBlkInner { queue, bounce })
+        })
+    }
+
+    /// Submit a single request and wait for it. The data is copied in/out of the bounce
+    /// page by `fill`/`drain`
+    fn request(&self, request_type: u32, block_id: usize,
+               fill: impl FnOnce(&mut [u8]), drain: impl FnOnce(&[u8])) -> Result<(), BlockError> {
+        if block_id >= self.capacity {
+            return Err(BlockError::OutOfRange(block_id));
+        }
+        let mut inner = self.inner.exclusive_access();
+        let base: usize = inner.bounce.ppn().start_addr().into();
+        let data = unsafe { core::slice::from_raw_parts_mut((base + DATA_OFFSET) as *mut u8, BLOCK_SIZE) };
+        fill(data);
+        unsafe {
+            write_volatile((base + HEADER_OFFSET) as *mut RequestHeader,
+                           RequestHeader { request_type, reserved: 0, sector: block_id as u64 });
+            write_volatile((base + STATUS_OFFSET) as *mut u8, STATUS_PENDING);
+        }
+        let data_flags = if request_type == REQUEST_IN { DESC_F_NEXT | DESC_F_WRITE } else { DESC_F_NEXT };
+        inner.queue.set_descriptor(0, base + HEADER_OFFSET, size_of::<RequestHeader>(), DESC_F_NEXT, 1);
+        inner.queue.set_descriptor(1, base + DATA_OFFSET, BLOCK_SIZE, data_flags, 2);
+        inner.queue.set_descriptor(2, base + STATUS_OFFSET, 1, DESC_F_WRITE, 0);
+        inner.queue.push_available(0);
+        self.transport.notify(0);
+
+        while inner.queue.pop_used().is_none() {
+            if self.irq_driven {
+                // woken up by any locally enabled interrupt even with interrupts globally
+                // disabled, serve the external ones so the controller doesn't stay pending
+                unsafe { asm!("wfi"); }
+                plic::handle_external_interrupt();
+            } else {
+                self.transport.ack_interrupt();
+                core::hint::spin_loop();
+            }
+        }
+        let status = unsafe { read_volatile((base + STATUS_OFFSET) as *const u8) };
+        if status != STATUS_OK {
+            return Err(BlockError::DeviceError(status));
+        }
+        drain(data);
+        Ok(())
+    }
+}
+
+impl BlockDevice for VirtioBlk {
+    fn num_blocks(&self) -> usize {
+        self.capacity
+    }
+
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        if buf.len() != BLOCK_SIZE {
+            return Err(BlockError::InvalidBuffer(buf.len()));
+        }
+        self.request(REQUEST_IN, block_id, |_| {}, |data| buf.copy_from_slice(data))
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
+        if buf.len() != BLOCK_SIZE {
+            return Err(BlockError::InvalidBuffer(buf.len()));
+        }
+        self.request(REQUEST_OUT, block_id, |data| data.copy_from_slice(buf), |_| {})
+    }
+}
+
+/// Bring up a virtio-blk device and register it as a block device
+pub fn probe(transport: VirtioMmio, irq: Option<u32>) -> Result<(), VirtioError> {
+    let mut device = VirtioBlk::new(transport)?;
+    if let Some(irq) = irq {
+        // the waiting side checks the used ring itself, only the device has to be quieted here
+        match plic::register_handler(irq, Arc::new(move || { transport.ack_interrupt(); })) {
+            Ok(()) => device.irq_driven = true,
+            Err(e) => warn!("[VIRTIO] Failed to register irq {}: {}, falling back to polling", irq, e)
+        }
+    }
+    let capacity = device.capacity;
+    let id = block::register(Arc::new(device));
+    debug!("[VIRTIO] Block device {} ready, {} blocks", id, capacity);
+    Ok(())
+}
@@ -0,0 +1,306 @@
+pub(crate) mod blk;
+
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{fence, Ordering};
+
+use alloc::vec::Vec;
+use log::{debug, warn};
+use thiserror::Error;
+
+use crate::io::dtb::DeviceTree;
+use crate::mm::address::{PhysPageNumber, PAGE_SIZE_BYTES};
+use crate::mm::frame_allocator::{Frame, FRAME_ALLOCATOR};
+use crate::mm::kernel::KERNEL_MEMORY_MANAGER;
+
+pub const COMPATIBLE: &str = "virtio,mmio";
+
+const MAGIC: u32 = 0x7472_6976; // "virt"
+const DEVICE_ID_BLOCK: u32 = 2;
+
+// register offsets of the mmio transport
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DEVICE_FEATURES: usize = 0x010;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028; // legacy only
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c; // legacy only
+const QUEUE_PFN: usize = 0x040; // legacy only
+const QUEUE_READY: usize = 0x044;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080;
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+const CONFIG: usize = 0x100;
+
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+const STATUS_FAILED: u32 = 128;
+
+/// Bit 32 of the features, mandatory for non-legacy devices
+const FEATURE_VERSION_1: u32 = 1 << 0;
+
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+
+#[derive(Debug, Error)]
+pub enum VirtioError {
+    #[error("bad magic value {0:#x}")]
+    BadMagic(u32),
+    #[error("unsupported version {0}")]
+    UnsupportedVersion(u32),
+    #[error("features not accepted by the device")]
+    FeaturesRejected,
+    #[error("queue {0} unavailable")]
+    QueueUnavailable(u32),
+    #[error("out of memory for the queues")]
+    OutOfMemory
+}
+
+/// Registers of a virtio-mmio device, both the legacy (version 1) and the
+/// modern (version 2) interface are supported
+#[derive(Clone, Copy)]
+pub struct VirtioMmio {
+    base: usize,
+    version: u32
+}
+
+impl VirtioMmio {
+    fn new(base: usize) -> Result<Self, VirtioError> {
+        let mut transport = VirtioMmio { base, version: 0 };
+        let magic = transport.read(MAGIC_VALUE);
+        if magic != MAGIC {
+            return Err(VirtioError::BadMagic(magic));
+        }
+        transport.version = transport.read(VERSION);
+        if transport.version != 1 && transport.version != 2 {
+            return Err(VirtioError::UnsupportedVersion(transport.version));
+        }
+        Ok(transport)
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { write_volatile((self.base + offset) as *mut u32, value) }
+    }
+
+    pub fn device_id(&self) -> u32 {
+        self.read(DEVICE_ID)
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version == 1
+    }
+
+    pub fn read_config_u32(&self, offset: usize) -> u32 {
+        self.read(CONFIG + offset)
+    }
+
+    pub fn read_config_u64(&self, offset: usize) -> u64 {
+        self.read_config_u32(offset) as u64 | (self.read_config_u32(offset + 4) as u64) << 32
+    }
+
+    /// Reset the device and negotiate the features, only the ones in `supported` are accepted
+    pub fn begin_init(&self, supported: u64) -> Result<(), VirtioError> {
+        self.write(STATUS, 0);
+        self.write(STATUS, STATUS_ACKNOWLEDGE);
+        self.write(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        self.write(DEVICE_FEATURES_SEL, 0);
+        let mut features = self.read(DEVICE_FEATURES) as u64;
+        self.write(DEVICE_FEATURES_SEL, 1);
+        features |= (self.read(DEVICE_FEATURES) as u64) << 32;
+        let mut accepted = features & supported;
+        if !self.is_legacy() {
+            accepted |= (FEATURE_VERSION_1 as u64) << 32;
+        }
+        self.write(DRIVER_FEATURES_SEL, 0);
+        self.write(DRIVER_FEATURES, accepted as u32);
+        self.write(DRIVER_FEATURES_SEL, 1);
+        self.write(DRIVER_FEATURES, (accepted >> 32) as u32);
+
+        if self.is_legacy() {
+            self.write(GUEST_PAGE_SIZE, PAGE_SIZE_BYTES as u32);
+            return Ok(());
+        }
+        self.write(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK);
+        if self.read(STATUS) & STATUS_FEATURES_OK == 0 {
+            self.write(STATUS, STATUS_FAILED);
+            return Err(VirtioError::FeaturesRejected);
+        }
+        Ok(())
+    }
+
+    pub fn finish_init(&self) {
+        let status = self.read(STATUS);
+        self.write(STATUS, status | STATUS_DRIVER_OK);
+    }
+
+    pub fn fail(&self) {
+        self.write(STATUS, STATUS_FAILED);
+    }
+
+    fn setup_queue(&self, index: u32, queue: &VirtQueue) -> Result<(), VirtioError> {
+        self.write(QUEUE_SEL, index);
+        let max = self.read(QUEUE_NUM_MAX);
+        if max < QUEUE_SIZE as u32 {
+            return Err(VirtioError::QueueUnavailable(index));
+        }
+        self.write(QUEUE_NUM, QUEUE_SIZE as u32);
+        if self.is_legacy() {
+            self.write(QUEUE_ALIGN, PAGE_SIZE_BYTES as u32);
+            self.write(QUEUE_PFN, queue.base_ppn().0 as u32);
+        } else {
+            let (desc, driver, device) = (queue.desc_addr() as u64, queue.avail_addr() as u64, queue.used_addr() as u64);
+            self.write(QUEUE_DESC_LOW, desc as u32);
+            self.write(QUEUE_DESC_HIGH, (desc >> 32) as u32);
+            self.write(QUEUE_DRIVER_LOW, driver as u32);
+            self.write(QUEUE_DRIVER_HIGH, (driver >> 32) as u32);
+            self.write(QUEUE_DEVICE_LOW, device as u32);
+            self.write(QUEUE_DEVICE_HIGH, (device >> 32) as u32);
+            self.write(QUEUE_READY, 1);
+        }
+        Ok(())
+    }
+
+    fn notify(&self, index: u32) {
+        self.write(QUEUE_NOTIFY, index);
+    }
+
+    /// Acknowledge a pending interrupt, returns whether there was any
+    pub fn ack_interrupt(&self) -> bool {
+        let status = self.read(INTERRUPT_STATUS);
+        if status != 0 {
+            self.write(INTERRUPT_ACK, status);
+        }
+        status != 0
+    }
+}
+
+/// Small enough for the descriptors and the available ring to share a page
+pub const QUEUE_SIZE: usize = 16;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16
+}
+
+/// A split virtqueue in two physically contiguous pages, laid out the way the legacy
+/// interface expects: descriptors and the available ring in the first page,
+/// the used ring in the second one. Physical memory is identically mapped, so the
+/// rings are accessed through their physical addresses
+pub struct VirtQueue {
+    frames: Vec<Frame>,
+    /// Value of the available index the device has caught up with
+    last_used: u16
+}
+
+impl VirtQueue {
+    fn new() -> Result<Self, VirtioError> {
+        let start = FRAME_ALLOCATOR.exclusive_access().alloc_contiguous(2).ok_or(VirtioError::OutOfMemory)?;
+        let frames = (0..2).map(|i| Frame::new(start + i)).collect();
+        Ok(VirtQueue { frames, last_used: 0 })
+    }
+
+    fn base_ppn(&self) -> PhysPageNumber {
+        self.frames[0].ppn()
+    }
+
+    fn desc_addr(&self) -> usize {
+        self.base_ppn().start_addr().into()
+    }
+
+    fn avail_addr(&self) -> usize {
+        self.desc_addr() + size_of::<Descriptor>() * QUEUE_SIZE
+    }
+
+    fn used_addr(&self) -> usize {
+        self.frames[1].ppn().start_addr().into()
+    }
+
+    fn set_descriptor(&mut self, index: usize, addr: usize, len: usize, flags: u16, next: usize) {
+        let desc = (self.desc_addr() + size_of::<Descriptor>() * index) as *mut Descriptor;
+        unsafe {
+            write_volatile(desc, Descriptor { addr: addr as u64, len: len as u32, flags, next: next as u16 });
+        }
+    }
+
+    /// Hand the chain starting at descriptor `head` over to the device
+    fn push_available(&mut self, head: usize) {
+        let idx_ptr = (self.avail_addr() + 2) as *mut u16;
+        unsafe {
+            let idx = read_volatile(idx_ptr);
+            let slot = (self.avail_addr() + 4 + 2 * (idx as usize % QUEUE_SIZE)) as *mut u16;
+            write_volatile(slot, head as u16);
+            // the entry has to be visible before the index
+            fence(Ordering::SeqCst);
+            write_volatile(idx_ptr, idx.wrapping_add(1));
+            fence(Ordering::SeqCst);
+        }
+    }
+
+    /// Take a finished chain from the used ring
+    fn pop_used(&mut self) -> Option<usize> {
+        fence(Ordering::SeqCst);
+        let idx = unsafe { read_volatile((self.used_addr() + 2) as *const u16) };
+        if idx == self.last_used {
+            return None;
+        }
+        let slot = (self.used_addr() + 4 + 8 * (self.last_used as usize % QUEUE_SIZE)) as *const u32;
+        let head = unsafe { read_volatile(slot) };
+        self.last_used = self.last_used.wrapping_add(1);
+        Some(head as usize)
+    }
+}
+
+/// Probe every virtio-mmio slot in the device tree and bring up the drivers we have
+pub fn init(device_tree: &DeviceTree) {
+    for node in device_tree.find_compatible(COMPATIBLE) {
+        let Some(reg) = node.reg.first().cloned() else {
+            continue;
+        };
+        if let Err(e) = KERNEL_MEMORY_MANAGER.exclusive_access().map_mmio(reg.clone()) {
+            warn!("[VIRTIO] Failed to map registers of {}: {}", node.name, e);
+            continue;
+        }
+        let transport = match VirtioMmio::new(reg.start) {
+            Ok(transport) => transport,
+            Err(e) => {
+                warn!("[VIRTIO] Ignoring {}: {}", node.name, e);
+                continue;
+            }
+        };
+        let irq = node.interrupts.first().copied();
+        match transport.device_id() {
+            // nothing attached to this slot
+            0 => {},
+            DEVICE_ID_BLOCK => {
+                debug!("[VIRTIO] Block device {} at {:#x}, version {}, irq {:?}", node.name, reg.start, transport.version, irq);
+                if let Err(e) = blk::probe(transport, irq) {
+                    transport.fail();
+                    warn!("[VIRTIO] Failed to initialize block device {}: {}", node.name, e);
+                }
+            },
+            id => debug!("[VIRTIO] Unsupported device type {} at {}", id, node.name)
+        }
+    }
+}
@@ -2,7 +2,6 @@
 use alloc::string::ToString;
 use core::fmt::Arguments;
 use crate::console::{print, print_with_color};
-use crate::sbi;
 use spin::RwLock;
 pub struct Logger{
     heap_enabled: RwLock<bool>
@@ -32,7 +31,7 @@ impl log::Log for Logger{
         let args = with_color!(args, color_code);
         let heap_enabled = *self.heap_enabled.read();
         if heap_enabled {
-            sbi::putstr(&args.to_string());
+            crate::console::putstr(&args.to_string());
         }else{
             print(args);
         }
@@ -9,13 +9,13 @@ mod sbi;
 #[macro_use]
 mod console;
 mod logging;
-mod loader;
 mod trap;
 mod syscall;
 mod task;
 mod timer;
 mod mm;
 mod io;
+mod fs;
 
 use core::arch::global_asm;
 use dtb_walker::{utils::indent, Dtb, DtbObj, WalkOperation, Property};
@@ -30,7 +30,6 @@ use task::TASK_MANAGER;
 use crate::sbi::shutdown;
 
 global_asm!(include_str!("entry.asm"));
-global_asm!(include_str!("link_app.S"));
 
 #[unsafe(no_mangle)]
 fn rust_main(_hart_id: usize, device_tree_ptr: usize) {
@@ -55,10 +54,11 @@ fn rust_main(_hart_id: usize, device_tree_ptr: usize) {
     logging::enable_heap_logging();
     io::init(device_tree_ptr as *const u8);
     mm::init();
+    io::init_devices();
+    fs::init();
     trap::init();
-    // trap::enable_timer_interrupt();
-    // timer::set_next_trigger();
-    // loader::load_apps();
+    trap::enable_timer_interrupt();
+    timer::set_next_trigger();
     TASK_MANAGER.run_next_app();
     // info!("[Kernel] No works to do, shutdown");
     // system_reset(Shutdown, NoReason);
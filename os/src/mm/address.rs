@@ -8,13 +8,37 @@ pub const PAGE_SIZE_WIDTH: usize = 12;
 pub const PAGE_SIZE_BYTES: usize = 1 << PAGE_SIZE_WIDTH; // 4KB page size
 const PPN_WIDTH_SV39: usize = PA_WIDTH_SV39 -PAGE_SIZE_WIDTH;
 const VPN_WIDTH_SV39: usize = VA_WIDTH_SV39 -PAGE_SIZE_WIDTH;
+/// Size of the user stack a program starts with
 pub const USER_STACK_SIZE: usize = 0x4000;
+const DEFAULT_USER_STACK_MAX_KB: usize = 8192;
+/// Size the user stack may grow to on page faults, can be overridden at build time through
+/// `CHIBIMOS_USER_STACK_MAX_KB` (e.g. `make run USER_STACK_MAX_KB=64`)
+pub const USER_STACK_MAX_SIZE: usize = match option_env!("CHIBIMOS_USER_STACK_MAX_KB") {
+    Some(s) => parse_stack_size(s.as_bytes()),
+    None => DEFAULT_USER_STACK_MAX_KB * 1024
+};
 pub const KERNEL_STACK_SIZE: usize = 0x40000;
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE_BYTES + 1; // 错误3：：未对齐页
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE_BYTES;
+/// Top of the lower half of the address space, mappings are placed downwards from here
+pub const MMAP_TOP: usize = 1 << (VA_WIDTH_SV39 - 1);
+/// The user stack sits at the very top, so mappings end up right below it
+pub const USER_STACK_TOP: usize = MMAP_TOP;
 pub const VPN_MASK: usize = (1 << VA_WIDTH_SV39) - 1;
-pub fn kernel_stack_position(process_id: usize) -> Range<VirtAddr> {
-    let top = TRAMPOLINE - process_id * (KERNEL_STACK_SIZE + PAGE_SIZE_BYTES); // guard page calculated
+const fn parse_stack_size(s: &[u8]) -> usize {
+    let mut kb = 0;
+    let mut i = 0;
+    while i < s.len() {
+        assert!(s[i].is_ascii_digit(), "CHIBIMOS_USER_STACK_MAX_KB must be a decimal number");
+        kb = kb * 10 + (s[i] - b'0') as usize;
+        i += 1;
+    }
+    let size = (kb * 1024).next_multiple_of(PAGE_SIZE_BYTES);
+    assert!(size >= USER_STACK_SIZE, "CHIBIMOS_USER_STACK_MAX_KB can't be below the initial stack size");
+    size
+}
+pub fn kernel_stack_position(slot: usize) -> Range<VirtAddr> {
+    let top = TRAMPOLINE - slot * (KERNEL_STACK_SIZE + PAGE_SIZE_BYTES); // guard page calculated
     let bottom = top - KERNEL_STACK_SIZE;
     VirtAddr(bottom)..VirtAddr(top)
 }
@@ -1,5 +1,6 @@
 use core::{error::Error, fmt::Display};
 
+use alloc::collections::btree_map::BTreeMap;
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
 use log::debug;
@@ -16,14 +17,17 @@ pub trait FrameAllocator {
 }
 
 pub struct StackFrameAllocator {
+    start: usize,
     current: usize,
     end: usize,
-    recycled: Vec<usize>
+    recycled: Vec<usize>,
+    /// References to the frames owned more than once, the others have a single owner
+    shared: BTreeMap<usize, usize>
 }
 
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
-        StackFrameAllocator { current: 0, end: 0, recycled: Vec::new() }
+        StackFrameAllocator { start: 0, current: 0, end: 0, recycled: Vec::new(), shared: BTreeMap::new() }
     }
     
     fn alloc(&mut self) -> Option<PhysPageNumber> {
@@ -52,9 +56,51 @@ impl FrameAllocator for StackFrameAllocator {
 
 impl StackFrameAllocator {
     fn init(&mut self, start: PhysPageNumber, end: PhysPageNumber) {
+        self.start = start.into();
         self.current = start.into();
         self.end = end.into();
     }
+
+    /// Number of frames managed, i.e. the memory after the kernel image
+    pub fn total_frames(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn free_frames(&self) -> usize {
+        self.end - self.current + self.recycled.len()
+    }
+
+    /// One more owner of `ppn`
+    fn share(&mut self, ppn: PhysPageNumber) {
+        *self.shared.entry(ppn.into()).or_insert(1) += 1;
+    }
+
+    /// One owner less of `ppn`, which is freed with the last one
+    fn release(&mut self, ppn: PhysPageNumber) {
+        let key: usize = ppn.into();
+        match self.shared.get_mut(&key) {
+            Some(count) if *count > 2 => *count -= 1,
+            Some(_) => {
+                self.shared.remove(&key);
+            },
+            None => self.dealloc(ppn)
+        }
+    }
+
+    fn ref_count(&self, ppn: PhysPageNumber) -> usize {
+        self.shared.get(&ppn.into()).copied().unwrap_or(1)
+    }
+
+    /// Allocate `count` physically contiguous pages (e.g. for DMA), returns the first one.
+    /// Recycled pages are scattered, so they are always taken from the untouched part
+    pub fn alloc_contiguous(&mut self, count: usize) -> Option<PhysPageNumber> {
+        if self.current + count > self.end {
+            return None;
+        }
+        let ppn = PhysPageNumber(self.current);
+        self.current += count;
+        Some(ppn)
+    }
 }
 
 
@@ -83,6 +129,8 @@ impl Display for AllocError {
 impl Error for AllocError {
     
 }
+/// A frame owned by one or more page mappings, cloning it shares it rather than its content.
+/// It goes back to the allocator when its last owner drops it
 pub struct Frame{
     ppn: PhysPageNumber
 }
@@ -96,10 +144,21 @@ impl Frame {
     pub fn ppn(&self) -> PhysPageNumber {
         self.ppn
     }
+    /// Number of owners of the frame, writing to it is only safe when this is 1
+    pub fn ref_count(&self) -> usize {
+        FRAME_ALLOCATOR.exclusive_access().ref_count(self.ppn)
+    }
+}
+
+impl Clone for Frame {
+    fn clone(&self) -> Self {
+        FRAME_ALLOCATOR.exclusive_access().share(self.ppn);
+        Frame { ppn: self.ppn }
+    }
 }
 
 impl Drop for Frame {
     fn drop(&mut self) {
-        FRAME_ALLOCATOR.exclusive_access().dealloc(self.ppn);
+        FRAME_ALLOCATOR.exclusive_access().release(self.ppn);
     }
 }
\ No newline at end of file
@@ -1,4 +1,6 @@
+use core::arch::asm;
 use core::cell::OnceCell;
+use core::ops::Range;
 use lazy_static::lazy_static;
 use log::debug;
 use crate::helper::cell::SingleThreadSafeCell;
@@ -37,8 +39,8 @@ impl KernelMemoryManager {
     pub fn is_identical_address(&self, address: usize) -> bool {
         address < ekernel as usize
     }
-    pub fn map_stack_for_process_syscall(&mut self, id: usize) -> Result<(), MemoryStructureError> {
-        let stack_range = address::kernel_stack_position(id);
+    pub fn map_stack_for_process_syscall(&mut self, slot: usize) -> Result<(), MemoryStructureError> {
+        let stack_range = address::kernel_stack_position(slot);
 
         self.memory_set.get_mut().unwrap().push(
             MemoryArea::new(stack_range.start,
@@ -51,6 +53,29 @@ impl KernelMemoryManager {
         Ok(())
     }
 
+    pub fn unmap_stack_for_process_syscall(&mut self, slot: usize) -> Result<(), MemoryStructureError> {
+        let stack_range = address::kernel_stack_position(slot);
+
+        self.memory_set.get_mut().unwrap().remove_area_with_start_vpn(stack_range.start.vpn())?;
+        // the kernel address space is active, drop the stale translations of the stack
+        unsafe { asm!("sfence.vma"); }
+        Ok(())
+    }
+
+    /// Identically map the registers of a device
+    pub fn map_mmio(&mut self, range: Range<usize>) -> Result<(), MemoryStructureError> {
+        debug!("[MM] Mapping MMIO [{:#x}, {:#x})", range.start, range.end);
+        self.memory_set.get_mut().unwrap().push(
+            MemoryArea::new(range.start.into(),
+                            range.end.into(),
+                            MemoryAreaType::Identical,
+                            MemoryAreaPermissions::R | MemoryAreaPermissions::W
+            )?, None
+        )?;
+        unsafe { asm!("sfence.vma"); }
+        Ok(())
+    }
+
     pub fn translate_byte_buffer(&self, ptr: *const u8, len: usize) -> Result<AddressIterator, MemoryStructureError> {
         self.memory_set.get().unwrap().translate_byte_buffer((ptr as usize).into(), len)
     }
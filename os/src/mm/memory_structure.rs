@@ -1,26 +1,42 @@
 use core::cmp::min;
 use core::{arch::asm, cmp::max, ops::Range};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-use alloc::{collections::btree_map::BTreeMap, format, vec::Vec};
+use alloc::{collections::btree_map::BTreeMap, format, sync::Arc, vec::Vec};
 use elf::endian::AnyEndian;
 use log::debug;
 use riscv::register::satp::{self, Satp};
 use riscv::register::satp::Mode as SatpMode;
 use thiserror::Error;
 use elf::{abi, ElfBytes, ParseError as ElfParseError};
+use crate::fs::{file::FileError, page_cache::PageCache};
 use crate::mm::address::PhysAddr;
-use crate::mm::{address::{IntoUsizeRange, PhysPageNumber, VirtAddr, VirtPageNumber, PAGE_SIZE_BYTES, PAGE_SIZE_WIDTH, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE}, frame_allocator::{Frame, FrameAllocator, FRAME_ALLOCATOR}, page_table::{PTEFlags, PageTable, PageTableError}};
+use crate::mm::{address::{IntoUsizeRange, PhysPageNumber, VirtAddr, VirtPageNumber, MMAP_TOP, PAGE_SIZE_BYTES, PAGE_SIZE_WIDTH, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_MAX_SIZE, USER_STACK_SIZE, USER_STACK_TOP}, frame_allocator::{Frame, FrameAllocator, FRAME_ALLOCATOR}, page_table::{PTEFlags, PageTable, PageTableError}};
 use crate::sbi::putstr_debug;
 
+#[derive(Clone, Copy)]
 pub enum MemoryAreaType {
     Identical,
-    Framed
+    Framed,
+    /// Pages of a file, mapped when first touched
+    FileBacked,
+    /// Like `Framed`, but a page only gets its zeroed frame when first touched
+    Lazy,
+    /// Room below the stack, which takes the pages above a fault in it. Nothing is ever
+    /// mapped here, and the lowest page is never given away
+    Guard
 }
 unsafe extern{
     fn strampoline();
 }
+/// Writes to a page shared since a fork, each gave the writer a page of its own
+static COW_FAULTS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn cow_faults() -> usize {
+    COW_FAULTS.load(Ordering::Relaxed)
+}
 bitflags! {
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, PartialEq, Eq)]
     pub struct MemoryAreaPermissions: u8 {
         const R = 1 << 1;
         const W = 1 << 2;
@@ -32,7 +48,21 @@ pub struct MemoryArea{
     vpn_range: Range<VirtPageNumber>,
     map_type: MemoryAreaType,
     map_permissions: MemoryAreaPermissions,
-    frames: BTreeMap<VirtPageNumber, Frame>
+    frames: BTreeMap<VirtPageNumber, Frame>,
+    /// What a file-backed area maps
+    file: Option<FileMapping>
+}
+/// The part of a file a file-backed area maps
+#[derive(Clone)]
+pub struct FileMapping {
+    pub cache: Arc<PageCache>,
+    /// Page of the file the area starts at
+    pub first_page: usize,
+    /// Writes reach the file and the other mappings, otherwise the area copies every page it
+    /// touches
+    pub shared: bool,
+    /// The file was opened for writing, shared mappings can only be made writable if so
+    pub writable: bool
 }
 #[derive(Debug, Error)]
 pub enum MemoryStructureError {
@@ -51,7 +81,13 @@ pub enum MemoryStructureError {
     #[error("address not aligned")]
     AddressNotAligned,
     #[error("cross memory area not allowed")]
-    CrossMemoryAreaNotAllowed
+    CrossMemoryAreaNotAllowed,
+    #[error("access denied")]
+    AccessDenied,
+    #[error("stack overflow")]
+    StackOverflow,
+    #[error(transparent)]
+    File(#[from] FileError)
 }
 
 impl MemoryArea {
@@ -73,16 +109,40 @@ impl MemoryArea {
             vpn_range: start_ppn..end_ppn,
             map_type,
             map_permissions,
-            frames: BTreeMap::new()
+            frames: BTreeMap::new(),
+            file: None
         })
     }
+    /// An area mapping `mapping` over `vpn_range`, no page is mapped before it is accessed
+    pub fn new_file_backed(vpn_range: Range<VirtPageNumber>, map_permissions: MemoryAreaPermissions, mapping: FileMapping) -> Self {
+        MemoryArea {
+            vpn_range,
+            map_type: MemoryAreaType::FileBacked,
+            map_permissions,
+            frames: BTreeMap::new(),
+            file: Some(mapping)
+        }
+    }
+    /// Create an area with the same range and attributes as `another`, but no frames mapped yet
+    pub fn from_another(another: &MemoryArea) -> Self {
+        MemoryArea {
+            vpn_range: another.vpn_range.clone(),
+            map_type: another.map_type,
+            map_permissions: another.map_permissions,
+            frames: BTreeMap::new(),
+            file: another.file.clone()
+        }
+    }
     pub fn map(&mut self, page_table: &mut PageTable) -> Result<(), MemoryStructureError> {
+        if self.is_lazy() || matches!(self.map_type, MemoryAreaType::Guard) {
+            return Ok(());
+        }
         for vpn in self.vpn_range.clone().into_usize_range(){
             self.map_one(page_table, vpn.into())?;
         }
         Ok(())
     }
-    pub fn unmap(&self, page_table: &mut PageTable) -> Result<(), MemoryStructureError> {
+    pub fn unmap(&mut self, page_table: &mut PageTable) -> Result<(), MemoryStructureError> {
         for vpn in self.vpn_range.clone().into_usize_range(){
             self.unmap_one(page_table, vpn.into())?;
         }
@@ -95,28 +155,73 @@ impl MemoryArea {
             MemoryAreaType::Identical => {
                 ppn = Into::<usize>::into(vpn).into();
             },
-            MemoryAreaType::Framed => {
+            MemoryAreaType::Framed | MemoryAreaType::Lazy => {
                 ppn = FRAME_ALLOCATOR.exclusive_access().alloc().ok_or(
                     MemoryStructureError::OutOfMemory)?;
                 self.frames.insert(vpn, Frame::new(ppn));
-            }
+            },
+            MemoryAreaType::FileBacked => {
+                let file = self.file.as_ref().unwrap();
+                let index = file.first_page + Into::<usize>::into(vpn - self.vpn_range.start);
+                let cached = file.cache.page(index)?;
+                if file.shared {
+                    if self.map_permissions.contains(MemoryAreaPermissions::W) {
+                        file.cache.mark_dirty(index);
+                    }
+                    ppn = cached;
+                } else if let Some(frame) = self.frames.get(&vpn) {
+                    ppn = frame.ppn();
+                } else {
+                    ppn = FRAME_ALLOCATOR.exclusive_access().alloc().ok_or(
+                        MemoryStructureError::OutOfMemory)?;
+                    self.frames.insert(vpn, Frame::new(ppn));
+                    ppn.get_mut_array::<u8>().copy_from_slice(cached.get_array::<u8>());
+                }
+            },
+            MemoryAreaType::Guard => return Err(MemoryStructureError::AccessDenied)
+        }
+        // an entry without any of R, W and X would point to another level of page table,
+        // such pages keep their frame but stay unmapped until they are made accessible
+        if self.is_accessible() {
+            page_table.map(vpn, ppn, self.flags_of(vpn))?;
         }
-        let flags = PTEFlags::from_bits(self.map_permissions.bits()).unwrap();
-        page_table.map(vpn, ppn, flags)?;
         Ok(())
     }
 
-    fn unmap_one(&self, page_table: &mut PageTable, vpn: VirtPageNumber) -> Result<(), MemoryStructureError>{
-        if let MemoryAreaType::Framed = self.map_type {
-                let frame = self.frames.get(&vpn).unwrap();
-                FRAME_ALLOCATOR.exclusive_access().dealloc(frame.ppn());
+    fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNumber) -> Result<(), MemoryStructureError>{
+        // the frame goes back to the allocator when dropped
+        self.frames.remove(&vpn);
+        // pages of lazy or inaccessible areas may have never been mapped
+        if page_table.translate(vpn).is_err() {
+            return Ok(());
         }
         page_table.unmap(vpn)?;
         Ok(())
     }
 
+    pub fn vpn_range(&self) -> Range<VirtPageNumber> {
+        self.vpn_range.clone()
+    }
+
+    pub fn map_type(&self) -> MemoryAreaType {
+        self.map_type
+    }
+
+    pub fn permissions(&self) -> MemoryAreaPermissions {
+        self.map_permissions
+    }
+
+    /// Number of frames owned by the area, identical areas own none
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn file(&self) -> Option<&FileMapping> {
+        self.file.as_ref()
+    }
+
     pub fn has_overlap_with(&self, other: &MemoryArea) -> bool {
-        let t = self.vpn_range.start <= other.vpn_range.end && other.vpn_range.start < self.vpn_range.end;
+        let t = self.overlaps(&other.vpn_range);
         if t {
             log::debug!("[MM] Memory area overlap detected: [{}, {}) overlaps with [{}, {})", 
                 self.vpn_range.start, self.vpn_range.end, 
@@ -125,6 +230,152 @@ impl MemoryArea {
         t
     }
 
+    /// Whether the area shares a page with `range`, areas that merely touch don't
+    fn overlaps(&self, range: &Range<VirtPageNumber>) -> bool {
+        self.vpn_range.start < range.end && range.start < self.vpn_range.end
+    }
+
+    fn is_accessible(&self) -> bool {
+        self.map_permissions.intersects(MemoryAreaPermissions::R | MemoryAreaPermissions::W | MemoryAreaPermissions::X)
+    }
+
+    /// Cut the area at `at`, keeping the pages below it and returning the others as a new area
+    fn split_off(&mut self, at: VirtPageNumber) -> MemoryArea {
+        let mut file = self.file.clone();
+        if let Some(file) = file.as_mut() {
+            file.first_page += Into::<usize>::into(at - self.vpn_range.start);
+        }
+        let upper = MemoryArea {
+            vpn_range: at..self.vpn_range.end,
+            map_type: self.map_type,
+            map_permissions: self.map_permissions,
+            frames: self.frames.split_off(&at),
+            file
+        };
+        self.vpn_range.end = at;
+        upper
+    }
+
+    /// Grow the area up to `end`, mapping the new pages
+    fn extend_to(&mut self, page_table: &mut PageTable, end: VirtPageNumber) -> Result<(), MemoryStructureError> {
+        let old_end = self.vpn_range.end;
+        self.vpn_range.end = end;
+        if self.is_lazy() {
+            return Ok(());
+        }
+        for vpn in Into::<usize>::into(old_end)..end.into() {
+            if let Err(e) = self.map_one(page_table, vpn.into()) {
+                for mapped in Into::<usize>::into(old_end)..vpn {
+                    self.unmap_one(page_table, mapped.into())?;
+                }
+                self.vpn_range.end = old_end;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Change the permissions of the area, rewriting the entries of the pages already mapped
+    fn protect(&mut self, page_table: &mut PageTable, permissions: MemoryAreaPermissions) -> Result<(), MemoryStructureError> {
+        self.map_permissions = permissions;
+        let accessible = self.is_accessible();
+        for vpn in self.vpn_range.clone().into_usize_range() {
+            let vpn: VirtPageNumber = vpn.into();
+            match (page_table.translate(vpn).is_ok(), accessible) {
+                (true, true) => {
+                    page_table.set_flags(vpn, self.flags_of(vpn))?;
+                    if let Some(file) = self.file.as_ref().filter(|file| file.shared)
+                        && permissions.contains(MemoryAreaPermissions::W) {
+                        file.cache.mark_dirty(file.first_page + Into::<usize>::into(vpn - self.vpn_range.start));
+                    }
+                },
+                (true, false) => page_table.unmap(vpn)?,
+                // pages kept while the area was inaccessible, the others are mapped on a fault
+                (false, true) => if let Some(frame) = self.frames.get(&vpn) {
+                    page_table.map(vpn, frame.ppn(), self.flags_of(vpn))?;
+                },
+                (false, false) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Pages are mapped when first touched rather than with the area
+    fn is_lazy(&self) -> bool {
+        matches!(self.map_type, MemoryAreaType::FileBacked | MemoryAreaType::Lazy)
+    }
+
+    /// Flags of the entry of `vpn`, a page shared since a fork stays read-only until written
+    fn flags_of(&self, vpn: VirtPageNumber) -> PTEFlags {
+        let mut flags = PTEFlags::from_bits(self.map_permissions.bits()).unwrap();
+        if self.frames.get(&vpn).is_some_and(|frame| frame.ref_count() > 1) {
+            flags.remove(PTEFlags::W);
+        }
+        flags
+    }
+
+    /// Map the page `vpn` of a lazy area on its first access, or give the writer of a page
+    /// shared since a fork its own copy
+    fn fault_in(&mut self, page_table: &mut PageTable, vpn: VirtPageNumber, access: MemoryAreaPermissions) -> Result<(), MemoryStructureError> {
+        if page_table.translate(vpn).is_ok() {
+            if access.contains(MemoryAreaPermissions::W) && self.frames.contains_key(&vpn) {
+                return self.copy_on_write(page_table, vpn);
+            }
+            return Err(MemoryStructureError::AccessDenied);
+        }
+        // other areas have all their pages mapped, the fault was a matter of permissions
+        if !self.is_lazy() {
+            return Err(MemoryStructureError::AccessDenied);
+        }
+        self.map_one(page_table, vpn)
+    }
+
+    /// Make the page `vpn` writable again, copying its frame unless nobody else owns it anymore
+    fn copy_on_write(&mut self, page_table: &mut PageTable, vpn: VirtPageNumber) -> Result<(), MemoryStructureError> {
+        let frame = self.frames.get_mut(&vpn).unwrap();
+        if frame.ref_count() > 1 {
+            let ppn = FRAME_ALLOCATOR.exclusive_access().alloc().ok_or(
+                MemoryStructureError::OutOfMemory)?;
+            let copy = Frame::new(ppn);
+            ppn.get_mut_array::<u8>().copy_from_slice(frame.ppn().get_array::<u8>());
+            *frame = copy;
+            page_table.unmap(vpn)?;
+            page_table.map(vpn, ppn, self.flags_of(vpn))?;
+        } else {
+            page_table.set_flags(vpn, self.flags_of(vpn))?;
+        }
+        COW_FAULTS.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// The area as a child sees it after a fork, in `child_table`. The frames are shared
+    /// rather than copied, and both sides lose write access to them until they write
+    fn share(&self, page_table: &mut PageTable, child_table: &mut PageTable) -> Result<MemoryArea, MemoryStructureError> {
+        let mut child = MemoryArea::from_another(self);
+        for (&vpn, frame) in self.frames.iter() {
+            child.frames.insert(vpn, frame.clone());
+            // inaccessible pages have a frame but no entry
+            if page_table.translate(vpn).is_ok() {
+                let flags = self.flags_of(vpn);
+                page_table.set_flags(vpn, flags)?;
+                child_table.map(vpn, frame.ppn(), flags)?;
+            }
+        }
+        Ok(child)
+    }
+
+    /// Write back the pages of a shared file mapping that lie in `range`
+    fn sync(&self, range: &Range<VirtPageNumber>) -> Result<(), MemoryStructureError> {
+        let Some(file) = self.file.as_ref().filter(|file| file.shared) else {
+            return Ok(());
+        };
+        let start = max(range.start, self.vpn_range.start);
+        let end = min(range.end, self.vpn_range.end);
+        let first = file.first_page + Into::<usize>::into(start - self.vpn_range.start);
+        file.cache.sync(first..first + Into::<usize>::into(end - start))?;
+        Ok(())
+    }
+
 }
 
 pub struct AddressIterator<'a>{
@@ -182,7 +433,11 @@ impl MemorySet {
         if self.areas.iter().any(|a| a.has_overlap_with(&area)) {
             return Err(MemoryStructureError::OverlappedMemoryArea);
         }
-        area.map(&mut self.page_table)?;
+        if let Err(e) = area.map(&mut self.page_table) {
+            // the frames go away with the area, so must their entries
+            area.unmap(&mut self.page_table)?;
+            return Err(e);
+        }
         if let Some(data) = data {
             let data_pages = (data.len() + PAGE_SIZE_BYTES - 1) / (PAGE_SIZE_BYTES);
             if data_pages > area.frames.len() {
@@ -199,6 +454,179 @@ impl MemorySet {
         Ok(())
     }
 
+    /// Duplicate a user address space area by area. The frames of user pages are shared
+    /// copy-on-write, shared pages of files are faulted in again by the child. The trap
+    /// context page, written by the kernel through its frame, is copied
+    pub fn from_existed_user(user_space: &mut MemorySet) -> Result<Self, MemoryStructureError> {
+        let mut set = MemorySet::new()?;
+        set.map_trampoline()?;
+        for area in user_space.areas.iter() {
+            if area.map_permissions.contains(MemoryAreaPermissions::U) && !matches!(area.map_type, MemoryAreaType::Identical) {
+                let new_area = area.share(&mut user_space.page_table, &mut set.page_table)?;
+                set.areas.push(new_area);
+                continue;
+            }
+            set.push(MemoryArea::from_another(area), None)?;
+            let new_area = set.areas.last().unwrap();
+            for (vpn, frame) in area.frames.iter() {
+                let dst_ppn = new_area.frames[vpn].ppn();
+                dst_ppn.get_mut_array::<u8>().copy_from_slice(frame.ppn().get_array::<u8>());
+            }
+        }
+        Ok(set)
+    }
+
+    /// Unmap the area starting at `start_vpn` and release its frames
+    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNumber) -> Result<(), MemoryStructureError> {
+        let index = self.areas.iter().position(|a| a.vpn_range.start == start_vpn)
+            .ok_or(MemoryStructureError::InvalidMemoryArea(start_vpn..start_vpn))?;
+        let mut area = self.areas.remove(index);
+        area.unmap(&mut self.page_table)?;
+        Ok(())
+    }
+
+    pub fn areas(&self) -> &[MemoryArea] {
+        &self.areas
+    }
+
+    /// Resolve a page fault at `va` caused by an `access` (one of R, W and X), which is
+    /// allowed if the area covering `va` permits it and maps its pages lazily
+    pub fn handle_page_fault(&mut self, va: VirtAddr, access: MemoryAreaPermissions) -> Result<(), MemoryStructureError> {
+        let vpn = va.vpn();
+        let index = self.areas.iter().position(|a| a.vpn_range.contains(&vpn))
+            .ok_or(MemoryStructureError::InvalidMemoryArea(vpn..vpn))?;
+        if matches!(self.areas[index].map_type, MemoryAreaType::Guard) {
+            return self.grow_stack(index, vpn, access);
+        }
+        let area = &mut self.areas[index];
+        if !area.map_permissions.contains(access | MemoryAreaPermissions::U) {
+            return Err(MemoryStructureError::AccessDenied);
+        }
+        area.fault_in(&mut self.page_table, vpn, access)
+    }
+
+    /// Grow the stack right above the guard area at `guard` down to `vpn`, which is in the
+    /// guard area. The lowest page of the guard area is never taken
+    fn grow_stack(&mut self, guard: usize, vpn: VirtPageNumber, access: MemoryAreaPermissions) -> Result<(), MemoryStructureError> {
+        let guard_range = self.areas[guard].vpn_range.clone();
+        if vpn == guard_range.start {
+            return Err(MemoryStructureError::StackOverflow);
+        }
+        let stack = self.areas.iter().position(|a| a.vpn_range.start == guard_range.end
+            && matches!(a.map_type, MemoryAreaType::Lazy))
+            .ok_or(MemoryStructureError::StackOverflow)?;
+        if !self.areas[stack].map_permissions.contains(access | MemoryAreaPermissions::U) {
+            return Err(MemoryStructureError::AccessDenied);
+        }
+        self.areas[guard].vpn_range.end = vpn;
+        self.areas[stack].vpn_range.start = vpn;
+        self.areas[stack].fault_in(&mut self.page_table, vpn, access)
+    }
+
+    fn is_free(&self, range: &Range<VirtPageNumber>) -> bool {
+        !self.areas.iter().any(|a| a.overlaps(range))
+    }
+
+    /// Whether every page of `range` is in some area
+    fn covers(&self, range: &Range<VirtPageNumber>) -> bool {
+        let covered: usize = self.areas.iter().filter(|a| a.overlaps(range))
+            .map(|a| Into::<usize>::into(min(a.vpn_range.end, range.end) - max(a.vpn_range.start, range.start)))
+            .sum();
+        covered == Into::<usize>::into(range.end - range.start)
+    }
+
+    /// A free range of `pages` pages below `MMAP_TOP`, starting at `hint` if there is room
+    /// there, the highest one otherwise
+    pub fn find_free_range(&self, hint: VirtPageNumber, pages: usize) -> Option<Range<VirtPageNumber>> {
+        let top = VirtAddr(MMAP_TOP).vpn();
+        let hinted = Into::<usize>::into(hint).checked_add(pages)
+            .filter(|&end| hint > VirtPageNumber(0) && end <= top.into())
+            .map(|end| hint..VirtPageNumber(end));
+        if let Some(range) = hinted.filter(|range| self.is_free(range)) {
+            return Some(range);
+        }
+        // a free range that fits ends either at the top or where an area starts
+        let mut ends: Vec<VirtPageNumber> = self.areas.iter().map(|a| a.vpn_range.start)
+            .filter(|&start| start <= top)
+            .chain([top])
+            .collect();
+        ends.sort_unstable_by(|a, b| b.cmp(a));
+        ends.into_iter()
+            .filter_map(|end| Into::<usize>::into(end).checked_sub(pages)
+                .filter(|&start| start > 0)
+                .map(|start| VirtPageNumber(start)..end))
+            .find(|range| self.is_free(range))
+    }
+
+    /// Split the areas crossing a bound of `range`, so that each area is either in it or out of it
+    fn split_at_bounds(&mut self, range: &Range<VirtPageNumber>) {
+        for bound in [range.start, range.end] {
+            if let Some(index) = self.areas.iter().position(|a| a.vpn_range.start < bound && bound < a.vpn_range.end) {
+                let upper = self.areas[index].split_off(bound);
+                self.areas.insert(index + 1, upper);
+            }
+        }
+    }
+
+    /// Remove whatever is mapped in `range`, areas partly in it lose the pages that are
+    pub fn unmap_range(&mut self, range: Range<VirtPageNumber>) -> Result<(), MemoryStructureError> {
+        self.split_at_bounds(&range);
+        let (removed, kept): (Vec<_>, Vec<_>) = core::mem::take(&mut self.areas).into_iter()
+            .partition(|a| a.overlaps(&range));
+        self.areas = kept;
+        for mut area in removed {
+            area.unmap(&mut self.page_table)?;
+        }
+        Ok(())
+    }
+
+    /// Move the end of the heap starting at `bottom` from `old_end` to `end`, mapping or
+    /// unmapping the pages in between
+    pub fn resize_heap(&mut self, bottom: VirtPageNumber, old_end: VirtPageNumber, end: VirtPageNumber) -> Result<(), MemoryStructureError> {
+        if end <= old_end {
+            return self.unmap_range(end..old_end);
+        }
+        if !self.is_free(&(old_end..end)) {
+            return Err(MemoryStructureError::OverlappedMemoryArea);
+        }
+        let heap_permissions = MemoryAreaPermissions::R | MemoryAreaPermissions::W | MemoryAreaPermissions::U;
+        let top = self.areas.iter_mut().find(|a| a.vpn_range.start >= bottom && a.vpn_range.end == old_end
+            && matches!(a.map_type, MemoryAreaType::Lazy) && a.map_permissions == heap_permissions);
+        match top {
+            Some(area) => area.extend_to(&mut self.page_table, end),
+            None => self.push(MemoryArea::new(old_end.start_addr(), end.start_addr(), MemoryAreaType::Lazy, heap_permissions)?, None)
+        }
+    }
+
+    /// Give the pages of `range` new permissions, every one of them has to be in some area
+    pub fn protect_range(&mut self, range: Range<VirtPageNumber>, permissions: MemoryAreaPermissions) -> Result<(), MemoryStructureError> {
+        if !self.covers(&range) {
+            return Err(MemoryStructureError::InvalidMemoryArea(range));
+        }
+        // writes to a shared mapping reach the file, which has to be open for writing
+        if permissions.contains(MemoryAreaPermissions::W) && self.areas.iter()
+            .any(|a| a.overlaps(&range) && a.file.as_ref().is_some_and(|file| file.shared && !file.writable)) {
+            return Err(MemoryStructureError::AccessDenied);
+        }
+        self.split_at_bounds(&range);
+        for area in self.areas.iter_mut().filter(|a| a.overlaps(&range)) {
+            area.protect(&mut self.page_table, permissions | MemoryAreaPermissions::U)?;
+        }
+        Ok(())
+    }
+
+    /// Write back what the shared file mappings in `range` hold, every page of `range` has
+    /// to be in some area
+    pub fn sync_files(&self, range: Range<VirtPageNumber>) -> Result<(), MemoryStructureError> {
+        if !self.covers(&range) {
+            return Err(MemoryStructureError::InvalidMemoryArea(range));
+        }
+        for area in self.areas.iter().filter(|a| a.overlaps(&range)) {
+            area.sync(&range)?;
+        }
+        Ok(())
+    }
+
     pub fn translate(&self, va: VirtAddr) -> Result<PhysPageNumber, MemoryStructureError> {
         let vpn = va.vpn();
         Ok(self.page_table.translate(vpn)?.ppn())
@@ -243,7 +671,9 @@ impl MemorySet {
 
 
 
-pub fn new_elf_memory_set(process_index: usize, elf_raw: &[u8]) -> Result<(MemorySet, usize, usize), MemoryStructureError> {
+/// Build the address space of a program, returns it with the initial stack pointer, the
+/// bottom of the heap and the entry point
+pub fn new_elf_memory_set(process_index: usize, elf_raw: &[u8]) -> Result<(MemorySet, usize, usize, usize), MemoryStructureError> {
     log::debug!("[MM] Initializing ELF memory set for process {}", process_index);
     let mut set = MemorySet::new()?;
 
@@ -271,13 +701,23 @@ pub fn new_elf_memory_set(process_index: usize, elf_raw: &[u8]) -> Result<(Memor
             }
         }
     }
-    let user_stack_bottom = (max_end_va.vpn() + 1).start_addr();
-    let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+    // the heap grows from right above the segments, the stack is out of its way at the top
+    let heap_bottom = max_end_va.next_vpn().start_addr();
+    log::debug!("[MM] User heap bottom: {heap_bottom}");
+    let user_stack_top = VirtAddr(USER_STACK_TOP);
+    let user_stack_bottom = user_stack_top - USER_STACK_SIZE;
     log::debug!("[MM] User stack segment: [{user_stack_bottom}, {user_stack_top})");
     let stack_area = MemoryArea::new(user_stack_bottom, user_stack_top,
-        MemoryAreaType::Framed, 
+        MemoryAreaType::Lazy,
         MemoryAreaPermissions::R | MemoryAreaPermissions::W | MemoryAreaPermissions::U)?;
     set.push(stack_area, None)?;
+    // room for the stack to grow into, plus the guard page below it
+    let guard_bottom = user_stack_top - USER_STACK_MAX_SIZE - PAGE_SIZE_BYTES;
+    log::debug!("[MM] User stack guard: [{guard_bottom}, {user_stack_bottom})");
+    let guard_area = MemoryArea::new(guard_bottom, user_stack_bottom,
+        MemoryAreaType::Guard,
+        MemoryAreaPermissions::U)?;
+    set.push(guard_area, None)?;
     log::debug!("[MM] User trap context segment: [{:#x}, {:#x})", TRAP_CONTEXT, TRAMPOLINE);
     let trap_context_area = MemoryArea::new(TRAP_CONTEXT.into(), TRAMPOLINE.into(),
         MemoryAreaType::Framed,
@@ -285,5 +725,5 @@ pub fn new_elf_memory_set(process_index: usize, elf_raw: &[u8]) -> Result<(Memor
 
     set.push(trap_context_area, None)?;
     log::debug!("[MM] User program entry point: {:#x}", elf.ehdr.e_entry as usize);
-    Ok((set, user_stack_top.into(), elf.ehdr.e_entry as usize))
+    Ok((set, user_stack_top.into(), heap_bottom.into(), elf.ehdr.e_entry as usize))
 }
\ No newline at end of file
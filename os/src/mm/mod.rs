@@ -1,7 +1,7 @@
 pub(crate) mod heap_allocator;
 pub(crate) mod address;
 pub(crate) mod page_table;
-mod frame_allocator;
+pub(crate) mod frame_allocator;
 pub(crate) mod memory_structure;
 pub(crate) mod kernel;
 
@@ -1,10 +1,11 @@
 use core::cmp::{max, min};
 
-use alloc::vec::Vec;
+use alloc::{string::String, vec::Vec};
 use riscv::register::satp::Satp;
 use thiserror::Error;
 
-use crate::mm::{address::{PhysPageNumber, VirtAddr, VirtPageNumber, PAGE_SIZE_BYTES}, frame_allocator::{Frame, FrameAllocator, FRAME_ALLOCATOR}};
+use crate::mm::{address::{PhysPageNumber, VirtAddr, VirtPageNumber, MMAP_TOP, PAGE_SIZE_BYTES, PAGE_SIZE_WIDTH}, frame_allocator::{Frame, FrameAllocator, FRAME_ALLOCATOR}, memory_structure::MemoryAreaPermissions};
+use crate::task::TASK_MANAGER;
 #[derive(Debug, Error)]
 pub enum PageTableError {
     #[error("Frame unavailable")]
@@ -14,7 +15,11 @@ pub enum PageTableError {
     #[error("No mapping exists for VPN {0}")]
     NoMapExists(VirtPageNumber),
     #[error("Address overflow occurred during translation")]
-    AddressOverflow
+    AddressOverflow,
+    #[error("No user access to VPN {0}")]
+    AccessDenied(VirtPageNumber),
+    #[error("Address {0:#x} is outside of the user address space")]
+    NotUserAddress(usize)
 }
 bitflags! {
     #[derive(Clone, Copy)]
@@ -125,6 +130,13 @@ impl PageTable {
         Ok(())
     }
 
+    /// Replace the flags of the existing mapping of `vpn`
+    pub fn set_flags(&mut self, vpn: VirtPageNumber, flags: PTEFlags) -> Result<(), PageTableError> {
+        let entry = self.find_pte(vpn).filter(|entry| entry.is_valid()).ok_or(PageTableError::NoMapExists(vpn))?;
+        *entry = PageTableEntry::new(entry.ppn(), flags | PTEFlags::V);
+        Ok(())
+    }
+
     pub fn translate(&self, vpn: VirtPageNumber) -> Result<PageTableEntry, PageTableError> {
         let entry = self.find_pte(vpn).ok_or(PageTableError::NoMapExists(vpn))?;
         if !entry.is_valid() {
@@ -133,6 +145,28 @@ impl PageTable {
         Ok(entry.clone())
     }
 
+    /// Translate a page for the kernel to access on behalf of the task, which must be allowed
+    /// the same access itself. A page of the current task is mapped first if its area maps
+    /// pages lazily, like a fault from the task itself would
+    fn translate_user(&self, vpn: VirtPageNumber, write: bool) -> Result<PageTableEntry, PageTableError> {
+        let access = if write { MemoryAreaPermissions::W } else { MemoryAreaPermissions::R };
+        let required = PTEFlags::from_bits((access | MemoryAreaPermissions::U).bits()).unwrap();
+        let entry = self.translate(vpn);
+        if matches!(&entry, Ok(entry) if entry.flags().contains(required)) {
+            return entry;
+        }
+        if self.token() == TASK_MANAGER.get_current_satp_token() {
+            TASK_MANAGER.handle_current_page_fault(vpn.start_addr(), access)
+                .map_err(|_| PageTableError::NoMapExists(vpn))?;
+        }
+        // checked again, a fault only maps what the area allows
+        let entry = self.translate(vpn)?;
+        if !entry.flags().contains(required) {
+            return Err(PageTableError::AccessDenied(vpn));
+        }
+        Ok(entry)
+    }
+
     pub fn root_ppn(&self) -> usize {
         self.root_ppn.into()
     }
@@ -144,14 +178,27 @@ impl PageTable {
             frames: Vec::new()
         }
     }
-    // TODO: 大小溢出
+    /// Check that `[ptr, ptr + len)` lies in the user half of the address space, before any of
+    /// it is translated. Every pointer a task hands to the kernel goes through this
+    fn check_user_range(ptr: usize, len: usize) -> Result<(), PageTableError> {
+        match ptr.checked_add(len) {
+            Some(end) if end <= MMAP_TOP => Ok(()),
+            _ => Err(PageTableError::NotUserAddress(ptr))
+        }
+    }
+
     pub fn translate_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Result<Vec<&'static [u8]>, PageTableError>{
+        Self::translate_user_buffer(token, ptr, len, false)
+    }
+
+    fn translate_user_buffer(token: usize, ptr: *const u8, len: usize, write: bool) -> Result<Vec<&'static [u8]>, PageTableError>{
         let page_table = PageTable::from_token(token);
         let start_addr = ptr as usize;
-        let start_vpn = VirtAddr(start_addr).vpn();
-        if (start_addr + len) == 0 {
-            return Err(PageTableError::AddressOverflow);
+        Self::check_user_range(start_addr, len)?;
+        if len == 0 {
+            return Ok(Vec::new());
         }
+        let start_vpn = VirtAddr(start_addr).vpn();
         let end_vpn = VirtAddr(start_addr + len - 1).next_vpn();
         let pages: usize = (end_vpn - start_vpn).into();
         let mut buffer_ref_array = Vec::<&'static [u8]>::new();
@@ -160,7 +207,7 @@ impl PageTable {
             let vpn_start_addr = Into::<usize>::into(current_vpn.start_addr());
             let start = max(ptr as usize, vpn_start_addr) - vpn_start_addr;
             let end = min(Into::<usize>::into(current_vpn.end_addr()), ptr as usize + len) - vpn_start_addr;
-            let ppn = page_table.translate(current_vpn)?.ppn();
+            let ppn = page_table.translate_user(current_vpn, write)?.ppn();
             buffer_ref_array.push(&ppn.get_array::<u8>()[start..end]);
             current_vpn = current_vpn + 1;
         }
@@ -168,6 +215,61 @@ impl PageTable {
         Ok(buffer_ref_array)
     }
 
+    /// Same as `translate_byte_buffer`, but the buffer can be written
+    pub fn translate_byte_buffer_mut(token: usize, ptr: *mut u8, len: usize) -> Result<Vec<&'static mut [u8]>, PageTableError>{
+        Ok(Self::translate_user_buffer(token, ptr, len, true)?.into_iter()
+            .map(|buffer| unsafe { core::slice::from_raw_parts_mut(buffer.as_ptr() as *mut u8, buffer.len()) })
+            .collect())
+    }
+
+    /// Copy `data` to `ptr` in the address space identified by `token`, which may cross pages
+    pub fn copy_to_user(token: usize, ptr: *mut u8, data: &[u8]) -> Result<(), PageTableError> {
+        let mut copied = 0;
+        for buffer in Self::translate_byte_buffer_mut(token, ptr, data.len())? {
+            buffer.copy_from_slice(&data[copied..copied + buffer.len()]);
+            copied += buffer.len();
+        }
+        Ok(())
+    }
+
+    /// Get a mutable reference to a `T` in the address space identified by `token`,
+    /// the object must not cross a page boundary
+    pub fn translate_mut_ref<T>(token: usize, ptr: *mut T) -> Result<&'static mut T, PageTableError> {
+        let page_table = PageTable::from_token(token);
+        let va = ptr as usize;
+        Self::check_user_range(va, core::mem::size_of::<T>())?;
+        let vpn = VirtAddr(va).vpn();
+        if (va + core::mem::size_of::<T>() - 1) >> PAGE_SIZE_WIDTH != va >> PAGE_SIZE_WIDTH {
+            return Err(PageTableError::AddressOverflow);
+        }
+        let offset = va - Into::<usize>::into(vpn.start_addr());
+        let pa: usize = page_table.translate_user(vpn, true)?.ppn().start_addr().into();
+        Ok(unsafe { &mut *((pa + offset) as *mut T) })
+    }
+
+    /// Read a nul-terminated string from the address space identified by `token`
+    pub fn translate_str(token: usize, ptr: *const u8) -> Result<String, PageTableError> {
+        let page_table = PageTable::from_token(token);
+        let mut bytes = Vec::new();
+        let mut va = ptr as usize;
+        loop {
+            Self::check_user_range(va, 1)?;
+            let vpn = VirtAddr(va).vpn();
+            let offset = va - Into::<usize>::into(vpn.start_addr());
+            let page = page_table.translate_user(vpn, false)?.ppn().get_array::<u8>();
+            match page[offset..].iter().position(|c| *c == b'\0') {
+                Some(len) => {
+                    bytes.extend_from_slice(&page[offset..offset + len]);
+                    return Ok(String::from_utf8_lossy(&bytes).into_owned());
+                },
+                None => {
+                    bytes.extend_from_slice(&page[offset..]);
+                    va = vpn.end_addr().into();
+                }
+            }
+        }
+    }
+
     pub fn token(&self) -> usize {
         8usize << 60 | self.root_ppn.0
     }
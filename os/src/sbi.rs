@@ -1,5 +1,6 @@
 use core::arch::asm;
 
+const SBI_EXT_LEGACY_CONSOLE_GETCHAR: usize = 0x02;
 const SBI_EXT_SRST: usize = 0x53525354;
 const SBI_EXT_DBCN: usize = 0x4442434E;
 const SBI_EXT_TIME: usize = 0x54494D45;
@@ -9,7 +10,9 @@ const SBI_SRST_RESET_REASON_NONE: usize = 0x0;
 const SBI_SRST_RESET_REASON_SYSFAIL: usize = 0x1;
 const SBI_SRST_RESET_TYPE_SHUTDOWN: usize = 0x0;
 const SBI_DBCN_CONSOLE_WRITE: usize = 0x0;
+const SBI_DBCN_CONSOLE_READ: usize = 0x1;
 const SBI_TIME_SET_TIMER: usize = 0x0;
+const SBI_ERR_NOT_SUPPORTED: isize = -2;
 #[inline(always)]
 /// general sbi call
 fn sbi_call(ext_id: usize, func_id: usize, arg0: usize, arg1: usize, arg2: usize) -> isize {
@@ -27,6 +30,24 @@ fn sbi_call(ext_id: usize, func_id: usize, arg0: usize, arg1: usize, arg2: usize
     ret
 }
 
+#[inline(always)]
+/// sbi call returning both `error` (a0) and `value` (a1)
+fn sbi_call_with_value(ext_id: usize, func_id: usize, arg0: usize, arg1: usize, arg2: usize) -> (isize, usize) {
+    let mut error;
+    let mut value;
+    unsafe {
+        asm!(
+        "ecall",
+        inlateout("x10") arg0 => error,
+        inlateout("x11") arg1 => value,
+        in("x12") arg2,
+        in("x16") func_id,
+        in("x17") ext_id,
+        );
+    }
+    (error, value)
+}
+
 /// use sbi call to set timer
 pub fn set_timer(timer: usize) {
     sbi_call(SBI_EXT_TIME, SBI_TIME_SET_TIMER, timer, 0, 0);
@@ -56,6 +77,32 @@ pub fn putstr_debug(content: &str) {
              0);
 
 }
+/// Read the pending console input into `buf` without blocking, returns the number of bytes read.
+/// `buf` must be identically mapped since SBI takes its physical address
+pub fn getstr(buf: &mut [u8]) -> usize {
+    let ptr = buf.as_mut_ptr() as usize;
+    let (error, value) = sbi_call_with_value(SBI_EXT_DBCN, SBI_DBCN_CONSOLE_READ,
+                                             buf.len(),
+                                             ptr & u32::MAX as usize,
+                                             ptr >> 32);
+    if error == SBI_ERR_NOT_SUPPORTED {
+        // fall back to the legacy extension, one byte at a time
+        return match getchar() {
+            Some(c) if !buf.is_empty() => {
+                buf[0] = c;
+                1
+            },
+            _ => 0
+        };
+    }
+    if error < 0 { 0 } else { value }
+}
+
+/// Read a byte from the console without blocking, `None` if no input is available
+pub fn getchar() -> Option<u8> {
+    let c = sbi_call(SBI_EXT_LEGACY_CONSOLE_GETCHAR, 0, 0, 0, 0);
+    if c < 0 { None } else { Some(c as u8) }
+}
 pub fn shutdown(is_failure: bool) -> !{
     sbi_call(SBI_EXT_SRST, SBI_EXT_SRST_RESET, SBI_SRST_RESET_TYPE_SHUTDOWN, if is_failure { SBI_SRST_RESET_REASON_SYSFAIL } else { SBI_SRST_RESET_REASON_NONE }, 0);
     unreachable!();
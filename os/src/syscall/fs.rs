@@ -1,33 +1,323 @@
-use alloc::vec::Vec;
+use alloc::sync::Arc;
+use alloc::vec;
+use easy_fs::FsError;
+use log::debug;
 
-use crate::{mm::page_table::PageTable, task::TASK_MANAGER};
+use crate::{fs::{file::{FileError, OpenFlags, SeekFrom, Stat}, inode::open_file, page_cache, pipe::Pipe, vfs::{self, Dentry, InodeKind}}, io::block_cache, mm::page_table::PageTable, syscall::ERESTARTSYS, task::TASK_MANAGER};
+use crate::syscall::errno::{EAGAIN, EBADF, EBUSY, EEXIST, EFAULT, EFBIG, EINVAL, EIO, EISDIR, EMFILE, ENAMETOOLONG, ENOENT, ENOMEM, ENOSPC, ENOTDIR, ENOTEMPTY, EPIPE, ERANGE, ESPIPE};
 
-const STDOUT_FD: usize = 1;
+/// `dirfd` meaning the current working directory
+const AT_FDCWD: isize = -100;
+/// `unlinkat` flag to remove a directory instead of a file
+const AT_REMOVEDIR: u32 = 0x200;
+/// Upper bound of what a single `getdents64` fills, the records are built in the kernel first
+const MAX_GETDENTS_LEN: usize = 4096;
 
+fn errno_of(e: FileError) -> isize {
+    match e {
+        FileError::WouldBlock => EAGAIN,
+        FileError::BadAccess => EBADF,
+        FileError::InvalidArgument => EINVAL,
+        FileError::IllegalSeek => ESPIPE,
+        FileError::BrokenPipe => EPIPE,
+        FileError::Busy => EBUSY,
+        FileError::OutOfMemory => ENOMEM,
+        FileError::Fs(FsError::Io(_) | FsError::BadMagic | FsError::Corrupted) => EIO,
+        FileError::Fs(FsError::NoSpace) => ENOSPC,
+        FileError::Fs(FsError::FileTooLarge) => EFBIG,
+        FileError::Fs(FsError::NotFound) => ENOENT,
+        FileError::Fs(FsError::AlreadyExists) => EEXIST,
+        FileError::Fs(FsError::NotADirectory) => ENOTDIR,
+        FileError::Fs(FsError::IsADirectory) => EISDIR,
+        FileError::Fs(FsError::DirectoryNotEmpty) => ENOTEMPTY,
+        FileError::Fs(FsError::NameTooLong) => ENAMETOOLONG
+    }
+}
+
+/// The directory `path` starts from if it's relative, absolute paths ignore `dirfd`
+/// even if it's invalid
+fn base_of(dirfd: isize, path: &str) -> Result<Arc<Dentry>, isize> {
+    if dirfd == AT_FDCWD || path.starts_with('/') {
+        return Ok(TASK_MANAGER.get_current_cwd());
+    }
+    let dentry = usize::try_from(dirfd).ok()
+        .and_then(|dirfd| TASK_MANAGER.get_current_file(dirfd))
+        .and_then(|file| file.dentry())
+        .ok_or(EBADF)?;
+    if dentry.is_dir() { Ok(dentry) } else { Err(ENOTDIR) }
+}
+
+pub fn sys_openat(dirfd: isize, path: *const u8, flags: u32, _mode: u32) -> isize {
+    let token = TASK_MANAGER.get_current_satp_token();
+    let Ok(path) = PageTable::translate_str(token, path) else {
+        return -EFAULT;
+    };
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    let base = match base_of(dirfd, &path) {
+        Ok(base) => base,
+        Err(errno) => return -errno
+    };
+    match open_file(&base, &path, flags) {
+        Ok(file) => TASK_MANAGER.alloc_current_fd(file).map_or(-EMFILE, |fd| fd as isize),
+        Err(e) => {
+            debug!("[FS] Failed to open {}: {}", path, e);
+            -errno_of(e)
+        }
+    }
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    match TASK_MANAGER.close_current_fd(fd) {
+        Some(_) => 0,
+        None => -EBADF
+    }
+}
+
+/// Create a pipe, its read end and write end are stored in `fds`
+pub fn sys_pipe2(fds: *mut i32, flags: u32) -> isize {
+    if flags != 0 {
+        return -EINVAL;
+    }
+    let (read_end, write_end) = Pipe::new();
+    let Some(read_fd) = TASK_MANAGER.alloc_current_fd(read_end) else {
+        return -EMFILE;
+    };
+    let Some(write_fd) = TASK_MANAGER.alloc_current_fd(write_end) else {
+        TASK_MANAGER.close_current_fd(read_fd);
+        return -EMFILE;
+    };
+    let value = [read_fd as i32, write_fd as i32];
+    let bytes = unsafe { core::slice::from_raw_parts(value.as_ptr() as *const u8, size_of_val(&value)) };
+    if PageTable::copy_to_user(TASK_MANAGER.get_current_satp_token(), fds as *mut u8, bytes).is_err() {
+        TASK_MANAGER.close_current_fd(read_fd);
+        TASK_MANAGER.close_current_fd(write_fd);
+        return -EFAULT;
+    }
+    0
+}
+
+/// Duplicate `fd` to the lowest free descriptor
+pub fn sys_dup(fd: usize) -> isize {
+    let Some(file) = TASK_MANAGER.get_current_file(fd) else {
+        return -EBADF;
+    };
+    TASK_MANAGER.alloc_current_fd(file).map_or(-EMFILE, |fd| fd as isize)
+}
+
+/// Duplicate `old_fd` to `new_fd`, closing `new_fd` first if it's open
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    if old_fd == new_fd || flags != 0 {
+        return -EINVAL;
+    }
+    let Some(file) = TASK_MANAGER.get_current_file(old_fd) else {
+        return -EBADF;
+    };
+    if TASK_MANAGER.set_current_fd(new_fd, file) { new_fd as isize } else { -EBADF }
+}
+
+/// Reading stdin returns at most one line, the caller is suspended until a whole line
+/// (or end of file) is available
+pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize{
+    let Some(file) = TASK_MANAGER.get_current_file(fd) else {
+        return -EBADF;
+    };
+    if !file.readable() {
+        return -EBADF;
+    }
+    if len == 0 {
+        return 0;
+    }
+    let buffers = match PageTable::translate_byte_buffer_mut(TASK_MANAGER.get_current_satp_token(), buf, len){
+        Ok(buffers) => buffers,
+        Err(_) => return -EFAULT,
+    };
+    let mut read = 0;
+    for buffer in buffers {
+        match file.read(buffer) {
+            Ok(n) => {
+                read += n;
+                if n < buffer.len() {
+                    break;
+                }
+            },
+            Err(FileError::WouldBlock) if read == 0 => return ERESTARTSYS,
+            Err(e) if read == 0 => return -errno_of(e),
+            Err(_) => break
+        }
+    }
+    read as isize
+}
 
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize{
-    match fd{
-        STDOUT_FD => {
-            let buffers = match PageTable::translate_byte_buffer(TASK_MANAGER.get_current_satp_token(), buf, len){
-                Ok(buffers) => buffers,
-                Err(_) => return -1,
-            };
-            let buffers = buffers.iter().map(|slice| core::str::from_utf8(slice))
-                .collect::<Result<Vec<&str>, _>>();
-            match buffers {
-                Ok(s) => {
-                    let len = s.iter().map(|s| s.len()).sum::<usize>();
-                    for str in s {
-                        print!("{}", str);
-                    }
-                    len as isize
-                },
-                Err(_) => -1,
-            }
+    let Some(file) = TASK_MANAGER.get_current_file(fd) else {
+        return -EBADF;
+    };
+    if !file.writable() {
+        return -EBADF;
+    }
+    if len == 0 {
+        return 0;
+    }
+    let buffers = match PageTable::translate_byte_buffer(TASK_MANAGER.get_current_satp_token(), buf, len){
+        Ok(buffers) => buffers,
+        Err(_) => return -EFAULT,
+    };
+    let mut written = 0;
+    for buffer in buffers {
+        match file.write(buffer) {
+            Ok(n) => {
+                written += n;
+                if n < buffer.len() {
+                    break;
+                }
+            },
+            Err(FileError::WouldBlock) if written == 0 => return ERESTARTSYS,
+            Err(e) if written == 0 => return -errno_of(e),
+            Err(_) => break
+        }
+    }
+    written as isize
+}
+
+pub fn sys_ftruncate(fd: usize, len: usize) -> isize {
+    let Some(file) = TASK_MANAGER.get_current_file(fd) else {
+        return -EBADF;
+    };
+    let Some(dentry) = file.dentry() else {
+        return -EINVAL;
+    };
+    if !file.writable() || dentry.is_dir() {
+        return -EINVAL;
+    }
+    match page_cache::truncate(&dentry, len) {
+        Ok(()) => 0,
+        Err(e) => -errno_of(e)
+    }
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    let Some(file) = TASK_MANAGER.get_current_file(fd) else {
+        return -EBADF;
+    };
+    let Some(pos) = SeekFrom::from_whence(whence, offset) else {
+        return -EINVAL;
+    };
+    match file.seek(pos) {
+        Ok(offset) => offset as isize,
+        Err(e) => -errno_of(e)
+    }
+}
+
+pub fn sys_fstat(fd: usize, stat: *mut Stat) -> isize {
+    let Some(file) = TASK_MANAGER.get_current_file(fd) else {
+        return -EBADF;
+    };
+    let value = match file.stat() {
+        Ok(value) => value,
+        Err(e) => return -errno_of(e)
+    };
+    let bytes = unsafe { core::slice::from_raw_parts(&value as *const Stat as *const u8, size_of::<Stat>()) };
+    match PageTable::copy_to_user(TASK_MANAGER.get_current_satp_token(), stat as *mut u8, bytes) {
+        Ok(()) => 0,
+        Err(_) => -EFAULT
+    }
+}
+
+pub fn sys_chdir(path: *const u8) -> isize {
+    let Ok(path) = PageTable::translate_str(TASK_MANAGER.get_current_satp_token(), path) else {
+        return -EFAULT;
+    };
+    match vfs::lookup(&TASK_MANAGER.get_current_cwd(), &path) {
+        Ok(dentry) if dentry.is_dir() => {
+            TASK_MANAGER.set_current_cwd(dentry);
+            0
         },
-        _ => {
-            -1
+        Ok(_) => -ENOTDIR,
+        Err(e) => -errno_of(e)
+    }
+}
+
+/// Returns the length of the path including the terminating nul, or `-ERANGE` if `size`
+/// is too small
+pub fn sys_getcwd(buf: *mut u8, size: usize) -> isize {
+    let mut path = TASK_MANAGER.get_current_cwd().path().into_bytes();
+    path.push(0);
+    if path.len() > size {
+        return -ERANGE;
+    }
+    match PageTable::copy_to_user(TASK_MANAGER.get_current_satp_token(), buf, &path) {
+        Ok(()) => path.len() as isize,
+        Err(_) => -EFAULT
+    }
+}
+
+pub fn sys_mkdirat(dirfd: isize, path: *const u8, _mode: u32) -> isize {
+    let Ok(path) = PageTable::translate_str(TASK_MANAGER.get_current_satp_token(), path) else {
+        return -EFAULT;
+    };
+    let base = match base_of(dirfd, &path) {
+        Ok(base) => base,
+        Err(errno) => return -errno
+    };
+    match vfs::lookup_parent(&base, &path).and_then(|(dir, name)| dir.inode().create(name, InodeKind::Directory)) {
+        Ok(_) => 0,
+        Err(e) => {
+            debug!("[FS] Failed to create directory {}: {}", path, e);
+            -errno_of(e)
+        }
+    }
+}
+
+pub fn sys_unlinkat(dirfd: isize, path: *const u8, flags: u32) -> isize {
+    let Ok(path) = PageTable::translate_str(TASK_MANAGER.get_current_satp_token(), path) else {
+        return -EFAULT;
+    };
+    let base = match base_of(dirfd, &path) {
+        Ok(base) => base,
+        Err(errno) => return -errno
+    };
+    let result = vfs::lookup_parent(&base, &path).and_then(|(dir, name)| {
+        let target = dir.child(name)?;
+        if target.is_mount_root() {
+            return Err(FileError::Busy);
+        }
+        match (target.is_dir(), flags & AT_REMOVEDIR != 0) {
+            (true, false) => Err(FsError::IsADirectory.into()),
+            (false, true) => Err(FsError::NotADirectory.into()),
+            _ => dir.inode().unlink(name)
         }
-        
+    });
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            debug!("[FS] Failed to remove {}: {}", path, e);
+            -errno_of(e)
+        }
+    }
+}
+
+/// Returns the number of bytes filled, 0 at the end of the directory
+pub fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let Some(file) = TASK_MANAGER.get_current_file(fd) else {
+        return -EBADF;
+    };
+    let mut records = vec![0u8; len.min(MAX_GETDENTS_LEN)];
+    let filled = match file.getdents(&mut records) {
+        Ok(filled) => filled,
+        Err(e) => return -errno_of(e)
+    };
+    match PageTable::copy_to_user(TASK_MANAGER.get_current_satp_token(), buf, &records[..filled]) {
+        Ok(()) => filled as isize,
+        Err(_) => -EFAULT
+    }
+}
+
+/// Write every cached block back to its device
+pub fn sys_sync() -> isize {
+    match block_cache::sync() {
+        Ok(()) => 0,
+        Err(_) => -EIO
     }
-}
\ No newline at end of file
+}
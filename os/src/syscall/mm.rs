@@ -0,0 +1,173 @@
+use core::ops::Range;
+
+use crate::fs::{file::FileError, page_cache::PageCache, vfs::InodeKind};
+use crate::mm::address::{VirtAddr, VirtPageNumber, MMAP_TOP, PAGE_SIZE_BYTES};
+use crate::mm::memory_structure::{FileMapping, MemoryArea, MemoryAreaPermissions, MemoryAreaType, MemoryStructureError};
+use crate::mm::page_table::PageTableError;
+use crate::syscall::errno::{EACCES, EBADF, EINVAL, EIO, ENODEV, ENOMEM};
+use crate::task::TASK_MANAGER;
+
+bitflags! {
+    #[derive(Clone, Copy)]
+    struct MmapProt: u32 {
+        const READ = 0x1;
+        const WRITE = 0x2;
+        const EXEC = 0x4;
+    }
+
+    #[derive(Clone, Copy)]
+    struct MmapFlags: u32 {
+        const SHARED = 0x01;
+        const PRIVATE = 0x02;
+        const FIXED = 0x10;
+        const ANONYMOUS = 0x20;
+    }
+
+    #[derive(Clone, Copy)]
+    struct MsyncFlags: u32 {
+        const ASYNC = 0x1;
+        const INVALIDATE = 0x2;
+        const SYNC = 0x4;
+    }
+}
+
+/// Pages of `[addr, addr + len)`, which has to start on a page and lie below `MMAP_TOP`
+fn page_range(addr: usize, len: usize) -> Option<Range<VirtPageNumber>> {
+    let end = addr.checked_add(len).filter(|&end| end <= MMAP_TOP)?;
+    if !addr.is_multiple_of(PAGE_SIZE_BYTES) || len == 0 {
+        return None;
+    }
+    Some(VirtAddr(addr).vpn()..VirtAddr(end).next_vpn())
+}
+
+fn permissions_of(prot: MmapProt) -> MemoryAreaPermissions {
+    let mut permissions = MemoryAreaPermissions::U;
+    if prot.contains(MmapProt::READ) {
+        permissions |= MemoryAreaPermissions::R;
+    }
+    // pages can't be writable without being readable
+    if prot.contains(MmapProt::WRITE) {
+        permissions |= MemoryAreaPermissions::R | MemoryAreaPermissions::W;
+    }
+    if prot.contains(MmapProt::EXEC) {
+        permissions |= MemoryAreaPermissions::X;
+    }
+    permissions
+}
+
+fn errno_of(e: MemoryStructureError) -> isize {
+    match e {
+        MemoryStructureError::OutOfMemory
+        | MemoryStructureError::InvalidMemoryArea(_)
+        | MemoryStructureError::PageTableEroor(PageTableError::FrameUnavailable)
+        | MemoryStructureError::File(FileError::OutOfMemory) => ENOMEM,
+        MemoryStructureError::AccessDenied => EACCES,
+        MemoryStructureError::File(_) => EIO,
+        _ => EINVAL
+    }
+}
+
+/// Move the program break to `brk`, returns the new break, or the current one if it can't
+/// be moved there. `brk(0)` asks where the break is
+pub fn sys_brk(brk: usize) -> isize {
+    TASK_MANAGER.set_current_program_brk(brk) as isize
+}
+
+/// Map `len` bytes of the file `fd` from `offset`, or zeroed memory if anonymous. Without
+/// `MAP_FIXED`, `addr` is only a hint and the mapping goes wherever there is room
+pub fn sys_mmap(addr: usize, len: usize, prot: u32, flags: u32, fd: usize, offset: usize) -> isize {
+    let (Some(prot), Some(flags)) = (MmapProt::from_bits(prot), MmapFlags::from_bits(flags)) else {
+        return -EINVAL;
+    };
+    let shared = flags.contains(MmapFlags::SHARED);
+    if shared == flags.contains(MmapFlags::PRIVATE) || len == 0 || !offset.is_multiple_of(PAGE_SIZE_BYTES) {
+        return -EINVAL;
+    }
+    let permissions = permissions_of(prot);
+    let mapping = if flags.contains(MmapFlags::ANONYMOUS) {
+        // private anonymous pages are copied on fork, shared ones stay in a cache of their own
+        shared.then(|| FileMapping { cache: PageCache::anonymous(), first_page: 0, shared, writable: true })
+    } else {
+        let Some(file) = TASK_MANAGER.get_current_file(fd) else {
+            return -EBADF;
+        };
+        // pipes, devices and directories have no pages to map
+        let Some(dentry) = file.dentry().filter(|dentry| dentry.inode().kind() == InodeKind::Regular) else {
+            return -ENODEV;
+        };
+        if !file.readable() || (shared && prot.contains(MmapProt::WRITE) && !file.writable()) {
+            return -EACCES;
+        }
+        Some(FileMapping {
+            cache: PageCache::of(&dentry),
+            first_page: offset / PAGE_SIZE_BYTES,
+            shared,
+            writable: file.writable()
+        })
+    };
+    let pages = len.div_ceil(PAGE_SIZE_BYTES);
+    let result: Result<VirtAddr, isize> = TASK_MANAGER.with_current_memory_set(|set| {
+        let range = if flags.contains(MmapFlags::FIXED) {
+            let range = page_range(addr, len).filter(|range| range.start > VirtPageNumber(0)).ok_or(EINVAL)?;
+            // whatever was there is replaced
+            set.unmap_range(range.clone()).map_err(errno_of)?;
+            range
+        } else {
+            set.find_free_range(VirtAddr(addr).vpn(), pages).ok_or(ENOMEM)?
+        };
+        let start = range.start.start_addr();
+        let area = match mapping {
+            Some(mapping) => MemoryArea::new_file_backed(range, permissions, mapping),
+            None => MemoryArea::new(start, range.end.start_addr(), MemoryAreaType::Lazy, permissions).map_err(errno_of)?
+        };
+        set.push(area, None).map_err(errno_of)?;
+        Ok(start)
+    });
+    match result {
+        Ok(va) => Into::<usize>::into(va) as isize,
+        Err(errno) => -errno
+    }
+}
+
+/// Remove whatever is mapped in `[addr, addr + len)`, splitting the areas partly in it
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    let Some(range) = page_range(addr, len) else {
+        return -EINVAL;
+    };
+    match TASK_MANAGER.with_current_memory_set(|set| set.unmap_range(range)) {
+        Ok(()) => 0,
+        Err(e) => -errno_of(e)
+    }
+}
+
+/// Change the access allowed to the pages of `[addr, addr + len)`, which all have to be mapped
+pub fn sys_mprotect(addr: usize, len: usize, prot: u32) -> isize {
+    let Some(prot) = MmapProt::from_bits(prot) else {
+        return -EINVAL;
+    };
+    let Some(range) = page_range(addr, len) else {
+        return -EINVAL;
+    };
+    match TASK_MANAGER.with_current_memory_set(|set| set.protect_range(range, permissions_of(prot))) {
+        Ok(()) => 0,
+        Err(e) => -errno_of(e)
+    }
+}
+
+/// Write what the shared mappings in `[addr, addr + len)` hold back to their files, whatever
+/// the flags ask for. Reaching the disk is up to `sync`
+pub fn sys_msync(addr: usize, len: usize, flags: u32) -> isize {
+    let Some(flags) = MsyncFlags::from_bits(flags) else {
+        return -EINVAL;
+    };
+    if flags.contains(MsyncFlags::ASYNC | MsyncFlags::SYNC) {
+        return -EINVAL;
+    }
+    let Some(range) = page_range(addr, len) else {
+        return -EINVAL;
+    };
+    match TASK_MANAGER.with_current_memory_set(|set| set.sync_files(range)) {
+        Ok(()) => 0,
+        Err(e) => -errno_of(e)
+    }
+}
@@ -1,14 +1,44 @@
+mod errno;
 mod fs;
+mod mm;
 mod process;
 
+/// Returned by a syscall that can't complete yet, the caller is suspended and
+/// the syscall is issued again once it gets scheduled
+pub const ERESTARTSYS: isize = -512;
 
-pub fn syscall(id: usize, args: [usize;3]) -> isize{
+
+pub fn syscall(id: usize, args: [usize;6]) -> isize{
     if let Some(syscall_type) = SyscallType::from_number(id){
         match syscall_type{
+            SyscallType::SysGetCwd => fs::sys_getcwd(args[0] as *mut u8, args[1]),
+            SyscallType::SysDup => fs::sys_dup(args[0]),
+            SyscallType::SysDup3 => fs::sys_dup3(args[0], args[1], args[2] as u32),
+            SyscallType::SysMkdirAt => fs::sys_mkdirat(args[0] as isize, args[1] as *const u8, args[2] as u32),
+            SyscallType::SysUnlinkAt => fs::sys_unlinkat(args[0] as isize, args[1] as *const u8, args[2] as u32),
+            SyscallType::SysFtruncate => fs::sys_ftruncate(args[0], args[1]),
+            SyscallType::SysChdir => fs::sys_chdir(args[0] as *const u8),
+            SyscallType::SysOpenAt => fs::sys_openat(args[0] as isize, args[1] as *const u8, args[2] as u32, args[3] as u32),
+            SyscallType::SysClose => fs::sys_close(args[0]),
+            SyscallType::SysPipe2 => fs::sys_pipe2(args[0] as *mut i32, args[1] as u32),
+            SyscallType::SysGetDents64 => fs::sys_getdents64(args[0], args[1] as *mut u8, args[2]),
+            SyscallType::SysLseek => fs::sys_lseek(args[0], args[1] as isize, args[2]),
+            SyscallType::SysRead => fs::sys_read(args[0], args[1] as *mut u8, args[2]),
             SyscallType::SysWrite => fs::sys_write(args[0], args[1] as *const u8, args[2]),
+            SyscallType::SysFstat => fs::sys_fstat(args[0], args[1] as *mut _),
+            SyscallType::SysSync => fs::sys_sync(),
             SyscallType::SysExit => process::sys_exit(args[0] as i32),
             SyscallType::SysYield => process::sys_yield(),
-            SyscallType::SysGetTime => process::sys_get_time()
+            SyscallType::SysReboot => process::sys_reboot(args[0], args[1], args[2]),
+            SyscallType::SysGetTime => process::sys_get_time(),
+            SyscallType::SysBrk => mm::sys_brk(args[0]),
+            SyscallType::SysMunmap => mm::sys_munmap(args[0], args[1]),
+            SyscallType::SysFork => process::sys_fork(),
+            SyscallType::SysExec => process::sys_exec(args[0] as *const u8),
+            SyscallType::SysMmap => mm::sys_mmap(args[0], args[1], args[2] as u32, args[3] as u32, args[4], args[5]),
+            SyscallType::SysMprotect => mm::sys_mprotect(args[0], args[1], args[2] as u32),
+            SyscallType::SysMsync => mm::sys_msync(args[0], args[1], args[2] as u32),
+            SyscallType::SysWaitPid => process::sys_waitpid(args[0] as isize, args[1] as *mut i32, args[2])
         }
     }else{
         -1
@@ -17,19 +47,67 @@ pub fn syscall(id: usize, args: [usize;3]) -> isize{
 
 #[repr(usize)]
 pub enum SyscallType{
+    SysGetCwd = 17,
+    SysDup = 23,
+    SysDup3 = 24,
+    SysMkdirAt = 34,
+    SysUnlinkAt = 35,
+    SysFtruncate = 46,
+    SysChdir = 49,
+    SysOpenAt = 56,
+    SysClose = 57,
+    SysPipe2 = 59,
+    SysGetDents64 = 61,
+    SysLseek = 62,
+    SysRead = 63,
     SysWrite = 64,
+    SysFstat = 80,
+    SysSync = 81,
     SysExit = 93,
     SysYield = 124,
-    SysGetTime = 169
+    SysReboot = 142,
+    SysGetTime = 169,
+    SysBrk = 214,
+    SysMunmap = 215,
+    SysFork = 220,
+    SysExec = 221,
+    SysMmap = 222,
+    SysMprotect = 226,
+    SysMsync = 227,
+    SysWaitPid = 260
 }
 
 impl SyscallType{
     pub fn from_number(id: usize) -> Option<Self>{
         match id{
+            17 => Some(Self::SysGetCwd),
+            23 => Some(Self::SysDup),
+            24 => Some(Self::SysDup3),
+            34 => Some(Self::SysMkdirAt),
+            35 => Some(Self::SysUnlinkAt),
+            46 => Some(Self::SysFtruncate),
+            49 => Some(Self::SysChdir),
+            56 => Some(Self::SysOpenAt),
+            57 => Some(Self::SysClose),
+            59 => Some(Self::SysPipe2),
+            61 => Some(Self::SysGetDents64),
+            62 => Some(Self::SysLseek),
+            63 => Some(Self::SysRead),
             64 => Some(Self::SysWrite),
+            80 => Some(Self::SysFstat),
+            81 => Some(Self::SysSync),
             93 => Some(Self::SysExit),
             124 => Some(Self::SysYield),
+            142 => Some(Self::SysReboot),
             169 => Some(Self::SysGetTime),
+            214 => Some(Self::SysBrk),
+            215 => Some(Self::SysMunmap),
+            220 => Some(Self::SysFork),
+            221 => Some(Self::SysExec),
+            222 => Some(Self::SysMmap),
+            226 => Some(Self::SysMprotect),
+            227 => Some(Self::SysMsync),
+            260 => Some(Self::SysWaitPid),
             _ => None
         }
     }
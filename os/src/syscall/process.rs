@@ -1,13 +1,16 @@
 use log::info;
 
 // use crate::batch::{APP_MANAGER, self};
-use crate::{task::TASK_MANAGER, timer::get_time_us};
+use crate::{fs, io::block_cache, mm::page_table::PageTable, sbi::shutdown, syscall::ERESTARTSYS, task::{WaitResult, TASK_MANAGER}, timer::get_time_us};
+
+const WNOHANG: usize = 1;
+
 pub fn sys_exit(xstate: i32) -> !{
     let app_id = {
         TASK_MANAGER.get_current_app_id()
     };
     info!("[Kernel] Application {} exited with code {}", app_id, xstate);
-    TASK_MANAGER.set_exit();
+    TASK_MANAGER.exit_current(xstate);
     TASK_MANAGER.run_next_app();
 }
 
@@ -16,6 +19,75 @@ pub fn sys_yield() -> !{
     TASK_MANAGER.run_next_app();    
 }
 
+pub fn sys_fork() -> isize{
+    match TASK_MANAGER.fork_current() {
+        Ok(child_id) => child_id as isize,
+        Err(e) => {
+            log::error!("[Kernel] Failed to fork application {}: {}", TASK_MANAGER.get_current_app_id(), e);
+            -1
+        }
+    }
+}
+
+pub fn sys_exec(path: *const u8) -> isize{
+    let path = match PageTable::translate_str(TASK_MANAGER.get_current_satp_token(), path) {
+        Ok(path) => path,
+        Err(_) => return -1
+    };
+    let Some(elf_data) = fs::read_app(&TASK_MANAGER.get_current_cwd(), &path) else {
+        return -1;
+    };
+    let name = path.rsplit('/').next().unwrap_or(&path);
+    match TASK_MANAGER.exec_current(name, &elf_data) {
+        Ok(()) => 0,
+        Err(e) => {
+            log::error!("[Kernel] Failed to execute {} in application {}: {}", path, TASK_MANAGER.get_current_app_id(), e);
+            -1
+        }
+    }
+}
+
+/// Wait for a child to exit, blocks unless `WNOHANG` is given.
+/// Returns the pid of the reaped child, 0 if `WNOHANG` is given and no child has exited, or -1 if there is no such child
+/// or `exit_code_ptr` can't be written
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: usize) -> isize{
+    // checked before reaping, a child whose status can't be stored stays a zombie
+    let exit_code_ref = if exit_code_ptr.is_null() {
+        None
+    } else {
+        match PageTable::translate_mut_ref(TASK_MANAGER.get_current_satp_token(), exit_code_ptr) {
+            Ok(ptr) => Some(ptr),
+            Err(_) => return -1
+        }
+    };
+    match TASK_MANAGER.wait_child(pid) {
+        WaitResult::Reaped(child_id, exit_code) => {
+            if let Some(ptr) = exit_code_ref {
+                *ptr = exit_code;
+            }
+            child_id as isize
+        },
+        WaitResult::Running if options & WNOHANG != 0 => 0,
+        WaitResult::Running => ERESTARTSYS,
+        WaitResult::NoChild => -1
+    }
+}
+
+const LINUX_REBOOT_MAGIC1: usize = 0xfee1dead;
+const LINUX_REBOOT_MAGIC2: usize = 672274793;
+const LINUX_REBOOT_CMD_POWER_OFF: usize = 0x4321fedc;
+
+/// The only way to power off the machine, only the power off command is supported
+pub fn sys_reboot(magic1: usize, magic2: usize, cmd: usize) -> isize{
+    if magic1 != LINUX_REBOOT_MAGIC1 || magic2 != LINUX_REBOOT_MAGIC2 || cmd != LINUX_REBOOT_CMD_POWER_OFF {
+        return -1;
+    }
+    info!("[Kernel] Power off requested by application {}", TASK_MANAGER.get_current_app_id());
+    // whatever is still cached would be lost, failures are logged by the cache
+    let _ = block_cache::sync();
+    shutdown(false);
+}
+
 pub fn sys_get_time() -> isize{
     get_time_us() as isize
 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+use core::ops::Range;
+
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+use crate::helper::cell::SingleThreadSafeCell;
+use crate::mm::address::{self, VirtAddr};
+use crate::mm::kernel::KERNEL_MEMORY_MANAGER;
+use crate::mm::memory_structure::MemoryStructureError;
+
+/// Hands out the smallest never used id unless a released one can be reused
+pub struct RecycleAllocator {
+    current: usize,
+    recycled: Vec<usize>
+}
+
+impl RecycleAllocator {
+    pub fn new() -> Self {
+        RecycleAllocator { current: 0, recycled: Vec::new() }
+    }
+
+    pub fn alloc(&mut self) -> usize {
+        if let Some(id) = self.recycled.pop() {
+            id
+        } else {
+            self.current += 1;
+            self.current - 1
+        }
+    }
+
+    pub fn dealloc(&mut self, id: usize) {
+        if id >= self.current || self.recycled.contains(&id) {
+            panic!("[TaskManager] Invalid deallocation of id: {}", id);
+        }
+        self.recycled.push(id);
+    }
+}
+
+lazy_static!{
+    static ref PID_ALLOCATOR: SingleThreadSafeCell<RecycleAllocator> = SingleThreadSafeCell::new(RecycleAllocator::new());
+    static ref KERNEL_STACK_ALLOCATOR: SingleThreadSafeCell<RecycleAllocator> = SingleThreadSafeCell::new(RecycleAllocator::new());
+}
+
+/// A process id, given back to the allocator once the task is reaped
+pub struct PidHandle(usize);
+
+impl PidHandle {
+    pub fn new() -> Self {
+        PidHandle(PID_ALLOCATOR.exclusive_access().alloc())
+    }
+
+    pub fn value(&self) -> usize {
+        self.0
+    }
+}
+
+impl Drop for PidHandle {
+    fn drop(&mut self) {
+        PID_ALLOCATOR.exclusive_access().dealloc(self.0);
+    }
+}
+
+/// A kernel stack mapped in the kernel address space, its slot is allocated
+/// independently from the pid of the owner
+pub struct KernelStack {
+    slot: usize
+}
+
+impl KernelStack {
+    pub fn new() -> Result<Self, MemoryStructureError> {
+        let slot = KERNEL_STACK_ALLOCATOR.exclusive_access().alloc();
+        if let Err(e) = KERNEL_MEMORY_MANAGER.exclusive_access().map_stack_for_process_syscall(slot) {
+            KERNEL_STACK_ALLOCATOR.exclusive_access().dealloc(slot);
+            return Err(e);
+        }
+        Ok(KernelStack { slot })
+    }
+
+    pub fn range(&self) -> Range<VirtAddr> {
+        address::kernel_stack_position(self.slot)
+    }
+
+    pub fn top(&self) -> usize {
+        self.range().end.into()
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        if let Err(e) = KERNEL_MEMORY_MANAGER.exclusive_access().unmap_stack_for_process_syscall(self.slot) {
+            // the slot is leaked rather than handed out while still (partially) mapped
+            log::error!("[TaskManager] Failed to release kernel stack slot {}: {}", self.slot, e);
+            return;
+        }
+        KERNEL_STACK_ALLOCATOR.exclusive_access().dealloc(self.slot);
+    }
+}
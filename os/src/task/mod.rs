@@ -1,38 +1,44 @@
 use core::cell::SyncUnsafeCell;
-use alloc::{collections::btree_map::BTreeMap, vec::Vec};
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
 use lazy_static::lazy_static;
-use crate::{helper::cell::SingleThreadSafeCell, loader, sbi::shutdown, task::{switch::__switch, tcb::{TaskControlBlock, TaskStatus}}, trap::{context::TrapContext, trap_return}};
+use crate::{fs::{self, file::File, mount, vfs::Dentry}, helper::cell::SingleThreadSafeCell, mm::{address::VirtAddr, memory_structure::{MemoryAreaPermissions, MemoryStructureError, MemorySet}}, task::{switch::__switch, tcb::{TaskControlBlock, TaskError, TaskStatus}}, trap::{context::TrapContext, trap_return}};
 mod context;
+mod id;
 mod switch;
 pub(crate) mod tcb;
 
 const MAX_TASK_NUM: usize = 64;
+/// Orphans are handed over to this task as long as it is alive
+pub const INIT_PID: usize = 0;
+
+pub enum WaitResult {
+    /// A child has been reaped, with its id and exit code
+    Reaped(usize, i32),
+    /// Matched children exist, but none of them has exited yet
+    Running,
+    NoChild
+}
 pub struct TaskManager{
     inner: SingleThreadSafeCell<_TaskManager>
 }
 
+/// The first user process, every other process descends from it
+const INIT_PROC: &str = "initproc";
+
 lazy_static!{
-    pub static ref TASK_MANAGER: TaskManager = TaskManager::new(loader::get_app_num());
+    pub static ref TASK_MANAGER: TaskManager = TaskManager::new();
 }
 impl TaskManager{
-    pub fn new(app_num: usize) -> Self{
-        let manager = {
-            let mut control_blocks = BTreeMap::new();
-            (0..app_num).for_each(|i| {
-                let app_data = loader::get_app_data(i);
-                match TaskControlBlock::new(i, app_data) {
-                    Ok(tcb) => {
-                        control_blocks.insert(i, tcb);
-                    },
-                    Err(e) => {
-                        log::error!("[TaskManager] Failed to create TaskControlBlock for app {}: {}, skipping", i, e);
-                    }
-                }
-            });
-            _TaskManager { num: app_num, current_id: 0, control_blocks }
-        };
+    pub fn new() -> Self{
+        let app_data = fs::read_app(&mount::root(), INIT_PROC)
+            .unwrap_or_else(|| panic!("[TaskManager] {} not found", INIT_PROC));
+        let init = TaskControlBlock::new(INIT_PROC, &app_data)
+            .unwrap_or_else(|e| panic!("[TaskManager] Failed to create {}: {}", INIT_PROC, e));
+        assert_eq!(init.pid(), INIT_PID);
+        let mut control_blocks = BTreeMap::new();
+        control_blocks.insert(init.pid(), init);
         TaskManager{
-            inner: SingleThreadSafeCell::new(manager)
+            inner: SingleThreadSafeCell::new(_TaskManager { current_id: INIT_PID, control_blocks })
         }
     }
 
@@ -50,19 +56,20 @@ impl TaskManager{
             drop(manager);
             unsafe{ __switch(ptr); }
         }else{
-            log::info!("[TaskManager] All applications finished running, shutdown");
-            shutdown(false);
+            // the init process never exits, so there is always something to run
+            panic!("[TaskManager] No task to run");
         }
     }
+    /// Round-robin: look for a ready task after the current one first, then wrap around,
+    /// so that a cpu-bound task can't keep the tasks with bigger ids away from the cpu
     fn find_next_app(&self) -> Option<usize>{
         let manager;
         manager = self.inner.exclusive_access();
-        for (i, tcb) in manager.control_blocks.iter(){
-            if tcb.status() == TaskStatus::Ready {
-                return Some(*i);
-            }
-        }
-        None
+        let current_id = manager.current_id;
+        manager.control_blocks.range(current_id + 1..)
+            .chain(manager.control_blocks.range(..=current_id))
+            .find(|(_, tcb)| tcb.status() == TaskStatus::Ready)
+            .map(|(i, _)| *i)
     }
 
     pub fn get_current_app_id(&self) -> usize{
@@ -88,17 +95,133 @@ impl TaskManager{
         let current_id = manager.current_id;
         manager.control_blocks.get(&current_id).unwrap().task_cx.sp
     }
+    /// Fork the current task, returns the id of the child
+    pub fn fork_current(&self) -> Result<usize, TaskError> {
+        let mut manager;
+        manager = self.inner.exclusive_access();
+        let current_id = manager.current_id;
+        let mut child = manager.control_blocks.get_mut(&current_id).unwrap().fork()?;
+        let child_id = child.pid();
+        child.set_parent(Some(current_id));
+        manager.control_blocks.insert(child_id, child);
+        manager.control_blocks.get_mut(&current_id).unwrap().children_mut().push(child_id);
+        Ok(child_id)
+    }
+    /// Replace the image of the current task with `elf_data` of the program `name`
+    pub fn exec_current(&self, name: &str, elf_data: &[u8]) -> Result<(), TaskError> {
+        let mut manager;
+        manager = self.inner.exclusive_access();
+        let current_id = manager.current_id;
+        manager.control_blocks.get_mut(&current_id).unwrap().exec(name, elf_data)
+    }
+    /// Pids of every task, zombies included
+    pub fn pids(&self) -> Vec<usize> {
+        let manager = self.inner.exclusive_access();
+        manager.control_blocks.keys().copied().collect()
+    }
+    /// Look into the task `pid`, `f` must not call back into the task manager
+    pub fn inspect_task<R>(&self, pid: usize, f: impl FnOnce(&TaskControlBlock) -> R) -> Option<R> {
+        let manager = self.inner.exclusive_access();
+        manager.control_blocks.get(&pid).map(f)
+    }
+    /// Work on the address space of the current task, `f` must not call back into the task manager
+    pub fn with_current_memory_set<R>(&self, f: impl FnOnce(&mut MemorySet) -> R) -> R {
+        let mut manager;
+        manager = self.inner.exclusive_access();
+        let current_id = manager.current_id;
+        f(manager.control_blocks.get_mut(&current_id).unwrap().memory_set_mut())
+    }
+    /// Map the page at `va` that the current task failed to access
+    pub fn handle_current_page_fault(&self, va: VirtAddr, access: MemoryAreaPermissions) -> Result<(), MemoryStructureError> {
+        self.with_current_memory_set(|set| set.handle_page_fault(va, access))
+    }
+    /// Move the program break of the current task, returns where it ends up
+    pub fn set_current_program_brk(&self, brk: usize) -> usize {
+        let mut manager;
+        manager = self.inner.exclusive_access();
+        let current_id = manager.current_id;
+        manager.control_blocks.get_mut(&current_id).unwrap().set_program_brk(brk)
+    }
+    pub fn get_current_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        let manager = self.inner.exclusive_access();
+        let current_id = manager.current_id;
+        manager.control_blocks.get(&current_id).unwrap().file(fd)
+    }
+    pub fn alloc_current_fd(&self, file: Arc<dyn File>) -> Option<usize> {
+        let mut manager;
+        manager = self.inner.exclusive_access();
+        let current_id = manager.current_id;
+        manager.control_blocks.get_mut(&current_id).unwrap().alloc_fd(file)
+    }
+    pub fn set_current_fd(&self, fd: usize, file: Arc<dyn File>) -> bool {
+        let mut manager;
+        manager = self.inner.exclusive_access();
+        let current_id = manager.current_id;
+        manager.control_blocks.get_mut(&current_id).unwrap().set_fd(fd, file)
+    }
+    pub fn close_current_fd(&self, fd: usize) -> Option<Arc<dyn File>> {
+        let mut manager;
+        manager = self.inner.exclusive_access();
+        let current_id = manager.current_id;
+        manager.control_blocks.get_mut(&current_id).unwrap().close_fd(fd)
+    }
+    pub fn get_current_cwd(&self) -> Arc<Dentry> {
+        let manager = self.inner.exclusive_access();
+        let current_id = manager.current_id;
+        manager.control_blocks.get(&current_id).unwrap().cwd().clone()
+    }
+    pub fn set_current_cwd(&self, cwd: Arc<Dentry>) {
+        let mut manager;
+        manager = self.inner.exclusive_access();
+        let current_id = manager.current_id;
+        manager.control_blocks.get_mut(&current_id).unwrap().set_cwd(cwd);
+    }
     pub fn suspend(&self) {
         let mut manager;
         manager = self.inner.exclusive_access();
         let current_id = manager.current_id;
         manager.control_blocks.get_mut(&current_id).unwrap().suspend();
     }
-    pub fn set_exit(&self) {
+    /// Turn the current task into a zombie and hand its children over to the init process
+    pub fn exit_current(&self, exit_code: i32) {
         let mut manager;
         manager = self.inner.exclusive_access();
         let current_id = manager.current_id;
-        manager.control_blocks.get_mut(&current_id).unwrap().set_exit();
+        if current_id == INIT_PID {
+            panic!("[TaskManager] {} exited with code {}", INIT_PROC, exit_code);
+        }
+        let tcb = manager.control_blocks.get_mut(&current_id).unwrap();
+        tcb.set_zombie(exit_code);
+        let children = core::mem::take(tcb.children_mut());
+        for child in children.iter() {
+            manager.control_blocks.get_mut(child).unwrap().set_parent(Some(INIT_PID));
+        }
+        manager.control_blocks.get_mut(&INIT_PID).unwrap().children_mut().extend(children);
+    }
+
+    /// Reap an exited child of the current task, `pid` being -1 means any child
+    pub fn wait_child(&self, pid: isize) -> WaitResult {
+        let mut manager;
+        manager = self.inner.exclusive_access();
+        let current_id = manager.current_id;
+        let tcb = manager.control_blocks.get(&current_id).unwrap();
+        let mut candidates = tcb.children().iter()
+            .filter(|child| pid == -1 || **child as isize == pid)
+            .peekable();
+        if candidates.peek().is_none() {
+            return WaitResult::NoChild;
+        }
+        let Some(child_id) = candidates.copied()
+            .find(|child| manager.control_blocks.get(child).unwrap().status() == TaskStatus::Zombie) else {
+            return WaitResult::Running;
+        };
+        manager.control_blocks.get_mut(&current_id).unwrap().children_mut().retain(|child| *child != child_id);
+        let child = manager.control_blocks.remove(&child_id).unwrap();
+        drop(manager);
+        let exit_code = child.exit_code();
+        // dropping the control block releases the address space, kernel stack and pid
+        drop(child);
+        WaitResult::Reaped(child_id, exit_code)
     }
 
     pub fn suspend_and_run_next(&self) -> ! {
@@ -113,7 +236,6 @@ impl TaskManager{
 }
 
 struct _TaskManager{
-    num: usize,
     current_id: usize,
     control_blocks: BTreeMap<usize, TaskControlBlock>
 }
@@ -1,45 +1,138 @@
-use crate::mm::address::{self, PhysPageNumber, TRAP_CONTEXT};
+use crate::mm::address::{PhysPageNumber, VirtAddr, MMAP_TOP, TRAP_CONTEXT};
 use crate::mm::memory_structure::{self, MemoryArea, MemoryAreaPermissions, MemoryAreaType, MemorySet, MemoryStructureError};
 use crate::mm::kernel::KERNEL_MEMORY_MANAGER;
 use crate::trap::context::TrapContext;
 use crate::trap::{trap_handler, trap_return};
 use super::context::TaskContext;
+use super::id::{KernelStack, PidHandle};
+use crate::fs::file::File;
+use crate::fs::mount;
+use crate::fs::stdio::{Stdin, Stdout};
+use crate::fs::vfs::Dentry;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use log::debug;
 use thiserror::Error;
 
+/// Upper bound of the descriptors a process can hold
+const MAX_FD_NUM: usize = 256;
+
 #[derive(Debug, Error)]
 pub enum TaskError {
     #[error("Failed to initialize memory for task: {0}")]
     FailedToInitializeMemory(#[from] MemoryStructureError)
 }
 pub struct TaskControlBlock{
+    pid: PidHandle,
+    /// Name of the program the task runs
+    name: String,
+    kernel_stack: KernelStack,
     task_status: TaskStatus,
     pub task_cx: TaskContext,
     memory_set: MemorySet,
     task_cx_ppn: PhysPageNumber,
-    base_size: usize
+    base_size: usize,
+    heap_bottom: usize,
+    /// End of the heap, as set by `brk`
+    program_brk: usize,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    exit_code: i32,
+    fd_table: Vec<Option<Arc<dyn File>>>,
+    cwd: Arc<Dentry>
 }
 impl TaskControlBlock{
-    pub fn new(app_id: usize, elf_data: &[u8]) -> Result<Self, TaskError> {
-        let (memory_set, user_sp, entry) = memory_structure::new_elf_memory_set(app_id, elf_data)?;
+    pub fn new(name: &str, elf_data: &[u8]) -> Result<Self, TaskError> {
+        let pid = PidHandle::new();
+        let (memory_set, user_sp, heap_bottom, entry) = memory_structure::new_elf_memory_set(pid.value(), elf_data)?;
         let task_cx_ppn = memory_set.translate(TRAP_CONTEXT.into())?;
 
-        let kernel_stack_range = address::kernel_stack_position(app_id);
-        KERNEL_MEMORY_MANAGER.exclusive_access().map_stack_for_process_syscall(app_id)?;
+        let kernel_stack = KernelStack::new()?;
         *task_cx_ppn.get_mut::<TrapContext>() = TrapContext::app_init_context(
             entry, 
             user_sp, 
             KERNEL_MEMORY_MANAGER.exclusive_access().token(),
-            kernel_stack_range.end.into(),
-            trap_handler as usize
+            kernel_stack.top(),
+            trap_handler as *const () as usize
         );
         Ok(Self {
+            pid,
+            name: String::from(name),
             task_status: TaskStatus::Ready,
-            task_cx: TaskContext::new(kernel_stack_range.end.into()),
+            task_cx: TaskContext::new(kernel_stack.top()),
+            kernel_stack,
             memory_set,
             base_size: user_sp,
-            task_cx_ppn
+            heap_bottom,
+            program_brk: heap_bottom,
+            task_cx_ppn,
+            parent: None,
+            children: Vec::new(),
+            exit_code: 0,
+            fd_table: vec![Some(Arc::new(Stdin)), Some(Arc::new(Stdout)), Some(Arc::new(Stdout))],
+            cwd: mount::root()
         })
     }
+    /// Duplicate the task, the child shares nothing with its parent but the opened files
+    /// and the working directory, and sees 0 as the return value of the fork syscall.
+    /// Memory is shared copy-on-write, which is why the parent is borrowed mutably.
+    /// The caller is responsible for linking the child to the parent
+    pub fn fork(&mut self) -> Result<Self, TaskError> {
+        let pid = PidHandle::new();
+        let memory_set = MemorySet::from_existed_user(&mut self.memory_set)?;
+        let task_cx_ppn = memory_set.translate(TRAP_CONTEXT.into())?;
+
+        let kernel_stack = KernelStack::new()?;
+        let trap_cx = task_cx_ppn.get_mut::<TrapContext>();
+        trap_cx.kernel_sp = kernel_stack.top();
+        trap_cx.x[10] = 0;
+        Ok(Self {
+            pid,
+            name: self.name.clone(),
+            task_status: TaskStatus::Ready,
+            task_cx: TaskContext::new(kernel_stack.top()),
+            kernel_stack,
+            memory_set,
+            base_size: self.base_size,
+            heap_bottom: self.heap_bottom,
+            program_brk: self.program_brk,
+            task_cx_ppn,
+            parent: None,
+            children: Vec::new(),
+            exit_code: 0,
+            fd_table: self.fd_table.clone(),
+            cwd: self.cwd.clone()
+        })
+    }
+    /// Replace the image of the task with `elf_data` of the program `name`, the kernel stack,
+    /// opened files and working directory are kept
+    pub fn exec(&mut self, name: &str, elf_data: &[u8]) -> Result<(), TaskError> {
+        let (memory_set, user_sp, heap_bottom, entry) = memory_structure::new_elf_memory_set(self.pid.value(), elf_data)?;
+        let task_cx_ppn = memory_set.translate(TRAP_CONTEXT.into())?;
+        *task_cx_ppn.get_mut::<TrapContext>() = TrapContext::app_init_context(
+            entry,
+            user_sp,
+            KERNEL_MEMORY_MANAGER.exclusive_access().token(),
+            self.kernel_stack.top(),
+            trap_handler as *const () as usize
+        );
+        // the old address space is released here
+        self.memory_set = memory_set;
+        self.task_cx_ppn = task_cx_ppn;
+        self.base_size = user_sp;
+        self.heap_bottom = heap_bottom;
+        self.program_brk = heap_bottom;
+        self.name = String::from(name);
+        Ok(())
+    }
+    pub fn pid(&self) -> usize {
+        self.pid.value()
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
     pub fn suspend(&mut self) {
         self.task_status = TaskStatus::Ready;
     }
@@ -47,8 +140,73 @@ impl TaskControlBlock{
         self.task_status = TaskStatus::Running;
         // self.task_cx.switch_to();
     }
-    pub fn set_exit(&mut self) {
-        self.task_status = TaskStatus::Exited;
+    /// The task stays a zombie until its parent (or the kernel if it has none) reaps it
+    pub fn set_zombie(&mut self, exit_code: i32) {
+        self.task_status = TaskStatus::Zombie;
+        self.exit_code = exit_code;
+        // files are closed right away, the parent may take a while to reap
+        self.fd_table.clear();
+    }
+
+    /// Install `file` at the lowest free descriptor
+    pub fn alloc_fd(&mut self, file: Arc<dyn File>) -> Option<usize> {
+        if let Some(fd) = self.fd_table.iter().position(|f| f.is_none()) {
+            self.fd_table[fd] = Some(file);
+            return Some(fd);
+        }
+        if self.fd_table.len() >= MAX_FD_NUM {
+            return None;
+        }
+        self.fd_table.push(Some(file));
+        Some(self.fd_table.len() - 1)
+    }
+
+    /// Install `file` at `fd`, closing what was there. Fails if `fd` is out of range
+    pub fn set_fd(&mut self, fd: usize, file: Arc<dyn File>) -> bool {
+        if fd >= MAX_FD_NUM {
+            return false;
+        }
+        if fd >= self.fd_table.len() {
+            self.fd_table.resize(fd + 1, None);
+        }
+        self.fd_table[fd] = Some(file);
+        true
+    }
+
+    pub fn file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.fd_table.get(fd).cloned().flatten()
+    }
+
+    pub fn close_fd(&mut self, fd: usize) -> Option<Arc<dyn File>> {
+        self.fd_table.get_mut(fd).and_then(|f| f.take())
+    }
+
+    pub fn cwd(&self) -> &Arc<Dentry> {
+        &self.cwd
+    }
+
+    pub fn set_cwd(&mut self, cwd: Arc<Dentry>) {
+        self.cwd = cwd;
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn set_parent(&mut self, parent: Option<usize>) {
+        self.parent = parent;
+    }
+
+    pub fn children(&self) -> &Vec<usize> {
+        &self.children
+    }
+
+    pub fn children_mut(&mut self) -> &mut Vec<usize> {
+        &mut self.children
     }
 
     pub fn status(&self) -> TaskStatus {
@@ -56,6 +214,29 @@ impl TaskControlBlock{
     }
 
 
+    pub fn memory_set(&self) -> &MemorySet {
+        &self.memory_set
+    }
+
+    pub fn memory_set_mut(&mut self) -> &mut MemorySet {
+        &mut self.memory_set
+    }
+
+    /// Move the program break to `brk`, the pages of the heap are mapped or unmapped to match.
+    /// Returns the break, which stays where it was if it can't go to `brk`
+    pub fn set_program_brk(&mut self, brk: usize) -> usize {
+        if brk < self.heap_bottom || brk > MMAP_TOP {
+            return self.program_brk;
+        }
+        let bottom = VirtAddr(self.heap_bottom).vpn();
+        let old_end = VirtAddr(self.program_brk).next_vpn();
+        match self.memory_set.resize_heap(bottom, old_end, VirtAddr(brk).next_vpn()) {
+            Ok(()) => self.program_brk = brk,
+            Err(e) => debug!("[TaskManager] Failed to move the break of task {} to {:#x}: {}", self.pid(), brk, e)
+        }
+        self.program_brk
+    }
+
     pub fn satp_token(&self) -> usize {
         self.memory_set.token()
     }
@@ -69,5 +250,5 @@ impl TaskControlBlock{
 pub enum TaskStatus{
     Ready,
     Running,
-    Exited
+    Zombie
 }
\ No newline at end of file
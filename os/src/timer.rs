@@ -3,13 +3,34 @@ use riscv::register::time;
 use crate::sbi;
 
 const CLOCK_FREQ: usize = 12500000;
-const TICKS_PER_SEC: usize = 100;
 const US_PER_SEC: usize = 1_000_000;
+const MS_PER_SEC: usize = 1_000;
+const DEFAULT_TIME_SLICE_MS: usize = 10;
+
+/// Length of a scheduling quantum, can be overridden at build time through
+/// `CHIBIMOS_TIME_SLICE_MS` (e.g. `make run TIME_SLICE_MS=5`)
+pub const TIME_SLICE_MS: usize = match option_env!("CHIBIMOS_TIME_SLICE_MS") {
+    Some(s) => parse_time_slice(s.as_bytes()),
+    None => DEFAULT_TIME_SLICE_MS
+};
+const TICKS_PER_SLICE: usize = CLOCK_FREQ / MS_PER_SEC * TIME_SLICE_MS;
+
+const fn parse_time_slice(s: &[u8]) -> usize {
+    let mut value = 0;
+    let mut i = 0;
+    while i < s.len() {
+        assert!(s[i].is_ascii_digit(), "CHIBIMOS_TIME_SLICE_MS must be a decimal number");
+        value = value * 10 + (s[i] - b'0') as usize;
+        i += 1;
+    }
+    assert!(value > 0, "CHIBIMOS_TIME_SLICE_MS must be positive");
+    value
+}
 
 pub fn set_next_trigger(){
-    sbi::set_timer(time::read64() as usize + (CLOCK_FREQ/TICKS_PER_SEC));
+    sbi::set_timer(time::read64() as usize + TICKS_PER_SLICE);
 }
 
 pub fn get_time_us() -> usize{
     time::read64() as usize / (CLOCK_FREQ / US_PER_SEC)
-}
\ No newline at end of file
+}
@@ -3,7 +3,7 @@ pub mod context;
 use core::arch::{asm, global_asm};
 use context::TrapContext;
 // use crate::{batch::{self, APP_MANAGER}, syscall::syscall};
-use crate::{mm::address::{TRAMPOLINE, TRAP_CONTEXT}, syscall::syscall, task::{tcb::TaskControlBlock, TASK_MANAGER}, timer};
+use crate::{io::{plic, uart}, mm::{address::{TRAMPOLINE, TRAP_CONTEXT}, memory_structure::{MemoryAreaPermissions, MemoryStructureError}}, syscall::{syscall, ERESTARTSYS}, task::{tcb::TaskControlBlock, TASK_MANAGER}, timer};
 use riscv::{interrupt::{supervisor::Interrupt, Exception}, register::{satp, scause, sie, stval, stvec::{self, Stvec, TrapMode}}};
 
 
@@ -41,31 +41,56 @@ pub fn trap_handler() -> !{
     unsafe { asm!(".align 4") };
     set_kernel_trap();
     let scause = scause::read();
-    let _stval = stval::read();
+    let stval = stval::read();
     let cx = TASK_MANAGER.get_current_trap_context();
-    if scause.is_interrupt(){
-        panic!("[Kernel] Currently Interrupts are not supported");
-    }
-    // Now the scause should be exceptions
     // 注：感觉这种 `try_into` 的方式还挺不错的，下次可以学习下
     match scause.cause().try_into::<riscv::interrupt::supervisor::Interrupt, _>().unwrap(){
         scause::Trap::Exception(Exception::UserEnvCall) => {
             cx.sepc += 4;
-            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
+            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]]);
+            // exec may have replaced the trap context, so look it up again
+            let cx = TASK_MANAGER.get_current_trap_context();
+            if result == ERESTARTSYS {
+                cx.sepc -= 4;
+                TASK_MANAGER.suspend();
+                TASK_MANAGER.run_next_app();
+            }
+            cx.x[10] = result as usize;
+        },
+        // pages of lazy areas are mapped on their first access and the stack grows into its
+        // guard area, the other faults are fatal
+        scause::Trap::Exception(e @ (Exception::LoadPageFault | Exception::StorePageFault | Exception::InstructionPageFault)) => {
+            if let Err(err) = TASK_MANAGER.handle_current_page_fault(stval.into(), access_of(e)) {
+                if matches!(err, MemoryStructureError::StackOverflow) {
+                    log::error!("[Kernel] stack overflow in application {} at {:#x}, killed",
+                        TASK_MANAGER.get_current_app_id(), cx.sepc);
+                } else {
+                    log::error!("[Kernel] {} at {:#x} in application {} at {:#x} ({}), killed",
+                        e.try_get().unwrap(), stval, TASK_MANAGER.get_current_app_id(), cx.sepc, err);
+                }
+                TASK_MANAGER.exit_current(-1);
+                TASK_MANAGER.run_next_app();
+            }
         },
         scause::Trap::Exception(e) => if let Ok(msg) = e.try_get(){
             let app_id = {
                 TASK_MANAGER.get_current_app_id()
             };
             log::error!("[Kernel] {} in application {} at {:#x}, killed", msg, app_id, cx.sepc);
-            TASK_MANAGER.set_exit();
+            TASK_MANAGER.exit_current(-1);
             TASK_MANAGER.run_next_app();
         },
         scause::Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            // Time slice used up, re-arm the timer and give the cpu to the next ready task
             timer::set_next_trigger();
+            // devices whose interrupt can't be delivered are served here instead
+            uart::poll();
             TASK_MANAGER.suspend();
             TASK_MANAGER.run_next_app();
-        }
+        },
+        scause::Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            plic::handle_external_interrupt();
+        },
         other => panic!("[Kernel] Current category of exception hasn't implemented: {:?}", other)
     }
 
@@ -92,6 +117,14 @@ pub fn trap_return() -> !{
             options(noreturn))
     }
 }
+/// The access a page fault was caused by
+fn access_of(fault: Exception) -> MemoryAreaPermissions {
+    match fault {
+        Exception::StorePageFault => MemoryAreaPermissions::W,
+        Exception::InstructionPageFault => MemoryAreaPermissions::X,
+        _ => MemoryAreaPermissions::R
+    }
+}
 trait MsgHelper{
     fn try_get(&self) -> Result<&'static str, ()>;
 }
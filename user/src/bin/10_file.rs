@@ -0,0 +1,74 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::testing::fail;
+use user_lib::{close, fstat, lseek, open, read, write, Stat, EBADF, EFAULT, ENOENT, O_APPEND, O_CREAT, O_RDONLY, O_TRUNC,
+               O_WRONLY, S_IFMT, S_IFREG, SEEK_SET};
+
+const PATH: &str = "file_test";
+const CONTENT: &[u8] = b"Hello, file system!\n";
+const TEST: &str = "file";
+/// Mapped in every task, but only for the kernel
+const TRAP_CONTEXT: usize = usize::MAX - 2 * 4096 + 1;
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let fd = open(PATH, O_CREAT | O_TRUNC | O_WRONLY);
+    if fd < 0 {
+        return fail(TEST, "create");
+    }
+    if write(fd as usize, CONTENT) != CONTENT.len() as isize {
+        return fail(TEST, "write");
+    }
+    close(fd as usize);
+
+    let fd = open(PATH, O_WRONLY | O_APPEND);
+    if fd < 0 || write(fd as usize, CONTENT) != CONTENT.len() as isize {
+        return fail(TEST, "append");
+    }
+    close(fd as usize);
+
+    if open("no_such_file", O_RDONLY) != -ENOENT {
+        return fail(TEST, "open of a missing file");
+    }
+    let fd = open(PATH, O_RDONLY);
+    if fd < 0 {
+        return fail(TEST, "open");
+    }
+    let fd = fd as usize;
+    let mut stat = Stat::default();
+    if fstat(fd, &mut stat) != 0 || stat.mode & S_IFMT != S_IFREG || stat.size != 2 * CONTENT.len() as i64 {
+        return fail(TEST, "fstat");
+    }
+    let mut buf = [0u8; 64];
+    let len = read(fd, &mut buf);
+    if len != 2 * CONTENT.len() as isize || &buf[..CONTENT.len()] != CONTENT || &buf[CONTENT.len()..len as usize] != CONTENT {
+        return fail(TEST, "read");
+    }
+    if read(fd, &mut buf) != 0 {
+        return fail(TEST, "end of file");
+    }
+    if lseek(fd, 7, SEEK_SET) != 7 || read(fd, &mut buf[..4]) != 4 || &buf[..4] != b"file" {
+        return fail(TEST, "lseek");
+    }
+    if write(fd, CONTENT) != -EBADF {
+        return fail(TEST, "write to a read-only file");
+    }
+    // memory the task can't access itself is refused instead of written by the kernel
+    let trap_context = unsafe { core::slice::from_raw_parts_mut(TRAP_CONTEXT as *mut u8, 64) };
+    if lseek(fd, 0, SEEK_SET) != 0 || read(fd, trap_context) != -EFAULT {
+        return fail(TEST, "read into kernel memory");
+    }
+    if fstat(fd, unsafe { &mut *(TRAP_CONTEXT as *mut Stat) }) != -EFAULT {
+        return fail(TEST, "fstat into kernel memory");
+    }
+    close(fd);
+    if read(fd, &mut buf) != -EBADF {
+        return fail(TEST, "read after close");
+    }
+    println!("Test file OK!");
+    0
+}
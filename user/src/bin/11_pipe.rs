@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::testing::fail;
+use user_lib::{close, dup, exit, fork, pipe, read, waitpid, write};
+
+/// More than the kernel pipe buffer holds, so that both ends have to wait for each other
+const TOTAL: usize = 16 * 1024;
+const TEST: &str = "pipe";
+
+fn pattern(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mut fds = [0i32; 2];
+    if pipe(&mut fds) != 0 {
+        return fail(TEST, "pipe");
+    }
+    let (read_end, write_end) = (fds[0] as usize, fds[1] as usize);
+
+    let pid = fork();
+    if pid == 0 {
+        // the write end of the child has to be closed as well, or the read never sees EOF
+        close(write_end);
+        let mut buf = [0u8; 1000];
+        let mut received = 0;
+        loop {
+            let len = read(read_end, &mut buf);
+            if len < 0 {
+                exit(-1);
+            }
+            if len == 0 {
+                break;
+            }
+            if buf[..len as usize].iter().enumerate().any(|(i, &b)| b != pattern(received + i)) {
+                exit(-2);
+            }
+            received += len as usize;
+        }
+        exit(if received == TOTAL { 0 } else { -3 });
+    }
+    close(read_end);
+    // send through a duplicate, the pipe is closed only once every descriptor is
+    let dup_end = dup(write_end);
+    if dup_end < 0 {
+        return fail(TEST, "dup");
+    }
+    close(write_end);
+    let mut buf = [0u8; 1000];
+    let mut sent = 0;
+    while sent < TOTAL {
+        let len = buf.len().min(TOTAL - sent);
+        buf[..len].iter_mut().enumerate().for_each(|(i, b)| *b = pattern(sent + i));
+        let written = write(dup_end as usize, &buf[..len]);
+        if written <= 0 {
+            return fail(TEST, "write");
+        }
+        sent += written as usize;
+    }
+    close(dup_end as usize);
+    let mut exit_code = 0;
+    if waitpid(pid as usize, &mut exit_code) != pid || exit_code != 0 {
+        println!("Test pipe failed: reader exited with {}", exit_code);
+        return -1;
+    }
+
+    // nobody reads any more, writing has to fail instead of blocking forever
+    if pipe(&mut fds) != 0 {
+        return fail(TEST, "second pipe");
+    }
+    close(fds[0] as usize);
+    if write(fds[1] as usize, b"lost") >= 0 {
+        return fail(TEST, "write without reader");
+    }
+    close(fds[1] as usize);
+    println!("Test pipe OK!");
+    0
+}
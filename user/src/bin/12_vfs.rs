@@ -0,0 +1,120 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::testing::fail;
+use user_lib::{chdir, close, dirents, getcwd, getdents, lseek, mkdir, open, read, rmdir, unlink, write, DT_DIR, DT_REG,
+               O_CREAT, O_DIRECTORY, O_RDONLY, O_RDWR, O_WRONLY, SEEK_SET};
+
+const DIR: &str = "/vfs_test";
+const CONTENT: &[u8] = b"nested\n";
+const OTHER: &[u8] = b"created after the unlink\n";
+const TEST: &str = "vfs";
+
+fn cwd_is(expected: &str) -> bool {
+    let mut buf = [0u8; 64];
+    let len = getcwd(&mut buf);
+    len > 0 && &buf[..len as usize - 1] == expected.as_bytes()
+}
+
+/// Kind of `name` in the directory `path`, 0 if it isn't listed
+fn listed(path: &str, name: &str) -> u8 {
+    let fd = open(path, O_RDONLY | O_DIRECTORY);
+    if fd < 0 {
+        return 0;
+    }
+    let mut buf = [0u8; 256];
+    let mut kind = 0;
+    loop {
+        let len = getdents(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        if let Some(entry) = dirents(&buf[..len as usize]).find(|entry| entry.name == name) {
+            kind = entry.kind;
+        }
+    }
+    close(fd as usize);
+    kind
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    // leftovers of an earlier run that didn't finish
+    unlink("/vfs_test/sub/file");
+    unlink("/vfs_test/other");
+    rmdir("/vfs_test/sub");
+    rmdir(DIR);
+
+    if mkdir(DIR) != 0 || mkdir("/vfs_test/sub") != 0 {
+        return fail(TEST, "mkdir");
+    }
+    if mkdir(DIR) == 0 {
+        return fail(TEST, "mkdir of an existing directory");
+    }
+    if chdir("/vfs_test/./sub/../sub") != 0 || !cwd_is("/vfs_test/sub") {
+        return fail(TEST, "chdir");
+    }
+    let fd = open("file", O_CREAT | O_WRONLY);
+    if fd < 0 || write(fd as usize, CONTENT) != CONTENT.len() as isize {
+        return fail(TEST, "create in a subdirectory");
+    }
+    close(fd as usize);
+    if chdir("..") != 0 || !cwd_is(DIR) {
+        return fail(TEST, "chdir to the parent");
+    }
+    let fd = open("sub/file", O_RDONLY);
+    let mut buf = [0u8; 16];
+    if fd < 0 || read(fd as usize, &mut buf) != CONTENT.len() as isize || &buf[..CONTENT.len()] != CONTENT {
+        return fail(TEST, "relative open");
+    }
+    close(fd as usize);
+    if listed(".", "sub") != DT_DIR || listed("sub", "file") != DT_REG || listed("sub", "..") != DT_DIR {
+        return fail(TEST, "getdents");
+    }
+    if chdir("sub/file") == 0 || open("sub/file/x", O_RDONLY) >= 0 {
+        return fail(TEST, "a file used as a directory");
+    }
+    if rmdir("sub") == 0 || unlink("sub") == 0 {
+        return fail(TEST, "removing a non-empty directory");
+    }
+    if unlink("sub/file") != 0 || listed("sub", "file") != 0 || rmdir("sub") != 0 {
+        return fail(TEST, "unlink");
+    }
+
+    // an open file outlives its name, a file created meanwhile doesn't get its blocks
+    let fd = open("gone", O_CREAT | O_RDWR);
+    if fd < 0 || write(fd as usize, CONTENT) != CONTENT.len() as isize || unlink("gone") != 0 {
+        return fail(TEST, "unlink of an open file");
+    }
+    if write(fd as usize, CONTENT) != CONTENT.len() as isize {
+        return fail(TEST, "write after unlink");
+    }
+    let other = open("other", O_CREAT | O_RDWR);
+    if other < 0 || write(other as usize, OTHER) != OTHER.len() as isize {
+        return fail(TEST, "create after unlink");
+    }
+    let mut buf = [0u8; 32];
+    lseek(fd as usize, 0, SEEK_SET);
+    if read(fd as usize, &mut buf) != 2 * CONTENT.len() as isize || &buf[..CONTENT.len()] != CONTENT
+        || &buf[CONTENT.len()..2 * CONTENT.len()] != CONTENT {
+        return fail(TEST, "content of an unlinked file");
+    }
+    lseek(other as usize, 0, SEEK_SET);
+    if read(other as usize, &mut buf) != OTHER.len() as isize || &buf[..OTHER.len()] != OTHER {
+        return fail(TEST, "file created after an unlink");
+    }
+    close(fd as usize);
+    close(other as usize);
+    if listed(".", "gone") != 0 || unlink("other") != 0 {
+        return fail(TEST, "names after unlink");
+    }
+
+    if chdir("/") != 0 || !cwd_is("/") || rmdir(DIR) != 0 {
+        return fail(TEST, "rmdir");
+    }
+    println!("Test vfs OK!");
+    0
+}
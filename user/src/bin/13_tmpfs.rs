@@ -0,0 +1,98 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::testing::fail;
+use user_lib::{close, fstat, ftruncate, lseek, mkdir, open, read, rmdir, unlink, write, Stat, O_CREAT, O_RDWR,
+               O_TRUNC, SEEK_SET};
+
+const PATH: &str = "/tmp/sparse";
+/// Far enough to leave whole pages unwritten
+const END_OFFSET: usize = 5 * 4096 + 100;
+const TEST: &str = "tmpfs";
+
+fn size_of(fd: usize) -> i64 {
+    let mut stat = Stat::default();
+    if fstat(fd, &mut stat) != 0 {
+        return -1;
+    }
+    stat.size
+}
+
+/// Whether `len` bytes from `offset` all read as zeros
+fn zeros(fd: usize, offset: usize, len: usize) -> bool {
+    let mut buf = [0xffu8; 512];
+    lseek(fd, offset as isize, SEEK_SET);
+    let mut left = len;
+    while left > 0 {
+        let chunk = left.min(buf.len());
+        if read(fd, &mut buf[..chunk]) != chunk as isize || buf[..chunk].iter().any(|&b| b != 0) {
+            return false;
+        }
+        left -= chunk;
+    }
+    true
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let fd = open(PATH, O_CREAT | O_TRUNC | O_RDWR);
+    if fd < 0 {
+        return fail(TEST, "create");
+    }
+    let fd = fd as usize;
+    if write(fd, b"hello") != 5 || lseek(fd, END_OFFSET as isize, SEEK_SET) != END_OFFSET as isize
+        || write(fd, b"end") != 3 {
+        return fail(TEST, "write");
+    }
+    if size_of(fd) != END_OFFSET as i64 + 3 {
+        return fail(TEST, "size after a write past the end");
+    }
+    if !zeros(fd, 5, END_OFFSET - 5) {
+        return fail(TEST, "hole not reading as zeros");
+    }
+    let mut buf = [0u8; 8];
+    if read(fd, &mut buf) != 3 || &buf[..3] != b"end" {
+        return fail(TEST, "read after the hole");
+    }
+
+    if ftruncate(fd, 3) != 0 || size_of(fd) != 3 {
+        return fail(TEST, "shrink");
+    }
+    lseek(fd, 0, SEEK_SET);
+    if read(fd, &mut buf) != 3 || &buf[..3] != b"hel" {
+        return fail(TEST, "read after shrinking");
+    }
+    if ftruncate(fd, 8000) != 0 || size_of(fd) != 8000 || !zeros(fd, 3, 8000 - 3) {
+        return fail(TEST, "extend");
+    }
+
+    // the file stays usable through the descriptor until it's closed
+    if unlink(PATH) != 0 || open(PATH, O_RDWR) >= 0 {
+        return fail(TEST, "unlink");
+    }
+    lseek(fd, 0, SEEK_SET);
+    if read(fd, &mut buf[..3]) != 3 || &buf[..3] != b"hel" {
+        return fail(TEST, "read after unlink");
+    }
+    close(fd);
+
+    if mkdir("/tmp/dir") != 0 {
+        return fail(TEST, "mkdir");
+    }
+    let fd = open("/tmp/dir/file", O_CREAT | O_RDWR);
+    if fd < 0 {
+        return fail(TEST, "create in a directory");
+    }
+    close(fd as usize);
+    if rmdir("/tmp/dir") == 0 {
+        return fail(TEST, "rmdir of a non-empty directory");
+    }
+    if unlink("/tmp/dir/file") != 0 || rmdir("/tmp/dir") != 0 {
+        return fail(TEST, "cleanup");
+    }
+    println!("Test tmpfs OK!");
+    0
+}
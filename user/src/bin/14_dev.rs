@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::testing::fail;
+use user_lib::{close, fstat, open, read, write, Stat, O_RDONLY, O_RDWR, O_WRONLY, S_IFCHR, S_IFMT};
+
+const TEST: &str = "dev";
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let null = open("/dev/null", O_RDWR);
+    if null < 0 {
+        return fail(TEST, "open /dev/null");
+    }
+    let mut buf = [0xffu8; 64];
+    if write(null as usize, b"discarded") != 9 || read(null as usize, &mut buf) != 0 {
+        return fail(TEST, "/dev/null");
+    }
+    let mut stat = Stat::default();
+    if fstat(null as usize, &mut stat) != 0 || stat.mode & S_IFMT != S_IFCHR {
+        return fail(TEST, "fstat of a device");
+    }
+    close(null as usize);
+
+    let null = open("/dev/null", O_RDONLY);
+    if null < 0 || write(null as usize, b"x") >= 0 {
+        return fail(TEST, "writing a device opened read-only");
+    }
+    close(null as usize);
+
+    let zero = open("/dev/zero", O_RDONLY);
+    if zero < 0 || read(zero as usize, &mut buf) != buf.len() as isize || buf.iter().any(|&b| b != 0) {
+        return fail(TEST, "/dev/zero");
+    }
+    close(zero as usize);
+
+    let urandom = open("/dev/urandom", O_RDONLY);
+    let mut other = [0u8; 64];
+    if urandom < 0 || read(urandom as usize, &mut buf) != buf.len() as isize
+        || read(urandom as usize, &mut other) != other.len() as isize {
+        return fail(TEST, "read /dev/urandom");
+    }
+    if buf == other || buf.iter().all(|&b| b == 0) {
+        return fail(TEST, "/dev/urandom is not random");
+    }
+    close(urandom as usize);
+
+    let console = open("/dev/console", O_WRONLY);
+    let message = b"Hello from /dev/console\n";
+    if console < 0 || write(console as usize, message) != message.len() as isize {
+        return fail(TEST, "/dev/console");
+    }
+    close(console as usize);
+    println!("Test dev OK!");
+    0
+}
@@ -0,0 +1,100 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::testing::fail;
+use user_lib::{close, dirents, fstat, getdents, mkdir, open, read, rmdir, unlink, write, Stat, O_CREAT, O_DIRECTORY,
+               O_RDONLY, O_RDWR, O_TRUNC};
+
+/// Only there when a FAT32 disk is attached
+const MOUNT_POINT: &str = "/mnt";
+const DIR: &str = "/mnt/A Long Directory Name";
+const FILE: &str = "/mnt/A Long Directory Name/a file with a long name.txt";
+/// Spans several clusters whatever the cluster size mkfs picked
+const FILE_SIZE: usize = 20000;
+const TEST: &str = "fat";
+
+fn byte_at(i: usize) -> u8 {
+    (i * 31 % 251) as u8
+}
+
+/// Whether `dir` lists an entry called `name`
+fn lists(dir: &str, name: &str) -> bool {
+    let fd = open(dir, O_RDONLY | O_DIRECTORY);
+    if fd < 0 {
+        return false;
+    }
+    let mut buf = [0u8; 1024];
+    let mut found = false;
+    loop {
+        let len = getdents(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        found |= dirents(&buf[..len as usize]).any(|entry| entry.name == name);
+    }
+    close(fd as usize);
+    found
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mnt = open(MOUNT_POINT, O_RDONLY | O_DIRECTORY);
+    if mnt < 0 {
+        println!("Test fat skipped, no FAT32 disk on {}", MOUNT_POINT);
+        return 0;
+    }
+    close(mnt as usize);
+
+    if mkdir(DIR) != 0 || !lists(MOUNT_POINT, "A Long Directory Name") {
+        return fail(TEST, "mkdir with a long name");
+    }
+    let fd = open(FILE, O_CREAT | O_TRUNC | O_RDWR);
+    if fd < 0 {
+        return fail(TEST, "create");
+    }
+    let fd = fd as usize;
+    let mut chunk = [0u8; 1000];
+    for offset in (0..FILE_SIZE).step_by(chunk.len()) {
+        chunk.iter_mut().enumerate().for_each(|(i, b)| *b = byte_at(offset + i));
+        if write(fd, &chunk) != chunk.len() as isize {
+            return fail(TEST, "write");
+        }
+    }
+    let mut stat = Stat::default();
+    if fstat(fd, &mut stat) != 0 || stat.size != FILE_SIZE as i64 {
+        return fail(TEST, "size after writing");
+    }
+    close(fd);
+
+    // names are matched ignoring case, like other systems do
+    let fd = open("/mnt/a long directory name/A FILE WITH A LONG NAME.TXT", O_RDONLY);
+    if fd < 0 {
+        return fail(TEST, "open ignoring case");
+    }
+    let fd = fd as usize;
+    for offset in (0..FILE_SIZE).step_by(chunk.len()) {
+        if read(fd, &mut chunk) != chunk.len() as isize
+            || chunk.iter().enumerate().any(|(i, &b)| b != byte_at(offset + i)) {
+            return fail(TEST, "read back");
+        }
+    }
+    close(fd);
+
+    if !lists(DIR, "a file with a long name.txt") {
+        return fail(TEST, "long name not listed");
+    }
+    if rmdir(DIR) == 0 {
+        return fail(TEST, "rmdir of a non-empty directory");
+    }
+    if unlink(FILE) != 0 || lists(DIR, "a file with a long name.txt") {
+        return fail(TEST, "unlink");
+    }
+    if rmdir(DIR) != 0 || lists(MOUNT_POINT, "A Long Directory Name") {
+        return fail(TEST, "rmdir");
+    }
+    println!("Test fat OK!");
+    0
+}
@@ -0,0 +1,136 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::slice;
+use user_lib::testing::fail;
+use user_lib::{close, fork, lseek, mmap, msync, munmap, open, pipe, read, unlink, waitpid, write, EINVAL, ENODEV,
+               MAP_PRIVATE, MAP_SHARED, MS_SYNC, O_CREAT, O_RDWR, O_TRUNC, PROT_READ, PROT_WRITE, SEEK_SET};
+
+const PATH: &str = "/tmp/mapped";
+const PAGE_SIZE: usize = 4096;
+/// Ends inside the third page, so that the mapping has a tail past the end of the file
+const FILE_SIZE: usize = 2 * PAGE_SIZE + 100;
+const MAP_LEN: usize = 3 * PAGE_SIZE;
+const TEST: &str = "mmap";
+
+fn byte_at(i: usize) -> u8 {
+    (i * 7 % 253) as u8
+}
+
+fn map(prot: u32, flags: u32, fd: usize) -> Option<&'static mut [u8]> {
+    let addr = mmap(0, MAP_LEN, prot, flags, fd, 0);
+    if addr <= 0 {
+        return None;
+    }
+    Some(unsafe { slice::from_raw_parts_mut(addr as *mut u8, MAP_LEN) })
+}
+
+fn read_byte(fd: usize, offset: usize) -> Option<u8> {
+    let mut byte = [0u8];
+    lseek(fd, offset as isize, SEEK_SET);
+    (read(fd, &mut byte) == 1).then_some(byte[0])
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let fd = open(PATH, O_CREAT | O_TRUNC | O_RDWR);
+    if fd < 0 {
+        return fail(TEST, "create");
+    }
+    let fd = fd as usize;
+    let data: [u8; FILE_SIZE] = core::array::from_fn(byte_at);
+    if write(fd, &data) != FILE_SIZE as isize {
+        return fail(TEST, "write");
+    }
+
+    let Some(shared) = map(PROT_READ | PROT_WRITE, MAP_SHARED, fd) else {
+        return fail(TEST, "shared mmap");
+    };
+    if shared[..FILE_SIZE] != data[..] || shared[FILE_SIZE..].iter().any(|&b| b != 0) {
+        return fail(TEST, "content of the shared mapping");
+    }
+    // both ways between the mapping and the file
+    shared[10] = 0xaa;
+    if read_byte(fd, 10) != Some(0xaa) {
+        return fail(TEST, "read of a byte written through the mapping");
+    }
+    lseek(fd, PAGE_SIZE as isize, SEEK_SET);
+    if write(fd, &[0xbb]) != 1 || shared[PAGE_SIZE] != 0xbb {
+        return fail(TEST, "mapping after a write to the file");
+    }
+
+    let Some(private) = map(PROT_READ | PROT_WRITE, MAP_PRIVATE, fd) else {
+        return fail(TEST, "private mmap");
+    };
+    if private[10] != 0xaa {
+        return fail(TEST, "content of the private mapping");
+    }
+    private[20] = 0xcc;
+    if shared[20] == 0xcc || read_byte(fd, 20) == Some(0xcc) {
+        return fail(TEST, "private write visible to others");
+    }
+
+    // a child shares the shared mapping and has its own copy of the private one
+    let pid = fork();
+    if pid == 0 {
+        shared[30] = 0xdd;
+        private[10] = 0xee;
+        return 0;
+    }
+    let mut exit_code = 0;
+    if pid < 0 || waitpid(pid as usize, &mut exit_code) != pid || exit_code != 0 {
+        return fail(TEST, "fork");
+    }
+    if shared[30] != 0xdd || private[10] != 0xaa {
+        return fail(TEST, "mappings after fork");
+    }
+
+    // the kernel writes into pages not touched yet
+    if read(fd, &mut private[2 * PAGE_SIZE..2 * PAGE_SIZE + 50]) != 50 {
+        return fail(TEST, "read into a private mapping");
+    }
+    if msync(shared.as_ptr() as usize, MAP_LEN, MS_SYNC) != 0 {
+        return fail(TEST, "msync");
+    }
+    if munmap(shared.as_ptr() as usize, MAP_LEN) != 0 || munmap(private.as_ptr() as usize, MAP_LEN) != 0 {
+        return fail(TEST, "munmap");
+    }
+    if read_byte(fd, 10) != Some(0xaa) || read_byte(fd, 30) != Some(0xdd) || read_byte(fd, 20) != Some(byte_at(20)) {
+        return fail(TEST, "file after munmap");
+    }
+
+    let mut fds = [0i32; 2];
+    if pipe(&mut fds) != 0 || mmap(0, PAGE_SIZE, PROT_READ, MAP_SHARED, fds[0] as usize, 0) != -ENODEV {
+        return fail(TEST, "mmap of a pipe");
+    }
+    close(fds[0] as usize);
+    close(fds[1] as usize);
+    if mmap(0, PAGE_SIZE, PROT_READ, MAP_SHARED | MAP_PRIVATE, fd, 0) != -EINVAL {
+        return fail(TEST, "mmap both shared and private");
+    }
+
+    // a file created after a mapped one is removed has pages of its own
+    let Some(old) = map(PROT_READ, MAP_SHARED, fd) else {
+        return fail(TEST, "mmap before unlink");
+    };
+    close(fd);
+    if unlink(PATH) != 0 {
+        return fail(TEST, "unlink of a mapped file");
+    }
+    let fd = open(PATH, O_CREAT | O_TRUNC | O_RDWR);
+    if fd < 0 || write(fd as usize, &[0x11; 16]) != 16 {
+        return fail(TEST, "create after unlink");
+    }
+    let fd = fd as usize;
+    if read_byte(fd, 10) != Some(0x11) || old[10] != 0xaa {
+        return fail(TEST, "new file sharing the pages of the removed one");
+    }
+    munmap(old.as_ptr() as usize, MAP_LEN);
+    close(fd);
+    unlink(PATH);
+    println!("Test mmap OK!");
+    0
+}
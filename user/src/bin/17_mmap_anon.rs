@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr;
+use user_lib::testing::{fail, killed_on, page};
+use user_lib::{close, fork, mmap, mprotect, munmap, pipe, read, waitpid, write, EINVAL, ENOMEM, MAP_ANONYMOUS,
+               MAP_FIXED, MAP_PRIVATE, MAP_SHARED, PROT_NONE, PROT_READ, PROT_WRITE};
+
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 4;
+const TEST: &str = "mmap_anon";
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let base = mmap(0, PAGES * PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, usize::MAX, 0);
+    if base <= 0 {
+        return fail(TEST, "anonymous mmap");
+    }
+    let base = base as usize;
+    for i in 0..PAGES {
+        unsafe {
+            if ptr::read_volatile(page(base, i)) != 0 {
+                return fail(TEST, "anonymous memory not zeroed");
+            }
+            ptr::write_volatile(page(base, i), i as u8 + 1);
+        }
+    }
+
+    // the middle pages become read-only, for the task and for the kernel writing on its behalf
+    if mprotect(page(base, 1) as usize, 2 * PAGE_SIZE, PROT_READ) != 0 {
+        return fail(TEST, "mprotect");
+    }
+    if unsafe { ptr::read_volatile(page(base, 2)) } != 3 || !killed_on(page(base, 2) as usize, true) {
+        return fail(TEST, "read-only pages");
+    }
+    let mut fds = [0i32; 2];
+    if pipe(&mut fds) != 0 || write(fds[1] as usize, b"x") != 1 {
+        return fail(TEST, "pipe");
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(page(base, 1), 1) };
+    if read(fds[0] as usize, buf) >= 0 {
+        return fail(TEST, "kernel wrote to a read-only page");
+    }
+    close(fds[0] as usize);
+    close(fds[1] as usize);
+
+    // nothing is accessible, but the content is kept
+    if mprotect(page(base, 1) as usize, PAGE_SIZE, PROT_NONE) != 0 || !killed_on(page(base, 1) as usize, false) {
+        return fail(TEST, "PROT_NONE");
+    }
+    if mprotect(page(base, 1) as usize, PAGE_SIZE, PROT_READ | PROT_WRITE) != 0
+        || unsafe { ptr::read_volatile(page(base, 1)) } != 2 {
+        return fail(TEST, "content after PROT_NONE");
+    }
+
+    // a hole in the middle of the area, filled again with MAP_FIXED
+    if munmap(page(base, 2) as usize, PAGE_SIZE) != 0 || !killed_on(page(base, 2) as usize, false) {
+        return fail(TEST, "partial munmap");
+    }
+    if unsafe { ptr::read_volatile(page(base, 1)) } != 2 || unsafe { ptr::read_volatile(page(base, 3)) } != 4 {
+        return fail(TEST, "pages around the hole");
+    }
+    let fixed = mmap(page(base, 2) as usize, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+                     usize::MAX, 0);
+    if fixed != page(base, 2) as isize || unsafe { ptr::read_volatile(page(base, 2)) } != 0 {
+        return fail(TEST, "MAP_FIXED");
+    }
+
+    // shared anonymous pages are the same for a child and its parent
+    let shared = mmap(0, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED | MAP_ANONYMOUS, usize::MAX, 0);
+    if shared <= 0 {
+        return fail(TEST, "shared anonymous mmap");
+    }
+    let shared = page(shared as usize, 0);
+    let pid = fork();
+    if pid == 0 {
+        unsafe { ptr::write_volatile(shared, 0x5a) };
+        user_lib::exit(0);
+    }
+    let mut exit_code = 0;
+    if pid < 0 || waitpid(pid as usize, &mut exit_code) != pid || exit_code != 0
+        || unsafe { ptr::read_volatile(shared) } != 0x5a {
+        return fail(TEST, "write of a child to a shared anonymous mapping");
+    }
+    munmap(shared as usize, PAGE_SIZE);
+
+    if mprotect(base - 16 * PAGE_SIZE, PAGE_SIZE, PROT_READ) != -ENOMEM {
+        return fail(TEST, "mprotect of unmapped memory");
+    }
+    if munmap(base + 1, PAGE_SIZE) != -EINVAL {
+        return fail(TEST, "munmap of an unaligned address");
+    }
+    if munmap(base, PAGES * PAGE_SIZE) != 0 || !killed_on(page(base, 0) as usize, false) {
+        return fail(TEST, "munmap");
+    }
+    println!("Test mmap_anon OK!");
+    0
+}
@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ptr;
+use user_lib::testing::{fail, killed_on};
+use user_lib::{brk, sbrk};
+
+const PAGE_SIZE: usize = 4096;
+/// Several times what the allocator asks for at once, so the heap has to grow again and again
+const ELEMENTS: u32 = 100_000;
+const TEST: &str = "heap";
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let start = sbrk(0);
+    if start <= 0 || brk(0) != start {
+        return fail(TEST, "current break");
+    }
+    if sbrk(2 * PAGE_SIZE as isize) != start || sbrk(0) != start + 2 * PAGE_SIZE as isize {
+        return fail(TEST, "growing the break");
+    }
+    let page = (start as usize).next_multiple_of(PAGE_SIZE) as *mut u8;
+    unsafe { ptr::write_volatile(page, 42) };
+    if unsafe { ptr::read_volatile(page) } != 42 {
+        return fail(TEST, "memory below the break");
+    }
+    if sbrk(-2 * PAGE_SIZE as isize) != start + 2 * PAGE_SIZE as isize || !killed_on(page as usize, false) {
+        return fail(TEST, "shrinking the break");
+    }
+    if brk(1) != start {
+        return fail(TEST, "break moved below the heap");
+    }
+
+    let mut numbers = Vec::new();
+    for i in 0..ELEMENTS {
+        numbers.push(i);
+    }
+    if numbers.iter().map(|&n| n as u64).sum::<u64>() != (ELEMENTS as u64) * (ELEMENTS as u64 - 1) / 2 {
+        return fail(TEST, "vec");
+    }
+    drop(numbers);
+
+    let mut text = String::new();
+    let mut names = BTreeMap::new();
+    for i in 0..1000 {
+        text.clear();
+        write!(text, "name {}", i).unwrap();
+        names.insert(text.clone(), i);
+    }
+    if names.len() != 1000 || names.get("name 999") != Some(&999) {
+        return fail(TEST, "strings in a map");
+    }
+    println!("Test heap OK!");
+    0
+}
@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr;
+use user_lib::testing::{fail, killed_on, meminfo, page};
+use user_lib::{fork, mmap, munmap, waitpid, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE};
+
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 1024;
+/// Pages touched out of the mapping, far enough apart to share no page table
+const TOUCHED: usize = 16;
+/// Room for the page tables the touched pages need
+const SLACK: usize = 8;
+const TEST: &str = "lazy";
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let Some(before) = meminfo("FramesFree") else {
+        return fail(TEST, "FramesFree in /proc/meminfo");
+    };
+    let base = mmap(0, PAGES * PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, usize::MAX, 0);
+    if base <= 0 {
+        return fail(TEST, "anonymous mmap");
+    }
+    let base = base as usize;
+    let Some(mapped) = meminfo("FramesFree") else {
+        return fail(TEST, "FramesFree after mmap");
+    };
+    if before.abs_diff(mapped) > SLACK {
+        return fail(TEST, "frames taken before the pages are touched");
+    }
+
+    let stride = PAGES / TOUCHED;
+    for i in 0..TOUCHED {
+        unsafe {
+            if ptr::read_volatile(page(base, i * stride)) != 0 {
+                return fail(TEST, "fresh page not zeroed");
+            }
+            ptr::write_volatile(page(base, i * stride), i as u8 + 1);
+        }
+    }
+    let Some(touched) = meminfo("FramesFree") else {
+        return fail(TEST, "FramesFree after touching");
+    };
+    if mapped < touched + TOUCHED || mapped > touched + TOUCHED + SLACK {
+        return fail(TEST, "frames taken by the touched pages");
+    }
+    if (0..TOUCHED).any(|i| unsafe { ptr::read_volatile(page(base, i * stride)) } != i as u8 + 1) {
+        return fail(TEST, "content of the touched pages");
+    }
+
+    // a child sees what was written and gets pages of its own for the rest
+    let pid = fork();
+    if pid == 0 {
+        let ok = unsafe { ptr::read_volatile(page(base, stride)) } == 2
+            && unsafe { ptr::read_volatile(page(base, 1)) } == 0;
+        unsafe { ptr::write_volatile(page(base, 1), 0xff) };
+        user_lib::exit(if ok { 0 } else { 1 });
+    }
+    let mut exit_code = 0;
+    if pid < 0 || waitpid(pid as usize, &mut exit_code) != pid || exit_code != 0 {
+        return fail(TEST, "lazy pages in a child");
+    }
+    if unsafe { ptr::read_volatile(page(base, 1)) } != 0 {
+        return fail(TEST, "write of a child visible to its parent");
+    }
+
+    if munmap(base, PAGES * PAGE_SIZE) != 0 {
+        return fail(TEST, "munmap");
+    }
+    let Some(unmapped) = meminfo("FramesFree") else {
+        return fail(TEST, "FramesFree after munmap");
+    };
+    if unmapped < touched + TOUCHED {
+        return fail(TEST, "frames kept after munmap");
+    }
+    // pages of nothing mapped still kill
+    if !killed_on(page(base, 0) as usize, true) {
+        return fail(TEST, "write to an unmapped page");
+    }
+    println!("Test lazy OK!");
+    0
+}
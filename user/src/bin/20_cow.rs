@@ -0,0 +1,98 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr;
+use user_lib::testing::{fail, meminfo, page};
+use user_lib::{close, fork, mmap, pipe, read, waitpid, write, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE};
+
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 64;
+/// Pages the child writes to, each should cost it one frame
+const WRITTEN: usize = 8;
+/// Page the child reads a pipe into, the kernel has to copy it too
+const READ_INTO: usize = WRITTEN + 1;
+const TEST: &str = "cow";
+
+/// Runs in the child, which writes to a few pages and checks that only those got copied
+fn child(base: usize, pipe_read: usize, free_before: usize, faults_before: usize) -> i32 {
+    // the fork itself costs a page table, a trap context and a kernel stack, not a copy of
+    // every page
+    let Some(free_forked) = meminfo("FramesFree") else {
+        return fail(TEST, "FramesFree in the child");
+    };
+    if free_before.saturating_sub(free_forked) >= PAGES / 2 {
+        return fail(TEST, "pages copied by fork");
+    }
+    for i in 0..WRITTEN {
+        unsafe { ptr::write_volatile(page(base, i), 0xc0 + i as u8) };
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(page(base, READ_INTO), 3) };
+    if read(pipe_read, buf) != 3 || buf != b"abc" {
+        return fail(TEST, "read into a shared page");
+    }
+    let (Some(free_written), Some(faults)) = (meminfo("FramesFree"), meminfo("CowFaults")) else {
+        return fail(TEST, "meminfo after writing");
+    };
+    if free_forked < free_written + WRITTEN + 1 || faults < faults_before + WRITTEN + 1 {
+        return fail(TEST, "pages written by the child not copied");
+    }
+    if (0..WRITTEN).any(|i| unsafe { ptr::read_volatile(page(base, i)) } != 0xc0 + i as u8) {
+        return fail(TEST, "writes of the child");
+    }
+    0
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let base = mmap(0, PAGES * PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, usize::MAX, 0);
+    if base <= 0 {
+        return fail(TEST, "anonymous mmap");
+    }
+    let base = base as usize;
+    for i in 0..PAGES {
+        unsafe { ptr::write_volatile(page(base, i), i as u8) };
+    }
+    let mut fds = [0i32; 2];
+    if pipe(&mut fds) != 0 || write(fds[1] as usize, b"abc") != 3 {
+        return fail(TEST, "pipe");
+    }
+    let (Some(free_before), Some(faults_before)) = (meminfo("FramesFree"), meminfo("CowFaults")) else {
+        return fail(TEST, "meminfo");
+    };
+
+    let pid = fork();
+    if pid == 0 {
+        user_lib::exit(child(base, fds[0] as usize, free_before, faults_before));
+    }
+    let mut exit_code = 0;
+    if pid < 0 || waitpid(pid as usize, &mut exit_code) != pid || exit_code != 0 {
+        return fail(TEST, "child");
+    }
+    close(fds[0] as usize);
+    close(fds[1] as usize);
+    if (0..PAGES).any(|i| unsafe { ptr::read_volatile(page(base, i)) } != i as u8) {
+        return fail(TEST, "writes of the child visible to the parent");
+    }
+
+    // the child is gone, so the pages are the parent's alone again and get no copy
+    let (Some(free_alone), Some(faults_alone)) = (meminfo("FramesFree"), meminfo("CowFaults")) else {
+        return fail(TEST, "meminfo after the child");
+    };
+    for i in 0..PAGES {
+        unsafe { ptr::write_volatile(page(base, i), !(i as u8)) };
+    }
+    let (Some(free_reclaimed), Some(faults_reclaimed)) = (meminfo("FramesFree"), meminfo("CowFaults")) else {
+        return fail(TEST, "meminfo after reclaiming");
+    };
+    if free_alone.abs_diff(free_reclaimed) >= PAGES / 8 || faults_reclaimed < faults_alone + PAGES {
+        return fail(TEST, "pages reclaimed by the parent");
+    }
+    if (0..PAGES).any(|i| unsafe { ptr::read_volatile(page(base, i)) } != !(i as u8)) {
+        return fail(TEST, "writes of the parent");
+    }
+    println!("Test cow OK!");
+    0
+}
@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+use user_lib::testing::fail;
+use user_lib::{fork, mmap, munmap, waitpid, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE};
+
+const PAGE_SIZE: usize = 4096;
+/// Well past the 16 KiB the stack starts with
+const DEPTH: usize = 128;
+const TEST: &str = "stack";
+
+/// Recurse `depth` times with a page worth of locals in each frame, returns the sum of the
+/// depths seen on the way
+fn deep(depth: usize) -> usize {
+    let mut locals = [0u8; PAGE_SIZE];
+    locals[0] = depth as u8;
+    black_box(&mut locals);
+    if depth == 0 {
+        return 0;
+    }
+    locals[0] as usize + deep(depth - 1) + locals[PAGE_SIZE - 1] as usize
+}
+
+/// Recurse until the stack has no room left, `limit` only keeps the compiler from
+/// proving that it never returns
+fn endless(limit: usize) -> usize {
+    let mut locals = [0u8; 1024];
+    black_box(&mut locals);
+    if limit == 0 {
+        return 0;
+    }
+    locals[0] as usize + endless(black_box(limit - 1))
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let expected: usize = (1..=DEPTH).map(|depth| depth as u8 as usize).sum();
+    if deep(DEPTH) != expected {
+        return fail(TEST, "deep recursion");
+    }
+
+    // the room the stack grows into isn't given to mappings
+    let sp = &expected as *const usize as usize & !(PAGE_SIZE - 1);
+    let hint = sp - 64 * PAGE_SIZE;
+    let addr = mmap(hint, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, usize::MAX, 0);
+    if addr <= 0 || addr as usize == hint {
+        return fail(TEST, "mapping placed where the stack grows");
+    }
+    munmap(addr as usize, PAGE_SIZE);
+
+    let pid = fork();
+    if pid == 0 {
+        endless(usize::MAX);
+        user_lib::exit(0);
+    }
+    let mut exit_code = 0;
+    if pid < 0 || waitpid(pid as usize, &mut exit_code) != pid || exit_code != -1 {
+        return fail(TEST, "stack overflow not caught");
+    }
+    println!("Test stack OK!");
+    0
+}
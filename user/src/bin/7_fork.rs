@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, waitpid};
+
+static mut SHARED: usize = 0;
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    unsafe { SHARED = 1; }
+    let pid = fork();
+    if pid == 0 {
+        // the child owns a copy of the parent's memory, changes are invisible to the parent
+        let inherited = unsafe { SHARED };
+        unsafe { SHARED = 2; }
+        println!("I'm the child, SHARED = {}", unsafe { SHARED });
+        exit(if inherited == 1 { 0 } else { 1 });
+    }
+    if pid < 0 {
+        println!("Test fork failed!");
+        return -1;
+    }
+    let mut exit_code = -1;
+    if waitpid(pid as usize, &mut exit_code) != pid || exit_code != 0 || unsafe { SHARED } != 1 {
+        println!("Test fork failed!");
+        return -1;
+    }
+    println!("I'm the parent, forked child {}, SHARED = {}", pid, unsafe { SHARED });
+    println!("Test fork OK!");
+    0
+}
@@ -0,0 +1,23 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exec, fork};
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        exec("1_hello_world");
+        println!("Test exec failed!");
+        return -1;
+    }
+    if exec("no_such_app") != -1 {
+        println!("Test exec failed!");
+        return -1;
+    }
+    println!("Test exec OK!");
+    0
+}
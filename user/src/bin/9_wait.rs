@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, wait, waitpid};
+
+const CHILDREN: usize = 5;
+/// Mapped in every task, but only for the kernel
+const TRAP_CONTEXT: usize = usize::MAX - 2 * 4096 + 1;
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mut pids = [0; CHILDREN];
+    for (i, pid) in pids.iter_mut().enumerate() {
+        let ret = fork();
+        if ret == 0 {
+            exit(100 + i as i32);
+        }
+        *pid = ret as usize;
+    }
+    let mut exit_code = 0;
+    if waitpid(pids[0], &mut exit_code) != pids[0] as isize || exit_code != 100 {
+        println!("Test wait failed!");
+        return -1;
+    }
+    for _ in 1..CHILDREN {
+        let pid = wait(&mut exit_code);
+        let Some(i) = pids.iter().position(|p| *p as isize == pid) else {
+            println!("Test wait failed!");
+            return -1;
+        };
+        if exit_code != 100 + i as i32 {
+            println!("Test wait failed!");
+            return -1;
+        }
+    }
+
+    // a status the task couldn't store itself is refused and the child is kept
+    let pid = fork();
+    if pid == 0 {
+        exit(42);
+    }
+    let trap_context = unsafe { &mut *(TRAP_CONTEXT as *mut i32) };
+    if pid < 0 || waitpid(pid as usize, trap_context) != -1 {
+        println!("Test wait failed!");
+        return -1;
+    }
+    if waitpid(pid as usize, &mut exit_code) != pid || exit_code != 42 {
+        println!("Test wait failed!");
+        return -1;
+    }
+    if wait(&mut exit_code) != -1 {
+        println!("Test wait failed!");
+        return -1;
+    }
+    println!("Test wait OK!");
+    0
+}
@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::{read, write, STDIN, STDOUT};
+
+/// Copy stdin to stdout until end of file
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let mut buf = [0u8; 512];
+    loop {
+        let len = read(STDIN, &mut buf);
+        if len < 0 {
+            return -1;
+        }
+        if len == 0 {
+            return 0;
+        }
+        let mut data = &buf[..len as usize];
+        while !data.is_empty() {
+            let written = write(STDOUT, data);
+            if written <= 0 {
+                return -1;
+            }
+            data = &data[written as usize..];
+        }
+    }
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, read, O_RDONLY};
+
+/// Number in the `key:\tvalue kB` line of meminfo
+fn field(meminfo: &str, key: &str) -> Option<usize> {
+    meminfo.lines()
+        .find_map(|line| line.split_once(":\t").filter(|(k, _)| *k == key))
+        .and_then(|(_, value)| value.trim_end_matches(" kB").parse().ok())
+}
+
+/// Show the memory usage from `/proc/meminfo`
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let fd = open("/proc/meminfo", O_RDONLY);
+    if fd < 0 {
+        println!("free: /proc/meminfo is not available");
+        return -1;
+    }
+    let mut buf = [0u8; 512];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    let Some(meminfo) = core::str::from_utf8(&buf[..len.max(0) as usize]).ok() else {
+        return -1;
+    };
+    let (Some(total), Some(used), Some(free)) =
+        (field(meminfo, "MemTotal"), field(meminfo, "MemUsed"), field(meminfo, "MemFree")) else {
+        println!("free: unexpected /proc/meminfo format");
+        return -1;
+    };
+    println!("{:<6}{:>12}{:>12}{:>12}", "", "total", "used", "free");
+    println!("{:<6}{:>12}{:>12}{:>12}", "Mem:", total, used, free);
+    0
+}
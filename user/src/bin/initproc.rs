@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exec, fork, wait, yield_now};
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    if fork() == 0 {
+        exec("user_shell");
+        println!("[initproc] Failed to start user_shell");
+        return -1;
+    }
+    // orphans are adopted by us, keep reaping them
+    loop {
+        let mut exit_code = 0;
+        let pid = wait(&mut exit_code);
+        if pid == -1 {
+            yield_now();
+            continue;
+        }
+        println!("[initproc] Released a zombie process, pid={}, exit_code={}", pid, exit_code);
+    }
+}
@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dirents, getdents, open, DT_DIR, O_DIRECTORY, O_RDONLY};
+
+/// List the current directory, directories get a trailing `/`
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let fd = open(".", O_RDONLY | O_DIRECTORY);
+    if fd < 0 {
+        println!("ls: cannot open the current directory");
+        return -1;
+    }
+    let mut buf = [0u8; 512];
+    loop {
+        let len = getdents(fd as usize, &mut buf);
+        if len < 0 {
+            println!("ls: failed to read the directory");
+            close(fd as usize);
+            return -1;
+        }
+        if len == 0 {
+            break;
+        }
+        for entry in dirents(&buf[..len as usize]) {
+            if entry.name == "." || entry.name == ".." {
+                continue;
+            }
+            println!("{}{}", entry.name, if entry.kind == DT_DIR { "/" } else { "" });
+        }
+    }
+    close(fd as usize);
+    0
+}
@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dirents, getdents, open, read, O_DIRECTORY, O_RDONLY};
+
+/// Read the whole file at `path` into `buf`, returns the part filled
+fn read_file<'a>(path: &str, buf: &'a mut [u8]) -> Option<&'a str> {
+    let fd = open(path, O_RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut len = 0;
+    while len < buf.len() {
+        let n = read(fd as usize, &mut buf[len..]);
+        if n <= 0 {
+            break;
+        }
+        len += n as usize;
+    }
+    close(fd as usize);
+    core::str::from_utf8(&buf[..len]).ok()
+}
+
+/// Value of the `key:\tvalue` line of a status file
+fn field<'a>(status: &'a str, key: &str) -> &'a str {
+    status.lines()
+        .find_map(|line| line.split_once(":\t").filter(|(k, _)| *k == key).map(|(_, value)| value))
+        .unwrap_or("?")
+}
+
+fn print_task(pid: &str) {
+    let mut path = [0u8; 32];
+    let prefix = b"/proc/";
+    let suffix = b"/status";
+    if prefix.len() + pid.len() + suffix.len() > path.len() {
+        return;
+    }
+    path[..prefix.len()].copy_from_slice(prefix);
+    path[prefix.len()..prefix.len() + pid.len()].copy_from_slice(pid.as_bytes());
+    let end = prefix.len() + pid.len() + suffix.len();
+    path[prefix.len() + pid.len()..end].copy_from_slice(suffix);
+    let mut buf = [0u8; 512];
+    // the task may have been reaped since the directory was listed
+    let Some(status) = read_file(core::str::from_utf8(&path[..end]).unwrap(), &mut buf) else {
+        return;
+    };
+    println!("{:>5} {:>5} {:<12} {:>8} {}", pid, field(status, "PPid"), field(status, "State"),
+             field(status, "VmRSS"), field(status, "Name"));
+}
+
+/// List the tasks from `/proc`
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    let fd = open("/proc", O_RDONLY | O_DIRECTORY);
+    if fd < 0 {
+        println!("ps: /proc is not available");
+        return -1;
+    }
+    println!("{:>5} {:>5} {:<12} {:>8} {}", "PID", "PPID", "STATE", "RSS", "NAME");
+    let mut buf = [0u8; 512];
+    loop {
+        let len = getdents(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        for entry in dirents(&buf[..len as usize]) {
+            if entry.name.bytes().all(|b| b.is_ascii_digit()) {
+                print_task(entry.name);
+            }
+        }
+    }
+    close(fd as usize);
+    0
+}
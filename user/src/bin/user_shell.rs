@@ -0,0 +1,187 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{chdir, close, dup2, exec, exit, fork, getcwd, mkdir, open, pipe, read, rmdir, shutdown, sync, unlink,
+               waitpid, O_CREAT, O_TRUNC, O_WRONLY, STDIN, STDOUT};
+
+const MAX_LINE_LEN: usize = 256;
+const MAX_STAGES: usize = 8;
+const PROMPT: &str = ">> ";
+
+/// One command of a pipeline, with where its output is redirected to
+#[derive(Clone, Copy)]
+struct Stage<'a> {
+    command: &'a str,
+    output: Option<&'a str>
+}
+
+/// Split `a | b > file` into stages
+fn parse<'a>(line: &'a str, stages: &mut [Stage<'a>; MAX_STAGES]) -> Result<usize, &'static str> {
+    let mut count = 0;
+    for part in line.split('|') {
+        if count == MAX_STAGES {
+            return Err("too many commands in the pipeline");
+        }
+        let (command, output) = match part.split_once('>') {
+            Some((command, file)) => (command.trim(), Some(file.trim())),
+            None => (part.trim(), None)
+        };
+        if command.is_empty() || output.is_some_and(|file| file.is_empty() || file.contains('>')) {
+            return Err("syntax error");
+        }
+        stages[count] = Stage { command, output };
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn close_pipes(pipes: &[[i32; 2]]) {
+    for fds in pipes {
+        close(fds[0] as usize);
+        close(fds[1] as usize);
+    }
+}
+
+/// Set up the descriptors of the `index`th stage in the child and run it
+fn exec_stage(stage: &Stage, index: usize, pipes: &[[i32; 2]]) -> ! {
+    if index > 0 {
+        dup2(pipes[index - 1][0] as usize, STDIN);
+    }
+    if index < pipes.len() {
+        dup2(pipes[index][1] as usize, STDOUT);
+    }
+    // the ends are all duplicated to stdin/stdout now, keeping them would hide EOF
+    close_pipes(pipes);
+    if let Some(file) = stage.output {
+        let fd = open(file, O_CREAT | O_TRUNC | O_WRONLY);
+        if fd < 0 {
+            println!("Shell: {}: cannot open", file);
+            exit(-4);
+        }
+        dup2(fd as usize, STDOUT);
+        close(fd as usize);
+    }
+    // arguments can't be passed yet, only the program name is used
+    let name = stage.command.split_whitespace().next().unwrap();
+    // programs are all in the root directory, bare names are looked up there
+    let mut buf = [0u8; MAX_LINE_LEN + 1];
+    let path = if name.contains('/') {
+        name
+    } else {
+        buf[0] = b'/';
+        buf[1..=name.len()].copy_from_slice(name.as_bytes());
+        core::str::from_utf8(&buf[..=name.len()]).unwrap()
+    };
+    exec(path);
+    println!("Shell: {}: command not found", name);
+    exit(-4);
+    unreachable!();
+}
+
+/// Commands changing the state of the shell itself, or with arguments programs can't take yet.
+/// Returns whether `line` was one of them
+fn run_builtin(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap();
+    let arg = words.next();
+    if words.next().is_some() {
+        return false;
+    }
+    let result = match (command, arg) {
+        ("shutdown", None) => {
+            shutdown();
+            -1
+        },
+        ("pwd", None) => {
+            let mut buf = [0u8; MAX_LINE_LEN];
+            let len = getcwd(&mut buf);
+            if len > 0 {
+                println!("{}", core::str::from_utf8(&buf[..len as usize - 1]).unwrap_or("?"));
+            }
+            len
+        },
+        ("cd", Some(dir)) => chdir(dir),
+        ("cd", None) => chdir("/"),
+        ("mkdir", Some(dir)) => mkdir(dir),
+        ("rm", Some(file)) => unlink(file),
+        ("rmdir", Some(dir)) => rmdir(dir),
+        ("sync", None) => sync(),
+        _ => return false
+    };
+    if result < 0 {
+        println!("Shell: {} failed", command);
+    }
+    true
+}
+
+fn run(line: &str) {
+    if !line.contains(['|', '>']) && run_builtin(line) {
+        return;
+    }
+    let mut stages = [Stage { command: "", output: None }; MAX_STAGES];
+    let count = match parse(line, &mut stages) {
+        Ok(count) => count,
+        Err(e) => {
+            println!("Shell: {}", e);
+            return;
+        }
+    };
+    let mut pipes = [[0i32; 2]; MAX_STAGES - 1];
+    for i in 0..count - 1 {
+        if pipe(&mut pipes[i]) != 0 {
+            println!("Shell: failed to create a pipe");
+            close_pipes(&pipes[..i]);
+            return;
+        }
+    }
+    let pipes = &pipes[..count - 1];
+    let mut pids = [0isize; MAX_STAGES];
+    let mut started = 0;
+    for (i, stage) in stages[..count].iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            exec_stage(stage, i, pipes);
+        } else if pid < 0 {
+            println!("Shell: failed to fork");
+            break;
+        }
+        pids[i] = pid;
+        started += 1;
+    }
+    close_pipes(pipes);
+    for &pid in &pids[..started] {
+        let mut exit_code = 0;
+        if waitpid(pid as usize, &mut exit_code) != pid {
+            println!("Shell: failed to wait for process {}", pid);
+            continue;
+        }
+        println!("Shell: Process {} exited with code {}", pid, exit_code);
+    }
+}
+
+#[unsafe(no_mangle)]
+fn main() -> i32 {
+    println!("ChibiMOS user shell, type the name of an app to run it, `a | b` to pipe them, `a > file` to \
+              redirect the output, `cd`, `pwd`, `mkdir`, `rm` and `rmdir` to manage files, `shutdown` to power off");
+    let mut line = [0u8; MAX_LINE_LEN];
+    loop {
+        print!("{}", PROMPT);
+        // the console echoes and handles line editing, we get a whole line at once
+        let len = read(STDIN, &mut line);
+        if len <= 0 {
+            println!("");
+            continue;
+        }
+        let Ok(command) = core::str::from_utf8(&line[..len as usize]) else {
+            println!("Shell: invalid input");
+            continue;
+        };
+        let command = command.trim();
+        if !command.is_empty() {
+            run(command);
+        }
+    }
+}
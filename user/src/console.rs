@@ -1,19 +1,21 @@
 use core::fmt::{self, Write};
 
-use crate::write;
-
-
-const STDOUT: usize = 1;
+use crate::{write, STDOUT};
 
 pub struct Stdout;
 
 impl Write for Stdout{
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        if write(STDOUT, s.as_bytes()) == -1{
-            Err(fmt::Error)
-        }else{
-            Ok(())
+        // stdout may be a pipe, which accepts only what fits in its buffer
+        let mut bytes = s.as_bytes();
+        while !bytes.is_empty() {
+            let written = write(STDOUT, bytes);
+            if written <= 0 {
+                return Err(fmt::Error);
+            }
+            bytes = &bytes[written as usize..];
         }
+        Ok(())
     }
 }
 pub fn print(args: core::fmt::Arguments){
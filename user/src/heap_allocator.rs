@@ -0,0 +1,24 @@
+use core::alloc::Layout;
+
+use buddy_system_allocator::{Heap, LockedHeapWithRescue};
+
+use crate::sbrk;
+
+const HEAP_ORDER: usize = 32;
+/// The heap grows by at least this much at a time, so that small allocations don't each
+/// cost a `brk`
+const MIN_HEAP_GROWTH: usize = 0x10000;
+
+#[global_allocator]
+static HEAP_ALLOCATOR: LockedHeapWithRescue<HEAP_ORDER> = LockedHeapWithRescue::new(grow_heap);
+
+/// Called when the heap can't satisfy `layout`. Buddy blocks are aligned to their size, twice
+/// the block `layout` needs always holds one
+fn grow_heap(heap: &mut Heap<HEAP_ORDER>, layout: &Layout) {
+    let block = layout.size().max(layout.align()).next_power_of_two();
+    let growth = (2 * block).max(MIN_HEAP_GROWTH);
+    let start = sbrk(growth as isize);
+    if start >= 0 {
+        unsafe { heap.add_to_heap(start as usize, start as usize + growth) };
+    }
+}
@@ -1,15 +1,18 @@
 #![no_std]
 #![feature(linkage)]
 
+extern crate alloc;
+
 mod syscall;
 #[macro_use]
 pub mod console;
+pub mod testing;
+mod heap_allocator;
 mod lang_items;
 
 #[unsafe(link_section = ".text.entry")]
 #[unsafe(no_mangle)]
 pub extern "C" fn _start() -> ! {
-    println!("test");
     // clear_bss();
     exit(main());
     panic!("Unreachable after sys_exit");
@@ -34,7 +37,212 @@ fn main() -> i32{
 
 use syscall::*;
 
+const MAX_PATH_LEN: usize = 255;
+pub const WNOHANG: usize = 1;
+pub const STDIN: usize = 0;
+pub const STDOUT: usize = 1;
+pub const STDERR: usize = 2;
+/// `dirfd` meaning the current working directory
+const AT_FDCWD: isize = -100;
+const AT_REMOVEDIR: u32 = 0x200;
+pub const O_RDONLY: u32 = 0;
+pub const O_WRONLY: u32 = 0o1;
+pub const O_RDWR: u32 = 0o2;
+pub const O_CREAT: u32 = 0o100;
+pub const O_TRUNC: u32 = 0o1000;
+pub const O_APPEND: u32 = 0o2000;
+pub const O_DIRECTORY: u32 = 0o200000;
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+pub const PROT_NONE: u32 = 0x0;
+pub const PROT_READ: u32 = 0x1;
+pub const PROT_WRITE: u32 = 0x2;
+pub const PROT_EXEC: u32 = 0x4;
+pub const MAP_SHARED: u32 = 0x01;
+pub const MAP_PRIVATE: u32 = 0x02;
+pub const MAP_FIXED: u32 = 0x10;
+pub const MAP_ANONYMOUS: u32 = 0x20;
+pub const MS_ASYNC: u32 = 0x1;
+pub const MS_INVALIDATE: u32 = 0x2;
+pub const MS_SYNC: u32 = 0x4;
+pub const ENOENT: isize = 2;
+pub const EBADF: isize = 9;
+pub const ENOMEM: isize = 12;
+pub const EACCES: isize = 13;
+pub const EFAULT: isize = 14;
+pub const ENODEV: isize = 19;
+pub const EINVAL: isize = 22;
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+const LINUX_REBOOT_MAGIC1: usize = 0xfee1dead;
+const LINUX_REBOOT_MAGIC2: usize = 672274793;
+const LINUX_REBOOT_CMD_POWER_OFF: usize = 0x4321fedc;
+
+/// `struct stat` of Linux riscv64
+#[repr(C)]
+#[derive(Default)]
+pub struct Stat {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u64,
+    __pad: u64,
+    pub size: i64,
+    pub blksize: i32,
+    __pad2: i32,
+    pub blocks: i64,
+    pub atime_sec: i64,
+    pub atime_nsec: i64,
+    pub mtime_sec: i64,
+    pub mtime_nsec: i64,
+    pub ctime_sec: i64,
+    pub ctime_nsec: i64,
+    __unused: [u32; 2]
+}
+
+/// An entry filled in by `getdents`
+pub struct Dirent<'a> {
+    pub ino: u64,
+    pub kind: u8,
+    pub name: &'a str
+}
+
+/// Walk the `struct linux_dirent64` records `getdents` filled `buf` with
+pub fn dirents(buf: &[u8]) -> impl Iterator<Item = Dirent<'_>> {
+    let mut rest = buf;
+    core::iter::from_fn(move || {
+        if rest.len() < 19 {
+            return None;
+        }
+        let record_len = u16::from_ne_bytes([rest[16], rest[17]]) as usize;
+        if record_len < 19 || record_len > rest.len() {
+            return None;
+        }
+        let (record, next) = rest.split_at(record_len);
+        rest = next;
+        let name = &record[19..];
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+        Some(Dirent {
+            ino: u64::from_ne_bytes(record[..8].try_into().unwrap()),
+            kind: record[18],
+            name: core::str::from_utf8(name).unwrap_or("?")
+        })
+    })
+}
+
+/// The kernel expects nul-terminated paths
+fn c_path(path: &str) -> Option<[u8; MAX_PATH_LEN + 1]> {
+    let mut buf = [0u8; MAX_PATH_LEN + 1];
+    if path.len() > MAX_PATH_LEN {
+        return None;
+    }
+    buf[..path.len()].copy_from_slice(path.as_bytes());
+    Some(buf)
+}
+
+pub fn open(path: &str, flags: u32) -> isize {
+    match c_path(path) {
+        Some(path) => sys_openat(AT_FDCWD, &path, flags, 0o644),
+        None => -1
+    }
+}
+pub fn mkdir(path: &str) -> isize {
+    match c_path(path) {
+        Some(path) => sys_mkdirat(AT_FDCWD, &path, 0o755),
+        None => -1
+    }
+}
+pub fn unlink(path: &str) -> isize {
+    match c_path(path) {
+        Some(path) => sys_unlinkat(AT_FDCWD, &path, 0),
+        None => -1
+    }
+}
+pub fn rmdir(path: &str) -> isize {
+    match c_path(path) {
+        Some(path) => sys_unlinkat(AT_FDCWD, &path, AT_REMOVEDIR),
+        None => -1
+    }
+}
+pub fn chdir(path: &str) -> isize {
+    match c_path(path) {
+        Some(path) => sys_chdir(&path),
+        None => -1
+    }
+}
+/// Returns the length of the nul-terminated path written into `buf`, including the nul
+pub fn getcwd(buf: &mut [u8]) -> isize { sys_getcwd(buf) }
+/// Returns the number of bytes filled with entries, read them with `dirents`
+pub fn getdents(fd: usize, buf: &mut [u8]) -> isize { sys_getdents64(fd, buf) }
+pub fn close(fd: usize) -> isize { sys_close(fd) }
+/// `fds[0]` is the read end and `fds[1]` the write end
+pub fn pipe(fds: &mut [i32; 2]) -> isize { sys_pipe2(fds, 0) }
+pub fn dup(fd: usize) -> isize { sys_dup(fd) }
+/// Make `new_fd` refer to the file of `old_fd`, closing `new_fd` first if it's open
+pub fn dup2(old_fd: usize, new_fd: usize) -> isize {
+    if old_fd == new_fd {
+        // dup3 refuses this, dup2 only checks that the descriptor is valid
+        let mut stat = Stat::default();
+        return if fstat(old_fd, &mut stat) == 0 { new_fd as isize } else { -1 };
+    }
+    sys_dup3(old_fd, new_fd, 0)
+}
+pub fn ftruncate(fd: usize, len: usize) -> isize { sys_ftruncate(fd, len) }
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize { sys_lseek(fd, offset, whence) }
+pub fn fstat(fd: usize, stat: &mut Stat) -> isize { sys_fstat(fd, stat as *mut Stat as *mut u8) }
+/// Write everything the kernel has cached back to the disks
+pub fn sync() -> isize { sys_sync() }
+pub fn read(fd: usize, buf: &mut [u8]) -> isize { sys_read(fd, buf) }
+/// Map `len` bytes of `fd` from `offset`, or zeroed memory with `MAP_ANONYMOUS`. Returns the
+/// address or a negated errno, `addr` is only a hint without `MAP_FIXED`
+pub fn mmap(addr: usize, len: usize, prot: u32, flags: u32, fd: usize, offset: usize) -> isize {
+    sys_mmap(addr, len, prot, flags, fd, offset)
+}
+pub fn munmap(addr: usize, len: usize) -> isize { sys_munmap(addr, len) }
+/// Move the program break to `addr`, returns the break after the call
+pub fn brk(addr: usize) -> isize { sys_brk(addr) }
+/// Move the program break by `increment`, returns the previous break or -1
+pub fn sbrk(increment: isize) -> isize {
+    let old = sys_brk(0);
+    if increment == 0 {
+        return old;
+    }
+    let new = (old as usize).wrapping_add_signed(increment);
+    if sys_brk(new) as usize != new {
+        return -1;
+    }
+    old
+}
+pub fn mprotect(addr: usize, len: usize, prot: u32) -> isize { sys_mprotect(addr, len, prot) }
+pub fn msync(addr: usize, len: usize, flags: u32) -> isize { sys_msync(addr, len, flags) }
 pub fn write(fd: usize, buf: &[u8]) -> isize { sys_write(fd, buf) }
 pub fn exit(exit_code: i32) -> isize { sys_exit(exit_code) }
 pub fn yield_now() -> isize{ sys_yield() }
-pub fn get_time_us() -> isize{ sys_get_time() }
\ No newline at end of file
+pub fn get_time_us() -> isize{ sys_get_time() }
+pub fn fork() -> isize{ sys_fork() }
+pub fn exec(path: &str) -> isize{
+    match c_path(path) {
+        Some(path) => sys_exec(&path),
+        None => -1
+    }
+}
+/// Wait for any child to exit, returns its pid
+pub fn wait(exit_code: &mut i32) -> isize{ sys_waitpid(-1, exit_code as *mut _, 0) }
+pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize{ sys_waitpid(pid as isize, exit_code as *mut _, 0) }
+pub fn waitpid_nohang(pid: isize, exit_code: &mut i32) -> isize{ sys_waitpid(pid, exit_code as *mut _, WNOHANG) }
+/// Block until a byte is available on stdin, console input becomes available line by line
+pub fn getchar() -> u8{
+    let mut c = [0u8; 1];
+    read(STDIN, &mut c);
+    c[0]
+}
+pub fn shutdown() -> isize{ sys_reboot(LINUX_REBOOT_MAGIC1, LINUX_REBOOT_MAGIC2, LINUX_REBOOT_CMD_POWER_OFF) }
@@ -1,9 +1,33 @@
 use core::arch::asm;
 
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_DUP3: usize = 24;
+const SYSCALL_MKDIRAT: usize = 34;
+const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_FTRUNCATE: usize = 46;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_OPENAT: usize = 56;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE2: usize = 59;
+const SYSCALL_GETDENTS64: usize = 61;
+const SYSCALL_LSEEK: usize = 62;
+const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_SYNC: usize = 81;
 const SYSCALL_EXIT: usize = 93;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_REBOOT: usize = 142;
 const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
+const SYSCALL_FORK: usize = 220;
+const SYSCALL_EXEC: usize = 221;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_MSYNC: usize = 227;
+const SYSCALL_WAITPID: usize = 260;
 
 fn syscall(id: usize, args: [usize;3]) -> isize {
     let mut ret: isize;
@@ -18,10 +42,86 @@ fn syscall(id: usize, args: [usize;3]) -> isize {
     ret
 }
 
+fn syscall6(id: usize, args: [usize;6]) -> isize {
+    let mut ret: isize;
+    unsafe{
+        asm!("ecall",
+            inlateout("x10") args[0] => ret,
+            in("x11") args[1],
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x15") args[5],
+            in("x17") id
+        );
+    }
+    ret
+}
+
+pub fn sys_getcwd(buf: &mut [u8]) -> isize{
+    syscall(SYSCALL_GETCWD, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+pub fn sys_mkdirat(dirfd: isize, path: &[u8], mode: u32) -> isize{
+    syscall(SYSCALL_MKDIRAT, [dirfd as usize, path.as_ptr() as usize, mode as usize])
+}
+
+pub fn sys_unlinkat(dirfd: isize, path: &[u8], flags: u32) -> isize{
+    syscall(SYSCALL_UNLINKAT, [dirfd as usize, path.as_ptr() as usize, flags as usize])
+}
+
+pub fn sys_ftruncate(fd: usize, len: usize) -> isize{
+    syscall(SYSCALL_FTRUNCATE, [fd, len, 0])
+}
+
+pub fn sys_chdir(path: &[u8]) -> isize{
+    syscall(SYSCALL_CHDIR, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_openat(dirfd: isize, path: &[u8], flags: u32, mode: u32) -> isize{
+    syscall6(SYSCALL_OPENAT, [dirfd as usize, path.as_ptr() as usize, flags as usize, mode as usize, 0, 0])
+}
+
+pub fn sys_close(fd: usize) -> isize{
+    syscall(SYSCALL_CLOSE, [fd, 0, 0])
+}
+
+pub fn sys_pipe2(fds: &mut [i32; 2], flags: u32) -> isize{
+    syscall(SYSCALL_PIPE2, [fds.as_mut_ptr() as usize, flags as usize, 0])
+}
+
+pub fn sys_dup(fd: usize) -> isize{
+    syscall(SYSCALL_DUP, [fd, 0, 0])
+}
+
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize{
+    syscall(SYSCALL_DUP3, [old_fd, new_fd, flags as usize])
+}
+
+pub fn sys_getdents64(fd: usize, buf: &mut [u8]) -> isize{
+    syscall(SYSCALL_GETDENTS64, [fd, buf.as_mut_ptr() as usize, buf.len()])
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize{
+    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence])
+}
+
+pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize{
+    syscall(SYSCALL_READ, [fd, buffer.as_mut_ptr() as usize, buffer.len()])
+}
+
 pub fn sys_write(fd: usize, buffer: &[u8]) -> isize{
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
+pub fn sys_fstat(fd: usize, stat: *mut u8) -> isize{
+    syscall(SYSCALL_FSTAT, [fd, stat as usize, 0])
+}
+
+pub fn sys_sync() -> isize{
+    syscall(SYSCALL_SYNC, [0, 0, 0])
+}
+
 pub fn sys_exit(xstate: i32) -> isize{
     syscall(SYSCALL_EXIT, [xstate as usize, 0, 0])
 }
@@ -30,6 +130,42 @@ pub fn sys_yield() -> isize{
     syscall(SYSCALL_YIELD, [0,0,0])
 }
 
+pub fn sys_reboot(magic1: usize, magic2: usize, cmd: usize) -> isize{
+    syscall(SYSCALL_REBOOT, [magic1, magic2, cmd])
+}
+
 pub fn sys_get_time() -> isize{
     syscall(SYSCALL_GET_TIME, [0,0,0])
 }
+
+pub fn sys_brk(addr: usize) -> isize{
+    syscall(SYSCALL_BRK, [addr, 0, 0])
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> isize{
+    syscall(SYSCALL_MUNMAP, [addr, len, 0])
+}
+
+pub fn sys_mmap(addr: usize, len: usize, prot: u32, flags: u32, fd: usize, offset: usize) -> isize{
+    syscall6(SYSCALL_MMAP, [addr, len, prot as usize, flags as usize, fd, offset])
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: u32) -> isize{
+    syscall(SYSCALL_MPROTECT, [addr, len, prot as usize])
+}
+
+pub fn sys_msync(addr: usize, len: usize, flags: u32) -> isize{
+    syscall(SYSCALL_MSYNC, [addr, len, flags as usize])
+}
+
+pub fn sys_fork() -> isize{
+    syscall(SYSCALL_FORK, [0,0,0])
+}
+
+pub fn sys_exec(path: &[u8]) -> isize{
+    syscall(SYSCALL_EXEC, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_waitpid(pid: isize, exit_code: *mut i32, options: usize) -> isize{
+    syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, options])
+}
@@ -0,0 +1,51 @@
+//! Helpers shared by the test programs
+
+use core::ptr;
+
+use crate::{close, exit, fork, open, read, waitpid, O_RDONLY};
+
+const PAGE_SIZE: usize = 4096;
+
+/// Report that `test` failed because of `reason`, returns the exit code of a failed test
+pub fn fail(test: &str, reason: &str) -> i32 {
+    println!("Test {} failed: {}", test, reason);
+    -1
+}
+
+/// Start of page `index` of the memory at `base`
+pub fn page(base: usize, index: usize) -> *mut u8 {
+    (base + index * PAGE_SIZE) as *mut u8
+}
+
+/// Whether a child reading `addr`, or writing to it if `write`, gets killed
+pub fn killed_on(addr: usize, write: bool) -> bool {
+    let pid = fork();
+    if pid == 0 {
+        let addr = addr as *mut u8;
+        unsafe {
+            if write {
+                ptr::write_volatile(addr, 1);
+            } else {
+                ptr::read_volatile(addr);
+            }
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    pid > 0 && waitpid(pid as usize, &mut exit_code) == pid && exit_code == -1
+}
+
+/// Number in the `key:\tvalue` line of /proc/meminfo
+pub fn meminfo(key: &str) -> Option<usize> {
+    let fd = open("/proc/meminfo", O_RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut buf = [0u8; 512];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    let meminfo = core::str::from_utf8(&buf[..len.max(0) as usize]).ok()?;
+    meminfo.lines()
+        .find_map(|line| line.split_once(":\t").filter(|(k, _)| *k == key))
+        .and_then(|(_, value)| value.trim().parse().ok())
+}